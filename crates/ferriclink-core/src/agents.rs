@@ -0,0 +1,812 @@
+//! Agent abstractions for FerricLink Core
+//!
+//! This module provides [`AgentExecutor`], which runs the tool-calling loop of
+//! an agent: the chat model is called with the tools bound, the tool calls it
+//! returns are executed, their results are sent back as tool messages, and the
+//! loop repeats until the model gives a final answer or a limit is hit.
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::errors::{FerricLinkError, OutputParserException, Result};
+use crate::impl_serializable;
+use crate::language_models::{BaseChatModel, GenerationConfig};
+use crate::messages::{AnyMessage, BaseMessage, ContentBlock, MessageContent};
+use crate::runnables::{Runnable, RunnableConfig, run_with_callbacks, to_callback_value};
+use crate::tools::{ToolCall, ToolCollection};
+
+/// Default maximum number of model calls of an agent run
+pub const DEFAULT_MAX_ITERATIONS: usize = 15;
+
+/// Final answer returned when an agent is stopped with [`EarlyStoppingMethod::Force`]
+pub const FORCE_STOP_MESSAGE: &str = "Agent stopped due to iteration limit or time limit.";
+
+/// What an agent does when it runs out of iterations or time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EarlyStoppingMethod {
+    /// Return a fixed message
+    #[default]
+    Force,
+    /// Ask the model for a final answer without tools
+    Generate,
+}
+
+/// How an agent handles model output whose tool calls cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ParsingErrorHandling {
+    /// Fail the run
+    #[default]
+    Raise,
+    /// Send the parsing error back to the model and let it retry
+    Feedback,
+    /// Send a fixed message back to the model and let it retry
+    Message(String),
+}
+
+/// A tool call executed by an agent, with its observation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentStep {
+    /// The tool call made by the model
+    pub tool_call: ToolCall,
+    /// The result of the tool, or the error it failed with
+    pub observation: String,
+    /// Whether the tool failed
+    pub is_error: bool,
+}
+
+impl_serializable!(AgentStep, ["ferriclink", "agents", "agent_step"]);
+
+/// The result of an agent run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentOutput {
+    /// The final answer
+    pub output: String,
+    /// The full conversation, including the input and tool messages
+    pub messages: Vec<AnyMessage>,
+    /// The tool calls executed, in order
+    pub intermediate_steps: Vec<AgentStep>,
+    /// Number of model calls made
+    pub iterations: usize,
+    /// Whether the run hit the iteration or time limit
+    pub stopped_early: bool,
+}
+
+impl_serializable!(AgentOutput, ["ferriclink", "agents", "agent_output"]);
+
+/// Extract the tool calls of an AI message
+///
+/// Tool calls are read from [`ContentBlock::ToolCall`] blocks and from
+/// OpenAI-style `tool_calls` in the additional kwargs, whose `arguments` may
+/// be a JSON-encoded string. Other messages have no tool calls.
+pub fn parse_tool_calls(message: &AnyMessage) -> Result<Vec<ToolCall>> {
+    let AnyMessage::AI(message) = message else {
+        return Ok(Vec::new());
+    };
+
+    let mut calls = Vec::new();
+    if let MessageContent::Blocks(blocks) = &message.content {
+        for block in blocks {
+            if let ContentBlock::ToolCall { id, name, args } = block {
+                calls.push(ToolCall::new_with_args(id, name, args.clone()));
+            }
+        }
+    }
+
+    let Some(raw_calls) = message.additional_kwargs.get("tool_calls") else {
+        return Ok(calls);
+    };
+    let parse_error = |reason: String| {
+        let llm_output = raw_calls.to_string();
+        FerricLinkError::from(OutputParserException::with_llm_context(
+            format!("Could not parse tool calls: {reason}"),
+            Some(format!(
+                "Invalid tool call format: {reason}. Call the tools with valid JSON arguments."
+            )),
+            Some(llm_output),
+            true,
+        ))
+    };
+
+    let raw_calls = raw_calls
+        .as_array()
+        .ok_or_else(|| parse_error("tool_calls is not an array".to_string()))?;
+    for (index, raw) in raw_calls.iter().enumerate() {
+        let function = raw.get("function").unwrap_or(raw);
+        let name = function
+            .get("name")
+            .and_then(|name| name.as_str())
+            .ok_or_else(|| parse_error(format!("tool call {index} has no name")))?;
+        let id = raw
+            .get("id")
+            .and_then(|id| id.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("call_{index}"));
+        let args = match function.get("arguments").or_else(|| function.get("args")) {
+            None | Some(serde_json::Value::Null) => HashMap::new(),
+            Some(serde_json::Value::String(encoded)) if encoded.trim().is_empty() => HashMap::new(),
+            Some(serde_json::Value::String(encoded)) => {
+                serde_json::from_str(encoded).map_err(|e| {
+                    parse_error(format!("arguments of '{name}' are not valid JSON: {e}"))
+                })?
+            }
+            Some(value) => serde_json::from_value(value.clone()).map_err(|e| {
+                parse_error(format!("arguments of '{name}' are not an object: {e}"))
+            })?,
+        };
+        calls.push(ToolCall::new_with_args(id, name, args));
+    }
+    Ok(calls)
+}
+
+/// Runs a tool-calling chat model in a loop until it gives a final answer
+///
+/// Every iteration calls the model with the schemas of the tools bound in the
+/// generation config (`extra["tools"]`). Tool calls returned by the model are
+/// executed concurrently, bounded by `max_concurrency` of the run config, and
+/// their results are appended as tool messages.
+pub struct AgentExecutor {
+    model: Arc<dyn BaseChatModel>,
+    tools: ToolCollection,
+    generation_config: GenerationConfig,
+    max_iterations: usize,
+    max_execution_time: Option<Duration>,
+    early_stopping_method: EarlyStoppingMethod,
+    parsing_error_handling: ParsingErrorHandling,
+    handle_tool_errors: bool,
+}
+
+impl AgentExecutor {
+    /// Create a new agent executor
+    pub fn new(model: Arc<dyn BaseChatModel>, tools: ToolCollection) -> Self {
+        Self {
+            model,
+            tools,
+            generation_config: GenerationConfig::default(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            max_execution_time: None,
+            early_stopping_method: EarlyStoppingMethod::default(),
+            parsing_error_handling: ParsingErrorHandling::default(),
+            handle_tool_errors: true,
+        }
+    }
+
+    /// Set the generation config used for every model call
+    pub fn with_generation_config(mut self, config: GenerationConfig) -> Self {
+        self.generation_config = config;
+        self
+    }
+
+    /// Set the maximum number of model calls
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Set the time budget of a run
+    ///
+    /// The step in progress when the budget runs out is interrupted, and its
+    /// tool calls are left out of the messages.
+    pub fn with_max_execution_time(mut self, max_execution_time: Duration) -> Self {
+        self.max_execution_time = Some(max_execution_time);
+        self
+    }
+
+    /// Set what happens when the run hits the iteration or time limit
+    pub fn with_early_stopping_method(mut self, method: EarlyStoppingMethod) -> Self {
+        self.early_stopping_method = method;
+        self
+    }
+
+    /// Set how unparsable tool calls are handled
+    pub fn with_parsing_error_handling(mut self, handling: ParsingErrorHandling) -> Self {
+        self.parsing_error_handling = handling;
+        self
+    }
+
+    /// Set whether tool errors are sent to the model instead of failing the run
+    pub fn with_handle_tool_errors(mut self, handle_tool_errors: bool) -> Self {
+        self.handle_tool_errors = handle_tool_errors;
+        self
+    }
+
+    /// Get the tools available to the agent
+    pub fn tools(&self) -> &ToolCollection {
+        &self.tools
+    }
+
+    /// Run the agent on a single user question
+    pub async fn run(&self, input: impl Into<String>) -> Result<AgentOutput> {
+        self.invoke(vec![AnyMessage::human(input)], None).await
+    }
+
+    /// Call the chat model as a traced child run
+    async fn call_model(
+        &self,
+        messages: Vec<AnyMessage>,
+        generation_config: GenerationConfig,
+        config: &RunnableConfig,
+    ) -> Result<AnyMessage> {
+        run_with_callbacks(
+            Some(config.child()),
+            self.model.model_name(),
+            "chat_model",
+            to_callback_value(&messages),
+            to_callback_value,
+            |run_config| async move {
+                let call = self.model.generate_chat(
+                    messages,
+                    Some(generation_config),
+                    Some(run_config.clone()),
+                );
+                run_config.run_cancellable(call).await
+            },
+        )
+        .await
+    }
+
+    /// Execute tool calls concurrently, keeping their order
+    async fn call_tools(
+        &self,
+        calls: Vec<ToolCall>,
+        config: &RunnableConfig,
+    ) -> Result<Vec<AgentStep>> {
+        let limit = config.max_concurrency.unwrap_or(calls.len()).max(1);
+        let steps = futures::stream::iter(calls)
+            .map(|call| async move {
+                let result = match self.tools.get_tool(&call.name) {
+                    Some(_) => {
+                        self.tools
+                            .invoke_tool(&call.name, call.args.clone(), Some(config.child()))
+                            .await
+                    }
+                    None => Err(FerricLinkError::validation(format!(
+                        "Tool '{}' not found, available tools: [{}]",
+                        call.name,
+                        self.tool_names().join(", ")
+                    ))),
+                };
+                match result {
+                    Ok(result) => Ok(AgentStep {
+                        tool_call: call,
+                        observation: result.content,
+                        is_error: false,
+                    }),
+                    Err(error) if error.is_timeout() || error.is_cancelled() => Err(error),
+                    Err(error) if self.handle_tool_errors => Ok(AgentStep {
+                        tool_call: call,
+                        observation: format!("Error: {error}"),
+                        is_error: true,
+                    }),
+                    Err(error) => Err(error),
+                }
+            })
+            .buffered(limit)
+            .collect::<Vec<_>>()
+            .await;
+        steps.into_iter().collect()
+    }
+
+    fn tool_names(&self) -> Vec<&str> {
+        let mut names = self.tools.tool_names();
+        names.sort_unstable();
+        names
+    }
+
+    /// Run iterations until a final answer, returning `None` if the limits were hit
+    async fn iterate(
+        &self,
+        messages: &mut Vec<AnyMessage>,
+        steps: &mut Vec<AgentStep>,
+        iterations: &mut usize,
+        config: &RunnableConfig,
+    ) -> Result<Option<String>> {
+        let generation_config = self
+            .generation_config
+            .clone()
+            .with_tools(self.tools.schemas());
+
+        while *iterations < self.max_iterations {
+            config.check_cancelled()?;
+            *iterations += 1;
+
+            let reply = self
+                .call_model(messages.clone(), generation_config.clone(), config)
+                .await?;
+            let calls = match parse_tool_calls(&reply) {
+                Ok(calls) => calls,
+                Err(FerricLinkError::OutputParser(error)) => {
+                    let feedback = match &self.parsing_error_handling {
+                        ParsingErrorHandling::Raise => {
+                            return Err(FerricLinkError::OutputParser(error));
+                        }
+                        ParsingErrorHandling::Feedback => error
+                            .observation()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| error.message.clone()),
+                        ParsingErrorHandling::Message(message) => message.clone(),
+                    };
+                    messages.push(AnyMessage::ai(reply.text()));
+                    messages.push(AnyMessage::human(feedback));
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            if calls.is_empty() {
+                let answer = reply.text();
+                messages.push(reply);
+                return Ok(Some(answer));
+            }
+
+            // The tool calls are only recorded with their results, so that an
+            // interrupted step leaves a history the model accepts
+            let tool_steps = self.call_tools(calls, config).await?;
+            messages.push(reply);
+            for step in tool_steps {
+                let mut message = crate::messages::ToolMessage::new(
+                    step.observation.clone(),
+                    step.tool_call.id.clone(),
+                );
+                message.name = Some(step.tool_call.name.clone());
+                messages.push(AnyMessage::Tool(message));
+                steps.push(step);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Produce the final answer of a run that hit its limits
+    async fn stop_early(
+        &self,
+        messages: &mut Vec<AnyMessage>,
+        config: &RunnableConfig,
+    ) -> Result<String> {
+        match self.early_stopping_method {
+            EarlyStoppingMethod::Force => Ok(FORCE_STOP_MESSAGE.to_string()),
+            EarlyStoppingMethod::Generate => {
+                messages.push(AnyMessage::human(
+                    "You have run out of steps. Give your final answer using the information gathered so far, without calling tools.",
+                ));
+                let reply = self
+                    .call_model(messages.clone(), self.generation_config.clone(), config)
+                    .await?;
+                messages.push(reply.clone());
+                Ok(reply.text())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Runnable<Vec<AnyMessage>, AgentOutput> for AgentExecutor {
+    async fn invoke(
+        &self,
+        input: Vec<AnyMessage>,
+        config: Option<RunnableConfig>,
+    ) -> Result<AgentOutput> {
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            to_callback_value(&input),
+            |output: &AgentOutput| serde_json::Value::String(output.output.clone()),
+            |run_config| async move {
+                let mut messages = input;
+                let mut steps = Vec::new();
+                let mut iterations = 0;
+
+                // The time budget is a deadline, so that model and tool runs
+                // stop cleanly and report their cancellation
+                let loop_config = match self.max_execution_time {
+                    Some(budget) => run_config.clone().with_timeout(budget),
+                    None => run_config.clone(),
+                };
+                let answer = match self
+                    .iterate(&mut messages, &mut steps, &mut iterations, &loop_config)
+                    .await
+                {
+                    Err(error)
+                        if error.is_timeout()
+                            && loop_config.is_cancelled()
+                            && !run_config.is_cancelled() =>
+                    {
+                        None
+                    }
+                    result => result?,
+                };
+
+                let stopped_early = answer.is_none();
+                let output = match answer {
+                    Some(output) => output,
+                    None => self.stop_early(&mut messages, &run_config).await?,
+                };
+                Ok(AgentOutput {
+                    output,
+                    messages,
+                    intermediate_steps: steps,
+                    iterations,
+                    stopped_early,
+                })
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language_models::BaseLanguageModel;
+    use crate::messages::AIMessage;
+    use crate::tools::{BaseTool, Tool, ToolResult, ToolSchema, function_tool};
+    use std::sync::Mutex;
+
+    /// A chat model replaying scripted replies and recording its calls
+    struct ScriptedChatModel {
+        replies: Mutex<Vec<AnyMessage>>,
+        configs: Mutex<Vec<GenerationConfig>>,
+        delay: Duration,
+    }
+
+    impl ScriptedChatModel {
+        fn new(replies: Vec<AnyMessage>) -> Self {
+            Self {
+                replies: Mutex::new(replies),
+                configs: Mutex::new(Vec::new()),
+                delay: Duration::ZERO,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl BaseLanguageModel for ScriptedChatModel {
+        fn model_name(&self) -> &str {
+            "scripted"
+        }
+
+        fn model_type(&self) -> &str {
+            "scripted_chat_model"
+        }
+    }
+
+    #[async_trait]
+    impl BaseChatModel for ScriptedChatModel {
+        async fn generate_chat(
+            &self,
+            _messages: Vec<AnyMessage>,
+            config: Option<GenerationConfig>,
+            _runnable_config: Option<RunnableConfig>,
+        ) -> Result<AnyMessage> {
+            tokio::time::sleep(self.delay).await;
+            self.configs
+                .lock()
+                .unwrap()
+                .push(config.unwrap_or_default());
+            let mut replies = self.replies.lock().unwrap();
+            if replies.len() > 1 {
+                Ok(replies.remove(0))
+            } else {
+                Ok(replies[0].clone())
+            }
+        }
+    }
+
+    /// A tool that sleeps before answering
+    struct SlowTool;
+
+    impl BaseTool for SlowTool {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn description(&self) -> &str {
+            "Waits a bit"
+        }
+
+        fn schema(&self) -> ToolSchema {
+            ToolSchema::new("slow", "Waits a bit")
+        }
+    }
+
+    #[async_trait]
+    impl Tool for SlowTool {
+        async fn invoke(
+            &self,
+            _input: HashMap<String, serde_json::Value>,
+            _config: Option<RunnableConfig>,
+        ) -> Result<ToolResult> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(ToolResult::new("", "done"))
+        }
+    }
+
+    fn tool_call(id: &str, name: &str, args: serde_json::Value) -> ContentBlock {
+        ContentBlock::ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            args: serde_json::from_value(args).unwrap(),
+        }
+    }
+
+    fn calls(blocks: Vec<ContentBlock>) -> AnyMessage {
+        AnyMessage::AI(AIMessage::new_with_blocks(blocks))
+    }
+
+    fn math_tools() -> ToolCollection {
+        let mut tools = ToolCollection::new();
+        tools.add_tool(function_tool("add", "Add two numbers", |args| {
+            let a = args["a"].as_f64().unwrap_or_default();
+            let b = args["b"].as_f64().unwrap_or_default();
+            Ok((a + b).to_string())
+        }));
+        tools.add_tool(function_tool("fail", "Always fails", |_| {
+            Err(FerricLinkError::runtime("boom"))
+        }));
+        tools
+    }
+
+    #[tokio::test]
+    async fn test_agent_executor_tool_loop() {
+        let model = Arc::new(ScriptedChatModel::new(vec![
+            calls(vec![
+                tool_call("1", "add", serde_json::json!({"a": 1, "b": 2})),
+                tool_call("2", "add", serde_json::json!({"a": 3, "b": 4})),
+            ]),
+            AnyMessage::ai("The sums are 3 and 7"),
+        ]));
+        let agent = AgentExecutor::new(model.clone(), math_tools());
+
+        let output = agent.run("Add 1+2 and 3+4").await.unwrap();
+        assert_eq!(output.output, "The sums are 3 and 7");
+        assert_eq!(output.iterations, 2);
+        assert!(!output.stopped_early);
+
+        let observations: Vec<_> = output
+            .intermediate_steps
+            .iter()
+            .map(|step| (step.tool_call.id.as_str(), step.observation.as_str()))
+            .collect();
+        assert_eq!(observations, vec![("1", "3"), ("2", "7")]);
+
+        let types: Vec<_> = output.messages.iter().map(|m| m.message_type()).collect();
+        assert_eq!(types, vec!["human", "ai", "tool", "tool", "ai"]);
+        let AnyMessage::Tool(tool_message) = &output.messages[2] else {
+            panic!("expected a tool message");
+        };
+        assert_eq!(tool_message.tool_call_id, "1");
+        assert_eq!(tool_message.name.as_deref(), Some("add"));
+
+        // The tools are bound on every call
+        let configs = model.configs.lock().unwrap();
+        let names: Vec<_> = configs[0].tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["add", "fail"]);
+    }
+
+    #[tokio::test]
+    async fn test_agent_executor_concurrent_tools() {
+        let model = Arc::new(ScriptedChatModel::new(vec![
+            calls(vec![
+                tool_call("1", "slow", serde_json::json!({})),
+                tool_call("2", "slow", serde_json::json!({})),
+                tool_call("3", "slow", serde_json::json!({})),
+            ]),
+            AnyMessage::ai("done"),
+        ]));
+        let mut tools = ToolCollection::new();
+        tools.add_tool(SlowTool);
+        let agent = AgentExecutor::new(model, tools);
+
+        let start = std::time::Instant::now();
+        let output = agent.run("go").await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(250));
+        assert_eq!(output.intermediate_steps.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_agent_executor_early_stopping() {
+        let looping = || {
+            Arc::new(ScriptedChatModel::new(vec![calls(vec![tool_call(
+                "1",
+                "add",
+                serde_json::json!({"a": 1, "b": 1}),
+            )])]))
+        };
+
+        let agent = AgentExecutor::new(looping(), math_tools()).with_max_iterations(2);
+        let output = agent.run("loop").await.unwrap();
+        assert!(output.stopped_early);
+        assert_eq!(output.iterations, 2);
+        assert_eq!(output.output, FORCE_STOP_MESSAGE);
+        assert_eq!(output.intermediate_steps.len(), 2);
+
+        let model = Arc::new(ScriptedChatModel::new(vec![
+            calls(vec![tool_call(
+                "1",
+                "add",
+                serde_json::json!({"a": 1, "b": 1}),
+            )]),
+            AnyMessage::ai("Best guess: 2"),
+        ]));
+        let agent = AgentExecutor::new(model.clone(), math_tools())
+            .with_max_iterations(1)
+            .with_early_stopping_method(EarlyStoppingMethod::Generate);
+        let output = agent.run("loop").await.unwrap();
+        assert!(output.stopped_early);
+        assert_eq!(output.output, "Best guess: 2");
+        // The final answer is generated without tools
+        assert!(model.configs.lock().unwrap()[1].tools().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_agent_executor_time_budget() {
+        let mut model = ScriptedChatModel::new(vec![calls(vec![tool_call(
+            "1",
+            "add",
+            serde_json::json!({"a": 1, "b": 1}),
+        )])]);
+        model.delay = Duration::from_millis(30);
+        let agent = AgentExecutor::new(Arc::new(model), math_tools())
+            .with_max_execution_time(Duration::from_millis(100));
+
+        let output = agent.run("loop").await.unwrap();
+        assert!(output.stopped_early);
+        assert!(output.iterations < DEFAULT_MAX_ITERATIONS);
+
+        // A budget too long to represent does not limit the run
+        let model = ScriptedChatModel::new(vec![AnyMessage::ai("2")]);
+        let agent = AgentExecutor::new(Arc::new(model), math_tools())
+            .with_max_execution_time(Duration::MAX);
+        let output = agent.run("1 + 1").await.unwrap();
+        assert!(!output.stopped_early);
+        assert_eq!(output.output, "2");
+    }
+
+    #[tokio::test]
+    async fn test_agent_executor_time_budget_generate() {
+        struct CancelCounter {
+            cancelled: Arc<Mutex<Vec<String>>>,
+        }
+
+        #[async_trait]
+        impl crate::callbacks::CallbackHandler for CancelCounter {
+            async fn on_run_cancel(&self, run_info: &crate::callbacks::RunInfo) -> Result<()> {
+                self.cancelled.lock().unwrap().push(run_info.name.clone());
+                Ok(())
+            }
+        }
+
+        let model = Arc::new(ScriptedChatModel::new(vec![
+            calls(vec![tool_call("1", "slow", serde_json::json!({}))]),
+            calls(vec![tool_call("2", "slow", serde_json::json!({}))]),
+            AnyMessage::ai("Best guess"),
+        ]));
+        let mut tools = ToolCollection::new();
+        tools.add_tool(SlowTool);
+        // The budget runs out while the second tool call is executing
+        let agent = AgentExecutor::new(model, tools)
+            .with_max_execution_time(Duration::from_millis(150))
+            .with_early_stopping_method(EarlyStoppingMethod::Generate);
+
+        let cancelled = Arc::new(Mutex::new(Vec::new()));
+        let config = RunnableConfig::new().with_callback(Arc::new(CancelCounter {
+            cancelled: cancelled.clone(),
+        }));
+        let output = agent
+            .invoke(vec![AnyMessage::human("go")], Some(config))
+            .await
+            .unwrap();
+        assert!(output.stopped_early);
+        assert_eq!(output.output, "Best guess");
+        assert_eq!(output.intermediate_steps.len(), 1);
+
+        // Every tool call is followed by its result
+        let types: Vec<_> = output.messages.iter().map(|m| m.message_type()).collect();
+        assert_eq!(types, vec!["human", "ai", "tool", "human", "ai"]);
+        let AnyMessage::Tool(tool_message) = &output.messages[2] else {
+            panic!("expected a tool message");
+        };
+        assert_eq!(tool_message.tool_call_id, "1");
+
+        // The interrupted tool run is reported as cancelled
+        assert_eq!(*cancelled.lock().unwrap(), vec!["slow".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_agent_executor_tool_errors() {
+        let model = || {
+            Arc::new(ScriptedChatModel::new(vec![
+                calls(vec![
+                    tool_call("1", "fail", serde_json::json!({})),
+                    tool_call("2", "missing", serde_json::json!({})),
+                ]),
+                AnyMessage::ai("Sorry"),
+            ]))
+        };
+
+        let output = AgentExecutor::new(model(), math_tools())
+            .run("try")
+            .await
+            .unwrap();
+        assert!(output.intermediate_steps.iter().all(|step| step.is_error));
+        assert!(output.intermediate_steps[0].observation.contains("boom"));
+        assert!(
+            output.intermediate_steps[1]
+                .observation
+                .contains("not found")
+        );
+
+        let result = AgentExecutor::new(model(), math_tools())
+            .with_handle_tool_errors(false)
+            .run("try")
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_agent_executor_parsing_errors() {
+        let mut malformed = AIMessage::new("");
+        malformed.additional_kwargs.insert(
+            "tool_calls".to_string(),
+            serde_json::json!([{"id": "1", "function": {"name": "add", "arguments": "{not json"}}]),
+        );
+        let model = || {
+            Arc::new(ScriptedChatModel::new(vec![
+                AnyMessage::AI(malformed.clone()),
+                AnyMessage::ai("Recovered"),
+            ]))
+        };
+
+        let error = AgentExecutor::new(model(), math_tools())
+            .run("go")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, FerricLinkError::OutputParser(_)));
+
+        let output = AgentExecutor::new(model(), math_tools())
+            .with_parsing_error_handling(ParsingErrorHandling::Feedback)
+            .run("go")
+            .await
+            .unwrap();
+        assert_eq!(output.output, "Recovered");
+        assert_eq!(output.iterations, 2);
+        assert!(output.messages[2].text().contains("valid JSON"));
+
+        let output = AgentExecutor::new(model(), math_tools())
+            .with_parsing_error_handling(ParsingErrorHandling::Message("Try again".to_string()))
+            .run("go")
+            .await
+            .unwrap();
+        assert_eq!(output.messages[2].text(), "Try again");
+    }
+
+    #[test]
+    fn test_parse_openai_tool_calls() {
+        let mut message = AIMessage::new("");
+        message.additional_kwargs.insert(
+            "tool_calls".to_string(),
+            serde_json::json!([
+                {"id": "a", "type": "function", "function": {"name": "add", "arguments": "{\"a\": 1}"}},
+                {"function": {"name": "noop", "arguments": ""}},
+            ]),
+        );
+
+        let calls = parse_tool_calls(&AnyMessage::AI(message)).unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "a");
+        assert_eq!(calls[0].get_arg("a"), Some(&serde_json::json!(1)));
+        assert_eq!(calls[1].id, "call_1");
+        assert!(calls[1].args.is_empty());
+
+        assert!(
+            parse_tool_calls(&AnyMessage::human("hi"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
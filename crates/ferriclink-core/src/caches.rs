@@ -14,19 +14,27 @@
 //! **Class hierarchy:**
 //!
 //! ```text
-//! BaseCache --> <name>Cache  # Examples: InMemoryCache, RedisCache, GPTCache
+//! BaseCache --> <name>Cache  # Examples: InMemoryCache, FileCache, SemanticCache, RedisCache
 //! ```
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
+use crate::documents::Document;
+use crate::embeddings::Embeddings;
 use crate::errors::Result;
 use crate::impl_serializable;
 use crate::language_models::Generation;
+use crate::utils::hash_key_parts;
+use crate::vectorstores::{InMemoryVectorStore, VectorStore};
 
 /// Type alias for cached return values
 pub type CachedGenerations = Vec<Generation>;
@@ -140,18 +148,42 @@ pub trait BaseCache: Send + Sync {
     async fn aclear(&self) -> Result<()>;
 }
 
+/// Policy choosing which entry to evict when a cache is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Evict the least recently used entry
+    #[default]
+    Lru,
+    /// Evict the least frequently used entry, the least recently used first on ties
+    Lfu,
+}
+
 /// Cache that stores things in memory.
 ///
 /// This is a simple in-memory cache implementation that stores cached values
-/// in a HashMap. It supports optional size limits and LRU eviction.
+/// in a HashMap keyed by a hash of the prompt and LLM string. It supports
+/// optional limits on the number of entries and on their total size in bytes,
+/// evicting entries according to an [`EvictionPolicy`].
+///
+/// Entries are spread over shards, each behind its own short-lived lock that
+/// is never held across an await point. Concurrent lookups and updates
+/// therefore never fail, and the sync and async methods can be mixed freely.
+///
+/// Handles returned by [`InMemoryCache::namespaced`] share the storage and
+/// limits of the cache, but keep their entries apart.
 #[derive(Debug)]
 pub struct InMemoryCache {
-    /// The actual cache storage
-    cache: Arc<RwLock<HashMap<String, CacheEntry>>>,
+    /// The actual cache storage and its statistics
+    state: Arc<CacheState>,
     /// Maximum number of items to store in the cache
     max_size: Option<usize>,
-    /// Statistics for monitoring
-    stats: Arc<RwLock<CacheStats>>,
+    /// Maximum total size of the entries in bytes
+    max_bytes: Option<usize>,
+    /// Which entry to evict when the cache is full
+    eviction_policy: EvictionPolicy,
+    /// Namespace of the entries of this handle
+    namespace: Option<String>,
 }
 
 /// A cache entry that includes the data and metadata
@@ -159,12 +191,324 @@ pub struct InMemoryCache {
 struct CacheEntry {
     /// The cached generations
     data: CachedGenerations,
-    /// When this entry was created
-    created_at: Instant,
-    /// When this entry was last accessed
-    last_accessed: Instant,
+    /// Namespace the entry belongs to
+    namespace: Option<String>,
+    /// Logical time of the last access, increasing with every access
+    last_used: u64,
     /// Number of times this entry has been accessed
     access_count: u64,
+    /// Approximate size of the entry in bytes
+    size_bytes: usize,
+    /// Position of the entry in the eviction order
+    priority: (u64, u64),
+    /// When this entry expires, if it does
+    expires_at: Option<Instant>,
+    /// Whether the entry has been returned once after expiring
+    stale_served: bool,
+}
+
+/// Freshness of a cache entry at lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Freshness {
+    /// The entry is valid
+    Fresh,
+    /// The entry has expired, but may be returned this once
+    Stale,
+    /// The entry has expired and must be removed
+    Expired,
+}
+
+/// Number of shards of an in-memory cache
+const CACHE_SHARDS: usize = 16;
+
+/// A shard of the entries of an in-memory cache
+#[derive(Debug, Default)]
+struct CacheShard {
+    /// Entries by hashed key
+    entries: HashMap<String, CacheEntry>,
+    /// Keys ordered by eviction priority, the next to evict first
+    eviction_order: BTreeMap<(u64, u64), String>,
+}
+
+impl CacheShard {
+    fn remove(&mut self, key: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.eviction_order.remove(&entry.priority);
+        Some(entry)
+    }
+}
+
+/// Statistics updated without locking
+#[derive(Debug, Default)]
+struct AtomicCacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    updates: AtomicU64,
+    clears: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+    stale_hits: AtomicU64,
+    max_size_reached: AtomicUsize,
+}
+
+/// The entries of an in-memory cache and their statistics
+#[derive(Debug)]
+struct CacheState {
+    /// Entries, sharded by hashed key
+    shards: Vec<std::sync::Mutex<CacheShard>>,
+    /// Logical clock ordering accesses across shards
+    clock: AtomicU64,
+    /// Number of entries
+    len: AtomicUsize,
+    /// Total size of the entries in bytes
+    total_bytes: AtomicUsize,
+    /// Statistics for monitoring
+    stats: AtomicCacheStats,
+}
+
+impl Default for CacheState {
+    fn default() -> Self {
+        Self {
+            shards: (0..CACHE_SHARDS).map(|_| Default::default()).collect(),
+            clock: AtomicU64::new(0),
+            len: AtomicUsize::new(0),
+            total_bytes: AtomicUsize::new(0),
+            stats: AtomicCacheStats::default(),
+        }
+    }
+}
+
+impl CacheState {
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Lock a shard; a panic while holding the lock leaves the shard usable.
+    fn lock(&self, index: usize) -> std::sync::MutexGuard<'_, CacheShard> {
+        self.shards[index].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn shard_index(key: &str) -> usize {
+        // Keys are hex digests, so their prefix is uniformly distributed
+        let prefix = key.get(..8).unwrap_or(key);
+        u64::from_str_radix(prefix, 16).unwrap_or_default() as usize % CACHE_SHARDS
+    }
+
+    fn priority(policy: EvictionPolicy, entry: &CacheEntry) -> (u64, u64) {
+        match policy {
+            EvictionPolicy::Lru => (0, entry.last_used),
+            EvictionPolicy::Lfu => (entry.access_count, entry.last_used),
+        }
+    }
+
+    fn removed(&self, entry: &CacheEntry) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        self.total_bytes
+            .fetch_sub(entry.size_bytes, Ordering::Relaxed);
+    }
+
+    /// Look up an entry, recording the access and the hit or miss.
+    ///
+    /// `check` decides whether the entry is fresh, may be returned stale, or
+    /// has expired; expired entries are removed and count as a miss. The
+    /// returned flag tells whether the value is stale.
+    fn get(
+        &self,
+        key: &str,
+        policy: EvictionPolicy,
+        check: impl FnOnce(&mut CacheEntry) -> Freshness,
+    ) -> Option<(CachedGenerations, bool)> {
+        let now = self.tick();
+        let mut shard = self.lock(Self::shard_index(key));
+        let shard = &mut *shard;
+        let result = match shard.entries.get_mut(key).map(check) {
+            Some(Freshness::Expired) => {
+                if let Some(entry) = shard.remove(key) {
+                    self.removed(&entry);
+                    self.stats.expirations.fetch_add(1, Ordering::Relaxed);
+                }
+                None
+            }
+            Some(freshness) => {
+                let entry = shard.entries.get_mut(key)?;
+                shard.eviction_order.remove(&entry.priority);
+                entry.last_used = entry.last_used.max(now);
+                entry.access_count += 1;
+                entry.priority = Self::priority(policy, entry);
+                shard.eviction_order.insert(entry.priority, key.to_string());
+                Some((entry.data.clone(), freshness == Freshness::Stale))
+            }
+            None => None,
+        };
+
+        match &result {
+            Some((_, stale)) => {
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                if *stale {
+                    self.stats.stale_hits.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            None => {
+                self.stats.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+
+    /// Remove the entries expired at `now`, returning how many were removed.
+    ///
+    /// Entries stay for `stale_window` after expiring, unless they have
+    /// already been returned stale.
+    fn remove_expired(&self, now: Instant, stale_window: Duration) -> usize {
+        let mut removed = 0;
+        for index in 0..self.shards.len() {
+            let mut shard = self.lock(index);
+            let keys: Vec<String> = shard
+                .entries
+                .iter()
+                .filter(|(_, entry)| {
+                    entry.expires_at.is_some_and(|expires_at| {
+                        now >= expires_at
+                            && (entry.stale_served
+                                || expires_at
+                                    .checked_add(stale_window)
+                                    .is_some_and(|end| now >= end))
+                    })
+                })
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in keys {
+                if let Some(entry) = shard.remove(&key) {
+                    self.removed(&entry);
+                    removed += 1;
+                }
+            }
+        }
+        self.stats
+            .expirations
+            .fetch_add(removed as u64, Ordering::Relaxed);
+        removed
+    }
+
+    /// Insert an entry, then evict others as needed to stay within the limits.
+    fn insert(
+        &self,
+        key: String,
+        mut entry: CacheEntry,
+        policy: EvictionPolicy,
+        max_size: Option<usize>,
+        max_bytes: Option<usize>,
+    ) {
+        self.stats.updates.fetch_add(1, Ordering::Relaxed);
+        let index = Self::shard_index(&key);
+        if max_bytes.is_some_and(|max_bytes| entry.size_bytes > max_bytes) {
+            // The entry would not fit even in an empty cache
+            if let Some(previous) = self.lock(index).remove(&key) {
+                self.removed(&previous);
+            }
+            return;
+        }
+
+        entry.last_used = self.tick();
+        entry.priority = Self::priority(policy, &entry);
+        let size_bytes = entry.size_bytes;
+        {
+            let mut shard = self.lock(index);
+            if let Some(previous) = shard.remove(&key) {
+                self.removed(&previous);
+            }
+            shard.eviction_order.insert(entry.priority, key.clone());
+            shard.entries.insert(key.clone(), entry);
+            // Counted under the lock, so that an eviction cannot go first
+            self.len.fetch_add(1, Ordering::Relaxed);
+            self.total_bytes.fetch_add(size_bytes, Ordering::Relaxed);
+        }
+
+        while max_size.is_some_and(|max_size| self.len.load(Ordering::Relaxed) > max_size)
+            || max_bytes
+                .is_some_and(|max_bytes| self.total_bytes.load(Ordering::Relaxed) > max_bytes)
+        {
+            if !self.evict_one(&key) {
+                break;
+            }
+        }
+        self.stats
+            .max_size_reached
+            .fetch_max(self.len.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// Evict the entry with the lowest priority over all shards, except `keep`.
+    ///
+    /// Returns false when there is no entry to evict.
+    fn evict_one(&self, keep: &str) -> bool {
+        let lowest = |shard: &CacheShard| {
+            shard
+                .eviction_order
+                .iter()
+                .find(|(_, key)| key.as_str() != keep)
+                .map(|(priority, _)| *priority)
+        };
+
+        let mut candidate: Option<((u64, u64), usize)> = None;
+        for index in 0..self.shards.len() {
+            if let Some(priority) = lowest(&self.lock(index)) {
+                if candidate.is_none_or(|(best, _)| priority < best) {
+                    candidate = Some((priority, index));
+                }
+            }
+        }
+        let Some((_, index)) = candidate else {
+            return false;
+        };
+
+        // The shard may have changed since it was inspected, so evict its
+        // current lowest entry
+        let mut shard = self.lock(index);
+        let Some(priority) = lowest(&shard) else {
+            return true;
+        };
+        if let Some(key) = shard.eviction_order.get(&priority).cloned() {
+            if let Some(entry) = shard.remove(&key) {
+                self.removed(&entry);
+                self.stats.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        true
+    }
+
+    /// Remove the entries of a namespace, or all entries when `namespace` is None.
+    fn clear(&self, namespace: Option<Option<&str>>) {
+        for index in 0..self.shards.len() {
+            let mut shard = self.lock(index);
+            let keys: Vec<String> = shard
+                .entries
+                .iter()
+                .filter(|(_, entry)| namespace.is_none_or(|ns| entry.namespace.as_deref() == ns))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in keys {
+                if let Some(entry) = shard.remove(&key) {
+                    self.removed(&entry);
+                }
+            }
+        }
+        self.stats.clears.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            updates: self.stats.updates.load(Ordering::Relaxed),
+            clears: self.stats.clears.load(Ordering::Relaxed),
+            current_size: self.len.load(Ordering::Relaxed),
+            max_size_reached: self.stats.max_size_reached.load(Ordering::Relaxed),
+            evictions: self.stats.evictions.load(Ordering::Relaxed),
+            current_bytes: self.total_bytes.load(Ordering::Relaxed),
+            expirations: self.stats.expirations.load(Ordering::Relaxed),
+            stale_hits: self.stats.stale_hits.load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// Cache statistics for monitoring
@@ -182,6 +526,18 @@ pub struct CacheStats {
     pub current_size: usize,
     /// Maximum size the cache has reached
     pub max_size_reached: usize,
+    /// Number of entries evicted to respect the cache limits
+    #[serde(default)]
+    pub evictions: u64,
+    /// Approximate total size of the entries currently in cache, in bytes
+    #[serde(default)]
+    pub current_bytes: usize,
+    /// Number of entries removed because they expired
+    #[serde(default)]
+    pub expirations: u64,
+    /// Number of hits returning an expired value
+    #[serde(default)]
+    pub stale_hits: u64,
 }
 
 impl CacheStats {
@@ -213,7 +569,8 @@ impl InMemoryCache {
     ///
     /// * `max_size` - The maximum number of items to store in the cache.
     ///   If None, the cache has no maximum size.
-    ///   If the cache exceeds the maximum size, the oldest items are removed.
+    ///   If the cache exceeds the maximum size, entries are evicted according
+    ///   to the eviction policy, least recently used first by default.
     ///
     /// # Panics
     ///
@@ -224,45 +581,151 @@ impl InMemoryCache {
         }
 
         Self {
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            state: Arc::new(CacheState::default()),
             max_size,
-            stats: Arc::new(RwLock::new(CacheStats::default())),
+            max_bytes: None,
+            eviction_policy: EvictionPolicy::default(),
+            namespace: None,
+        }
+    }
+
+    /// Set the policy choosing which entry to evict when the cache is full.
+    pub fn with_eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.eviction_policy = eviction_policy;
+        self
+    }
+
+    /// Limit the approximate total size of the entries in bytes.
+    ///
+    /// Entries larger than the limit are not stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_bytes` is 0.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        assert!(max_bytes > 0, "max_bytes must be greater than 0");
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Get a handle storing its entries in a namespace.
+    ///
+    /// The handle shares the storage, limits and statistics of this cache.
+    /// Its `clear` only removes the entries of its namespace.
+    pub fn namespaced(&self, namespace: impl Into<String>) -> Self {
+        Self {
+            state: self.state.clone(),
+            max_size: self.max_size,
+            max_bytes: self.max_bytes,
+            eviction_policy: self.eviction_policy,
+            namespace: Some(namespace.into()),
         }
     }
 
+    /// Get the namespace of this handle.
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Remove the entries of a namespace.
+    pub fn clear_namespace(&self, namespace: &str) -> Result<()> {
+        self.state.clear(Some(Some(namespace)));
+        Ok(())
+    }
+
+    /// Async remove the entries of a namespace.
+    pub async fn aclear_namespace(&self, namespace: &str) -> Result<()> {
+        self.clear_namespace(namespace)
+    }
+
     /// Generate a cache key from prompt and llm_string.
     fn generate_key(prompt: &str, llm_string: &str) -> String {
-        // Use a simple concatenation with a delimiter
-        // In production, you might want to use a hash function
-        format!("{prompt}|||{llm_string}")
+        hash_key_parts(&[prompt, llm_string])
+    }
+
+    /// Generate the key of an entry of this handle.
+    fn entry_key(&self, prompt: &str, llm_string: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => hash_key_parts(&[namespace, prompt, llm_string]),
+            None => Self::generate_key(prompt, llm_string),
+        }
+    }
+
+    /// Create an entry for generations.
+    fn new_entry(&self, data: CachedGenerations, expires_at: Option<Instant>) -> CacheEntry {
+        let size_bytes = data
+            .iter()
+            .map(|generation| {
+                generation.text.len()
+                    + serde_json::to_vec(&generation.generation_info)
+                        .map(|info| info.len())
+                        .unwrap_or_default()
+            })
+            .sum::<usize>()
+            // The hashed key
+            + 64;
+        CacheEntry {
+            data,
+            namespace: self.namespace.clone(),
+            last_used: 0,
+            access_count: 0,
+            size_bytes,
+            priority: (0, 0),
+            expires_at,
+            stale_served: false,
+        }
+    }
+
+    /// Store generations, replacing any entry with the same key.
+    fn store(
+        &self,
+        prompt: &str,
+        llm_string: &str,
+        data: CachedGenerations,
+        expires_at: Option<Instant>,
+    ) {
+        self.state.insert(
+            self.entry_key(prompt, llm_string),
+            self.new_entry(data, expires_at),
+            self.eviction_policy,
+            self.max_size,
+            self.max_bytes,
+        );
+    }
+
+    fn clear_scope(&self) -> Option<Option<&str>> {
+        self.namespace.as_deref().map(Some)
     }
 
     /// Get cache statistics.
     pub async fn stats(&self) -> CacheStats {
-        let stats = self.stats.read().await;
-        let cache = self.cache.read().await;
-        CacheStats {
-            current_size: cache.len(),
-            ..stats.clone()
-        }
+        self.state.stats()
     }
 
     /// Get the current cache size.
     pub async fn size(&self) -> usize {
-        let cache = self.cache.read().await;
-        cache.len()
+        self.state.len.load(Ordering::Relaxed)
     }
 
     /// Check if the cache is empty.
     pub async fn is_empty(&self) -> bool {
-        let cache = self.cache.read().await;
-        cache.is_empty()
+        self.size().await == 0
     }
 
     /// Get the maximum cache size.
     pub fn max_size(&self) -> Option<usize> {
         self.max_size
     }
+
+    /// Get the maximum total size of the entries in bytes.
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_bytes
+    }
+
+    /// Get the eviction policy.
+    pub fn eviction_policy(&self) -> EvictionPolicy {
+        self.eviction_policy
+    }
 }
 
 impl Default for InMemoryCache {
@@ -274,97 +737,25 @@ impl Default for InMemoryCache {
 #[async_trait]
 impl BaseCache for InMemoryCache {
     fn lookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
-        let key = Self::generate_key(prompt, llm_string);
-        let mut cache = self.cache.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Cache lock error: {e}"))
-        })?;
-        let mut stats = self.stats.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Stats lock error: {e}"))
-        })?;
-
-        if let Some(entry) = cache.get_mut(&key) {
-            // Update access information
-            entry.last_accessed = Instant::now();
-            entry.access_count += 1;
-            stats.hits += 1;
-            Ok(Some(entry.data.clone()))
-        } else {
-            stats.misses += 1;
-            Ok(None)
-        }
+        let key = self.entry_key(prompt, llm_string);
+        Ok(self
+            .state
+            .get(&key, self.eviction_policy, |_| Freshness::Fresh)
+            .map(|(data, _)| data))
     }
 
     fn update(&self, prompt: &str, llm_string: &str, return_val: CachedGenerations) -> Result<()> {
-        let key = Self::generate_key(prompt, llm_string);
-        let mut cache = self.cache.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Cache lock error: {e}"))
-        })?;
-        let mut stats = self.stats.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Stats lock error: {e}"))
-        })?;
-
-        // Evict if needed before adding new entry
-        if let Some(max_size) = self.max_size {
-            if cache.len() >= max_size {
-                // Find and remove the least recently used entry
-                let mut oldest_key = None;
-                let mut oldest_time = Instant::now();
-
-                for (key, entry) in cache.iter() {
-                    if entry.last_accessed < oldest_time {
-                        oldest_time = entry.last_accessed;
-                        oldest_key = Some(key.clone());
-                    }
-                }
-
-                if let Some(key) = oldest_key {
-                    cache.remove(&key);
-                }
-            }
-        }
-
-        let entry = CacheEntry {
-            data: return_val,
-            created_at: Instant::now(),
-            last_accessed: Instant::now(),
-            access_count: 0,
-        };
-
-        cache.insert(key, entry);
-        stats.updates += 1;
-        stats.max_size_reached = stats.max_size_reached.max(cache.len());
-
+        self.store(prompt, llm_string, return_val, None);
         Ok(())
     }
 
     fn clear(&self) -> Result<()> {
-        let mut cache = self.cache.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Cache lock error: {e}"))
-        })?;
-        let mut stats = self.stats.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Stats lock error: {e}"))
-        })?;
-
-        cache.clear();
-        stats.clears += 1;
+        self.state.clear(self.clear_scope());
         Ok(())
     }
 
     async fn alookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
-        let key = Self::generate_key(prompt, llm_string);
-        let mut cache = self.cache.write().await;
-        let mut stats = self.stats.write().await;
-
-        if let Some(entry) = cache.get_mut(&key) {
-            // Update access information
-            entry.last_accessed = Instant::now();
-            entry.access_count += 1;
-            stats.hits += 1;
-            Ok(Some(entry.data.clone()))
-        } else {
-            stats.misses += 1;
-            Ok(None)
-        }
+        self.lookup(prompt, llm_string)
     }
 
     async fn aupdate(
@@ -373,63 +764,42 @@ impl BaseCache for InMemoryCache {
         llm_string: &str,
         return_val: CachedGenerations,
     ) -> Result<()> {
-        let key = Self::generate_key(prompt, llm_string);
-        let mut cache = self.cache.write().await;
-        let mut stats = self.stats.write().await;
-
-        // Evict if needed before adding new entry
-        if let Some(max_size) = self.max_size {
-            if cache.len() >= max_size {
-                // Find and remove the least recently used entry
-                let mut oldest_key = None;
-                let mut oldest_time = Instant::now();
-
-                for (key, entry) in cache.iter() {
-                    if entry.last_accessed < oldest_time {
-                        oldest_time = entry.last_accessed;
-                        oldest_key = Some(key.clone());
-                    }
-                }
-
-                if let Some(key) = oldest_key {
-                    cache.remove(&key);
-                }
-            }
-        }
-
-        let entry = CacheEntry {
-            data: return_val,
-            created_at: Instant::now(),
-            last_accessed: Instant::now(),
-            access_count: 0,
-        };
-
-        cache.insert(key, entry);
-        stats.updates += 1;
-        stats.max_size_reached = stats.max_size_reached.max(cache.len());
-
-        Ok(())
+        self.update(prompt, llm_string, return_val)
     }
 
     async fn aclear(&self) -> Result<()> {
-        let mut cache = self.cache.write().await;
-        let mut stats = self.stats.write().await;
-
-        cache.clear();
-        stats.clears += 1;
-        Ok(())
+        self.clear()
     }
 }
 
 impl_serializable!(CacheStats, ["ferriclink", "caches", "cache_stats"]);
 
+/// Result of a [`TtlCache`] lookup
+#[derive(Debug, Clone, PartialEq)]
+pub struct TtlLookup {
+    /// The cached generations
+    pub generations: CachedGenerations,
+    /// Whether the entry has expired and should be refreshed
+    pub stale: bool,
+}
+
 /// A more advanced cache with TTL (Time To Live) support.
+///
+/// Every entry expires after the default TTL, or after the TTL given to
+/// [`TtlCache::update_with_ttl`]. With a stale-while-revalidate window, an
+/// entry that expired less than the window ago is returned once more,
+/// flagged as stale, giving the caller time to refresh it; later lookups
+/// miss. Expired entries are removed on lookup, by
+/// [`TtlCache::purge_expired`], or periodically by a sweeper started with
+/// [`TtlCache::start_sweeper`].
 #[derive(Debug)]
 pub struct TtlCache {
     /// The underlying cache
     inner: InMemoryCache,
     /// Default TTL for cache entries
     default_ttl: Duration,
+    /// How long expired entries may still be returned once
+    stale_while_revalidate: Option<Duration>,
 }
 
 impl TtlCache {
@@ -443,22 +813,137 @@ impl TtlCache {
         Self {
             inner: InMemoryCache::with_max_size(max_size),
             default_ttl,
+            stale_while_revalidate: None,
         }
     }
 
+    /// Set the window during which an expired entry is returned once more, flagged as stale.
+    pub fn with_stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.stale_while_revalidate = Some(window);
+        self
+    }
+
     /// Get the default TTL.
     pub fn default_ttl(&self) -> Duration {
         self.default_ttl
     }
 
+    /// Get the stale-while-revalidate window.
+    pub fn stale_while_revalidate(&self) -> Option<Duration> {
+        self.stale_while_revalidate
+    }
+
     /// Get cache statistics.
     pub async fn stats(&self) -> CacheStats {
         self.inner.stats().await
     }
 
-    /// Check if an entry has expired.
-    fn is_expired(entry: &CacheEntry, ttl: Duration) -> bool {
-        entry.created_at.elapsed() > ttl
+    /// Update the cache with an entry expiring after `ttl` instead of the default TTL.
+    pub fn update_with_ttl(
+        &self,
+        prompt: &str,
+        llm_string: &str,
+        return_val: CachedGenerations,
+        ttl: Duration,
+    ) -> Result<()> {
+        // A TTL too long to represent never expires
+        let expires_at = Instant::now().checked_add(ttl);
+        self.inner.store(prompt, llm_string, return_val, expires_at);
+        Ok(())
+    }
+
+    /// Async update the cache with an entry expiring after `ttl` instead of the default TTL.
+    pub async fn aupdate_with_ttl(
+        &self,
+        prompt: &str,
+        llm_string: &str,
+        return_val: CachedGenerations,
+        ttl: Duration,
+    ) -> Result<()> {
+        self.update_with_ttl(prompt, llm_string, return_val, ttl)
+    }
+
+    /// Look up an entry, telling whether it is stale.
+    pub fn lookup_with_status(&self, prompt: &str, llm_string: &str) -> Result<Option<TtlLookup>> {
+        let key = self.inner.entry_key(prompt, llm_string);
+        Ok(self
+            .lookup_key(&key)
+            .map(|(generations, stale)| TtlLookup { generations, stale }))
+    }
+
+    /// Async look up an entry, telling whether it is stale.
+    pub async fn alookup_with_status(
+        &self,
+        prompt: &str,
+        llm_string: &str,
+    ) -> Result<Option<TtlLookup>> {
+        self.lookup_with_status(prompt, llm_string)
+    }
+
+    /// Remove all expired entries, returning how many were removed.
+    pub fn purge_expired(&self) -> usize {
+        self.inner
+            .state
+            .remove_expired(Instant::now(), self.stale_window())
+    }
+
+    /// Start a background task removing expired entries every `interval`.
+    ///
+    /// The task stops when the returned handle or the cache is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    pub fn start_sweeper(&self, interval: Duration) -> TtlSweeper {
+        let state = Arc::downgrade(&self.inner.state);
+        let stale_window = self.stale_window();
+        let token = CancellationToken::new();
+        let cancelled = token.clone();
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                let Some(state) = state.upgrade() else {
+                    break;
+                };
+                state.remove_expired(Instant::now(), stale_window);
+            }
+        });
+        TtlSweeper { token, handle }
+    }
+
+    fn stale_window(&self) -> Duration {
+        self.stale_while_revalidate.unwrap_or_default()
+    }
+
+    /// Look up an entry, removing it instead if it has expired.
+    fn lookup_key(&self, key: &str) -> Option<(CachedGenerations, bool)> {
+        let now = Instant::now();
+        let stale_window = self.stale_while_revalidate;
+        self.inner
+            .state
+            .get(key, self.inner.eviction_policy, |entry| {
+                match entry.expires_at {
+                    Some(expires_at) if now >= expires_at => {
+                        let in_window = stale_window.is_some_and(|window| {
+                            expires_at.checked_add(window).is_none_or(|end| now < end)
+                        });
+                        if in_window && !entry.stale_served {
+                            entry.stale_served = true;
+                            Freshness::Stale
+                        } else {
+                            Freshness::Expired
+                        }
+                    }
+                    _ => Freshness::Fresh,
+                }
+            })
     }
 }
 
@@ -466,38 +951,12 @@ impl TtlCache {
 impl BaseCache for TtlCache {
     fn lookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
         // For TTL cache, we need to check expiration
-        let key = InMemoryCache::generate_key(prompt, llm_string);
-        let mut cache = self.inner.cache.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Cache lock error: {e}"))
-        })?;
-        let mut stats = self.inner.stats.try_write().map_err(|e| {
-            crate::errors::FerricLinkError::runtime(format!("Stats lock error: {e}"))
-        })?;
-
-        if let Some(entry) = cache.get(&key) {
-            if Self::is_expired(entry, self.default_ttl) {
-                // Entry has expired, remove it
-                cache.remove(&key);
-                stats.misses += 1;
-                Ok(None)
-            } else {
-                // Entry is still valid, update access info
-                let mut entry = entry.clone();
-                entry.last_accessed = Instant::now();
-                entry.access_count += 1;
-                let data = entry.data.clone();
-                cache.insert(key, entry);
-                stats.hits += 1;
-                Ok(Some(data))
-            }
-        } else {
-            stats.misses += 1;
-            Ok(None)
-        }
+        let key = self.inner.entry_key(prompt, llm_string);
+        Ok(self.lookup_key(&key).map(|(data, _)| data))
     }
 
     fn update(&self, prompt: &str, llm_string: &str, return_val: CachedGenerations) -> Result<()> {
-        self.inner.update(prompt, llm_string, return_val)
+        self.update_with_ttl(prompt, llm_string, return_val, self.default_ttl)
     }
 
     fn clear(&self) -> Result<()> {
@@ -505,31 +964,7 @@ impl BaseCache for TtlCache {
     }
 
     async fn alookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
-        // For TTL cache, we need to check expiration
-        let key = InMemoryCache::generate_key(prompt, llm_string);
-        let mut cache = self.inner.cache.write().await;
-        let mut stats = self.inner.stats.write().await;
-
-        if let Some(entry) = cache.get(&key) {
-            if Self::is_expired(entry, self.default_ttl) {
-                // Entry has expired, remove it
-                cache.remove(&key);
-                stats.misses += 1;
-                Ok(None)
-            } else {
-                // Entry is still valid, update access info
-                let mut entry = entry.clone();
-                entry.last_accessed = Instant::now();
-                entry.access_count += 1;
-                let data = entry.data.clone();
-                cache.insert(key, entry);
-                stats.hits += 1;
-                Ok(Some(data))
-            }
-        } else {
-            stats.misses += 1;
-            Ok(None)
-        }
+        self.lookup(prompt, llm_string)
     }
 
     async fn aupdate(
@@ -538,7 +973,7 @@ impl BaseCache for TtlCache {
         llm_string: &str,
         return_val: CachedGenerations,
     ) -> Result<()> {
-        self.inner.aupdate(prompt, llm_string, return_val).await
+        self.update(prompt, llm_string, return_val)
     }
 
     async fn aclear(&self) -> Result<()> {
@@ -546,41 +981,729 @@ impl BaseCache for TtlCache {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::language_models::Generation;
+/// Handle of a background task removing the expired entries of a [`TtlCache`]
+///
+/// Dropping the handle stops the task.
+#[derive(Debug)]
+pub struct TtlSweeper {
+    token: CancellationToken,
+    handle: tokio::task::JoinHandle<()>,
+}
 
-    fn create_test_generation(text: &str) -> Generation {
-        Generation {
-            text: text.to_string(),
-            generation_info: std::collections::HashMap::new(),
-        }
+impl TtlSweeper {
+    /// Stop the task.
+    pub fn stop(&self) {
+        self.token.cancel();
     }
 
-    #[test]
-    fn test_in_memory_cache_basic() {
-        let cache = InMemoryCache::new();
-
-        // Test empty cache
-        assert!(cache.lookup("test", "llm").unwrap().is_none());
-
-        // Test update and lookup
-        let generations = vec![create_test_generation("Hello, world!")];
-        cache.update("test", "llm", generations.clone()).unwrap();
+    /// Check if the task has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+}
 
-        let result = cache.lookup("test", "llm").unwrap();
-        assert!(result.is_some());
-        assert_eq!(result.unwrap(), generations);
+impl Drop for TtlSweeper {
+    fn drop(&mut self) {
+        self.token.cancel();
     }
+}
 
-    #[test]
-    fn test_in_memory_cache_with_max_size() {
-        let cache = InMemoryCache::with_max_size(Some(2));
+/// Age after which a temporary file of [`FileCache`] is left by a crashed writer
+const STALE_TEMP_FILE_AGE: Duration = Duration::from_secs(60 * 60);
 
-        // Add entries up to max size
-        cache
-            .update("test1", "llm", vec![create_test_generation("1")])
+/// Cache that stores generations as files on disk.
+///
+/// Each entry is a JSON file named after the SHA-256 hash of the prompt and
+/// LLM string, in directories sharded by the first bytes of the hash
+/// (`ab/cd/abcd....json`), so that no directory grows too large. Entries are
+/// written to a temporary file and renamed into place, so processes sharing
+/// the directory never read a partially written entry.
+///
+/// Entries can expire after a TTL. With a maximum size, the entries are
+/// counted once and the count is kept up to date by this process's updates.
+/// When it exceeds the maximum size, the entries are listed again and the
+/// least recently used ones are removed until a tenth of the maximum size is
+/// free, so that updates only list the entries once in a while. Lookups
+/// refresh the modification time of the entry they read. Entries written by
+/// other processes are only counted when the entries are listed again.
+#[derive(Debug, Clone)]
+pub struct FileCache {
+    /// Root directory of the cache
+    directory: PathBuf,
+    /// Time-to-live of new entries
+    ttl: Option<Duration>,
+    /// Maximum number of entries to keep
+    max_size: Option<usize>,
+    /// Approximate number of entries on disk, `None` until they are listed
+    entry_count: Arc<std::sync::Mutex<Option<usize>>>,
+    /// Statistics of this process
+    stats: Arc<std::sync::Mutex<CacheStats>>,
+}
+
+/// A cache entry as stored on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct FileCacheEntry {
+    /// The prompt, checked on lookup
+    prompt: String,
+    /// The LLM string, checked on lookup
+    llm_string: String,
+    /// The cached generations
+    generations: CachedGenerations,
+    /// When this entry was written
+    created_at: DateTime<Utc>,
+    /// When this entry expires
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl FileCache {
+    /// Create a new file cache in a directory, creating it if needed.
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            ttl: None,
+            max_size: None,
+            entry_count: Arc::new(std::sync::Mutex::new(None)),
+            stats: Arc::new(std::sync::Mutex::new(CacheStats::default())),
+        })
+    }
+
+    /// Set the time-to-live of new entries.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Set the maximum number of entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_size` is 0.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        assert!(max_size > 0, "max_size must be greater than 0");
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Get the root directory of the cache.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Get the time-to-live of new entries.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Get the maximum number of entries.
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Get cache statistics.
+    ///
+    /// Hits, misses, updates and clears are counted for this process, while
+    /// the current size counts the entries on disk.
+    pub async fn stats(&self) -> Result<CacheStats> {
+        let current_size = self.size().await?;
+        Ok(CacheStats {
+            current_size,
+            ..self.lock_stats().clone()
+        })
+    }
+
+    /// Get the number of entries on disk, including expired ones not yet removed.
+    pub async fn size(&self) -> Result<usize> {
+        self.run_blocking(|cache| Ok(cache.entry_files()?.len()))
+            .await
+    }
+
+    /// Remove all expired entries, returning how many were removed.
+    ///
+    /// Temporary files left by writers that crashed are removed as well.
+    pub async fn purge_expired(&self) -> Result<usize> {
+        self.run_blocking(|cache| {
+            let stale_before = SystemTime::now() - STALE_TEMP_FILE_AGE;
+            for (path, modified) in cache.shard_files(Self::is_temp_file)? {
+                if modified < stale_before {
+                    Self::remove_file(&path)?;
+                }
+            }
+
+            let now = Utc::now();
+            let files = cache.entry_files()?;
+            let mut removed = 0;
+            for (path, _) in &files {
+                if let Some(entry) = Self::read_entry(path)? {
+                    if entry.expires_at.is_some_and(|expires_at| expires_at <= now) {
+                        Self::remove_file(path)?;
+                        removed += 1;
+                    }
+                }
+            }
+            *cache.lock_entry_count() = Some(files.len() - removed);
+            Ok(removed)
+        })
+        .await
+    }
+
+    /// Hash the prompt and LLM string into a hex key.
+    fn hash_key(prompt: &str, llm_string: &str) -> String {
+        hash_key_parts(&[prompt, llm_string])
+    }
+
+    /// Get the path of the entry file of a key.
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.directory
+            .join(&key[0..2])
+            .join(&key[2..4])
+            .join(format!("{key}.json"))
+    }
+
+    fn is_shard_name(name: &str) -> bool {
+        name.len() == 2 && name.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    fn is_temp_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.') && name.ends_with(".tmp"))
+    }
+
+    /// List the entry files with their modification time.
+    fn entry_files(&self) -> Result<Vec<(PathBuf, SystemTime)>> {
+        self.shard_files(|path| path.extension().is_some_and(|ext| ext == "json"))
+    }
+
+    /// List the files of the shards matching a predicate, with their modification time.
+    fn shard_files(&self, matches: impl Fn(&Path) -> bool) -> Result<Vec<(PathBuf, SystemTime)>> {
+        let mut files = Vec::new();
+        for shard in Self::shard_dirs(&self.directory)? {
+            for sub_shard in Self::shard_dirs(&shard)? {
+                for file in std::fs::read_dir(&sub_shard)? {
+                    let file = file?;
+                    let path = file.path();
+                    if matches(&path) {
+                        // The entry may be removed concurrently
+                        if let Ok(modified) = file.metadata().and_then(|m| m.modified()) {
+                            files.push((path, modified));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    fn shard_dirs(directory: &Path) -> Result<Vec<PathBuf>> {
+        let mut dirs = Vec::new();
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            let is_shard = entry.file_name().to_str().is_some_and(Self::is_shard_name);
+            if is_shard && entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Read an entry file, treating missing and corrupted files as absent.
+    fn read_entry(path: &Path) -> Result<Option<FileCacheEntry>> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match serde_json::from_slice(&content) {
+            Ok(entry) => Ok(Some(entry)),
+            Err(_) => {
+                Self::remove_file(path)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn remove_file(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write an entry file atomically.
+    ///
+    /// With a maximum size, returns the approximate number of entries left and
+    /// the number of evicted entries. Without one, the entries are not counted.
+    fn write_entry(&self, key: &str, entry: &FileCacheEntry) -> Result<(Option<usize>, usize)> {
+        let path = self.entry_path(key);
+        let shard = path.parent().unwrap_or(&self.directory);
+        std::fs::create_dir_all(shard)?;
+        let replaced = path.exists();
+
+        let temp_path = shard.join(format!(".{key}.{}.tmp", uuid::Uuid::new_v4()));
+        let write = || -> Result<()> {
+            let mut file = std::fs::File::create(&temp_path)?;
+            std::io::Write::write_all(&mut file, &serde_json::to_vec(entry)?)?;
+            file.sync_all()?;
+            std::fs::rename(&temp_path, &path)?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        let Some(max_size) = self.max_size else {
+            return Ok((None, 0));
+        };
+        let mut entry_count = self.lock_entry_count();
+        let count = match *entry_count {
+            Some(count) if replaced => count,
+            Some(count) => count + 1,
+            None => self.entry_files()?.len(),
+        };
+        *entry_count = Some(count);
+        if count <= max_size {
+            return Ok((Some(count), 0));
+        }
+
+        // Free a tenth of the maximum size, so the entries are not listed
+        // again on every new entry
+        let mut files = self.entry_files()?;
+        let keep = max_size - max_size / 10;
+        let mut evicted = 0;
+        if files.len() > keep {
+            files.sort_by_key(|(_, modified)| *modified);
+            evicted = files.len() - keep;
+            for (path, _) in files.drain(..evicted) {
+                Self::remove_file(&path)?;
+            }
+        }
+        *entry_count = Some(files.len());
+        Ok((Some(files.len()), evicted))
+    }
+
+    fn lock_entry_count(&self) -> std::sync::MutexGuard<'_, Option<usize>> {
+        self.entry_count.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, CacheStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run a function with a clone of the cache on the blocking thread pool.
+    async fn run_blocking<T, F>(&self, func: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(FileCache) -> Result<T> + Send + 'static,
+    {
+        let cache = self.clone();
+        tokio::task::spawn_blocking(move || func(cache))
+            .await
+            .map_err(|e| {
+                crate::errors::FerricLinkError::runtime(format!("Cache task failed: {e}"))
+            })?
+    }
+}
+
+#[async_trait]
+impl BaseCache for FileCache {
+    fn lookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
+        let path = self.entry_path(&Self::hash_key(prompt, llm_string));
+        let result = match Self::read_entry(&path)? {
+            Some(entry) if entry.prompt == prompt && entry.llm_string == llm_string => {
+                if entry
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= Utc::now())
+                {
+                    Self::remove_file(&path)?;
+                    if let Some(count) = self.lock_entry_count().as_mut() {
+                        *count = count.saturating_sub(1);
+                    }
+                    None
+                } else {
+                    // Mark the entry as recently used for eviction
+                    if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+                        let _ = file.set_modified(SystemTime::now());
+                    }
+                    Some(entry.generations)
+                }
+            }
+            _ => None,
+        };
+
+        let mut stats = self.lock_stats();
+        if result.is_some() {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+        Ok(result)
+    }
+
+    fn update(&self, prompt: &str, llm_string: &str, return_val: CachedGenerations) -> Result<()> {
+        let created_at = Utc::now();
+        let entry = FileCacheEntry {
+            prompt: prompt.to_string(),
+            llm_string: llm_string.to_string(),
+            generations: return_val,
+            created_at,
+            expires_at: self
+                .ttl
+                .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+                .map(|ttl| created_at + ttl),
+        };
+        let (size, evicted) = self.write_entry(&Self::hash_key(prompt, llm_string), &entry)?;
+
+        let mut stats = self.lock_stats();
+        stats.updates += 1;
+        stats.evictions += evicted as u64;
+        if let Some(size) = size {
+            stats.max_size_reached = stats.max_size_reached.max(size);
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        for shard in Self::shard_dirs(&self.directory)? {
+            match std::fs::remove_dir_all(&shard) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        *self.lock_entry_count() = Some(0);
+        self.lock_stats().clears += 1;
+        Ok(())
+    }
+
+    async fn alookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
+        let (prompt, llm_string) = (prompt.to_string(), llm_string.to_string());
+        self.run_blocking(move |cache| cache.lookup(&prompt, &llm_string))
+            .await
+    }
+
+    async fn aupdate(
+        &self,
+        prompt: &str,
+        llm_string: &str,
+        return_val: CachedGenerations,
+    ) -> Result<()> {
+        let (prompt, llm_string) = (prompt.to_string(), llm_string.to_string());
+        self.run_blocking(move |cache| cache.update(&prompt, &llm_string, return_val))
+            .await
+    }
+
+    async fn aclear(&self) -> Result<()> {
+        self.run_blocking(|cache| cache.clear()).await
+    }
+}
+
+/// Run a future to completion from a synchronous cache method.
+///
+/// Inside a multi-threaded Tokio runtime the worker thread is handed over to
+/// the runtime while blocking. A current-thread runtime cannot make progress
+/// while blocked, so an error is returned there instead.
+fn block_on_cache<F: std::future::Future>(future: F) -> Result<F::Output> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => match handle.runtime_flavor() {
+            tokio::runtime::RuntimeFlavor::CurrentThread => {
+                Err(crate::errors::FerricLinkError::runtime(
+                    "Synchronous cache calls are not supported on a current-thread runtime, use the async methods",
+                ))
+            }
+            _ => Ok(tokio::task::block_in_place(|| handle.block_on(future))),
+        },
+        Err(_) => Ok(futures::executor::block_on(future)),
+    }
+}
+
+/// Function creating the vector store of a partition from its LLM string
+pub type VectorStoreFactory = Arc<dyn Fn(&str) -> Arc<dyn VectorStore> + Send + Sync>;
+
+/// Default minimum cosine similarity for a semantic cache hit
+pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.95;
+
+/// Statistics of a [`SemanticCache`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SemanticCacheStats {
+    /// Number of cache hits, exact and near
+    pub hits: u64,
+    /// Number of hits on a cached prompt different from the looked up one
+    pub near_hits: u64,
+    /// Number of cache misses
+    pub misses: u64,
+    /// Number of cache updates
+    pub updates: u64,
+    /// Number of cache clears
+    pub clears: u64,
+    /// Total number of entries currently in cache
+    pub current_size: usize,
+    /// Sum of the similarity of near-hits
+    pub near_hit_similarity_sum: f64,
+}
+
+impl SemanticCacheStats {
+    /// Get the hit rate as a percentage
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            (self.hits as f64 / total as f64) * 100.0
+        }
+    }
+
+    /// Get the number of hits on the exact looked up prompt
+    pub fn exact_hits(&self) -> u64 {
+        self.hits - self.near_hits
+    }
+
+    /// Get the average similarity of near-hits
+    pub fn average_near_hit_similarity(&self) -> Option<f64> {
+        (self.near_hits > 0).then(|| self.near_hit_similarity_sum / self.near_hits as f64)
+    }
+}
+
+impl_serializable!(
+    SemanticCacheStats,
+    ["ferriclink", "caches", "semantic_cache_stats"]
+);
+
+/// Entries cached for one LLM string
+struct SemanticPartition {
+    /// The vector store holding the embedded prompts
+    store: Arc<dyn VectorStore>,
+    /// Vector store ID of each cached prompt
+    ids: HashMap<String, String>,
+}
+
+/// Cache returning the generations of similar prompts.
+///
+/// Prompts are embedded and stored in a vector store, one per LLM string so
+/// that generations are never shared between model configurations. A lookup
+/// is a hit when the most similar cached prompt has a cosine similarity of at
+/// least the threshold. The vector stores must report cosine similarity as
+/// the score of their search results, as [`InMemoryVectorStore`] does.
+///
+/// The synchronous methods block on the async ones, and need a
+/// multi-threaded runtime when called from within Tokio.
+pub struct SemanticCache {
+    /// Model embedding the prompts
+    embeddings: Arc<dyn Embeddings>,
+    /// Creates the vector store of new partitions
+    vectorstore_factory: VectorStoreFactory,
+    /// Minimum cosine similarity for a hit
+    similarity_threshold: f32,
+    /// Partitions by LLM string
+    partitions: RwLock<HashMap<String, SemanticPartition>>,
+    /// Statistics for monitoring
+    stats: std::sync::Mutex<SemanticCacheStats>,
+}
+
+/// Metadata key of the cached prompt
+const SEMANTIC_PROMPT_KEY: &str = "prompt";
+/// Metadata key of the cached generations
+const SEMANTIC_GENERATIONS_KEY: &str = "generations";
+
+impl SemanticCache {
+    /// Create a new semantic cache storing prompts in in-memory vector stores.
+    pub fn new(embeddings: Arc<dyn Embeddings>) -> Self {
+        Self {
+            embeddings,
+            vectorstore_factory: Arc::new(|_| Arc::new(InMemoryVectorStore::new())),
+            similarity_threshold: DEFAULT_SIMILARITY_THRESHOLD,
+            partitions: RwLock::new(HashMap::new()),
+            stats: std::sync::Mutex::new(SemanticCacheStats::default()),
+        }
+    }
+
+    /// Set the function creating the vector store of each LLM string.
+    pub fn with_vectorstore_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&str) -> Arc<dyn VectorStore> + Send + Sync + 'static,
+    {
+        self.vectorstore_factory = Arc::new(factory);
+        self
+    }
+
+    /// Set the minimum cosine similarity for a hit.
+    pub fn with_similarity_threshold(mut self, similarity_threshold: f32) -> Self {
+        self.similarity_threshold = similarity_threshold;
+        self
+    }
+
+    /// Get the minimum cosine similarity for a hit.
+    pub fn similarity_threshold(&self) -> f32 {
+        self.similarity_threshold
+    }
+
+    /// Get cache statistics.
+    pub async fn stats(&self) -> SemanticCacheStats {
+        let current_size = self.size().await;
+        SemanticCacheStats {
+            current_size,
+            ..self.lock_stats().clone()
+        }
+    }
+
+    /// Get the number of cached prompts over all LLM strings.
+    pub async fn size(&self) -> usize {
+        let partitions = self.partitions.read().await;
+        partitions.values().map(|p| p.ids.len()).sum()
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, SemanticCacheStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl BaseCache for SemanticCache {
+    fn lookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
+        block_on_cache(self.alookup(prompt, llm_string))?
+    }
+
+    fn update(&self, prompt: &str, llm_string: &str, return_val: CachedGenerations) -> Result<()> {
+        block_on_cache(self.aupdate(prompt, llm_string, return_val))?
+    }
+
+    fn clear(&self) -> Result<()> {
+        block_on_cache(self.aclear())?
+    }
+
+    async fn alookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
+        let store = {
+            let partitions = self.partitions.read().await;
+            partitions.get(llm_string).map(|p| p.store.clone())
+        };
+        let Some(store) = store else {
+            self.lock_stats().misses += 1;
+            return Ok(None);
+        };
+
+        let embedding = self.embeddings.embed_query(prompt).await?;
+        let best = store
+            .similarity_search_by_embedding(&embedding, 1, None)
+            .await?
+            .into_iter()
+            .next()
+            .filter(|result| result.score >= self.similarity_threshold);
+        let Some(best) = best else {
+            self.lock_stats().misses += 1;
+            return Ok(None);
+        };
+
+        let generations: CachedGenerations =
+            match best.document.metadata.get(SEMANTIC_GENERATIONS_KEY) {
+                Some(value) => serde_json::from_value(value.clone())?,
+                None => {
+                    self.lock_stats().misses += 1;
+                    return Ok(None);
+                }
+            };
+        let cached_prompt = best
+            .document
+            .metadata
+            .get(SEMANTIC_PROMPT_KEY)
+            .and_then(|p| p.as_str());
+
+        let mut stats = self.lock_stats();
+        stats.hits += 1;
+        if cached_prompt != Some(prompt) {
+            stats.near_hits += 1;
+            stats.near_hit_similarity_sum += f64::from(best.score);
+        }
+        Ok(Some(generations))
+    }
+
+    async fn aupdate(
+        &self,
+        prompt: &str,
+        llm_string: &str,
+        return_val: CachedGenerations,
+    ) -> Result<()> {
+        let embedding = self.embeddings.embed_query(prompt).await?;
+        let mut metadata = HashMap::new();
+        metadata.insert(SEMANTIC_PROMPT_KEY.to_string(), serde_json::json!(prompt));
+        metadata.insert(
+            SEMANTIC_GENERATIONS_KEY.to_string(),
+            serde_json::to_value(&return_val)?,
+        );
+        let document = Document::new_with_metadata(prompt, metadata);
+
+        let mut partitions = self.partitions.write().await;
+        let partition =
+            partitions
+                .entry(llm_string.to_string())
+                .or_insert_with(|| SemanticPartition {
+                    store: (self.vectorstore_factory)(llm_string),
+                    ids: HashMap::new(),
+                });
+        if let Some(previous) = partition.ids.remove(prompt) {
+            partition.store.delete(vec![previous]).await?;
+        }
+        let ids = partition
+            .store
+            .add_documents(vec![document], Some(vec![embedding]))
+            .await?;
+        if let Some(id) = ids.into_iter().next() {
+            partition.ids.insert(prompt.to_string(), id);
+        }
+        drop(partitions);
+
+        self.lock_stats().updates += 1;
+        Ok(())
+    }
+
+    async fn aclear(&self) -> Result<()> {
+        let mut partitions = self.partitions.write().await;
+        for partition in partitions.values() {
+            partition.store.clear().await?;
+        }
+        partitions.clear();
+        drop(partitions);
+
+        self.lock_stats().clears += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language_models::Generation;
+
+    fn create_test_generation(text: &str) -> Generation {
+        Generation {
+            text: text.to_string(),
+            generation_info: std::collections::HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_in_memory_cache_basic() {
+        let cache = InMemoryCache::new();
+
+        // Test empty cache
+        assert!(cache.lookup("test", "llm").unwrap().is_none());
+
+        // Test update and lookup
+        let generations = vec![create_test_generation("Hello, world!")];
+        cache.update("test", "llm", generations.clone()).unwrap();
+
+        let result = cache.lookup("test", "llm").unwrap();
+        assert!(result.is_some());
+        assert_eq!(result.unwrap(), generations);
+    }
+
+    #[test]
+    fn test_in_memory_cache_with_max_size() {
+        let cache = InMemoryCache::with_max_size(Some(2));
+
+        // Add entries up to max size
+        cache
+            .update("test1", "llm", vec![create_test_generation("1")])
             .unwrap();
         cache
             .update("test2", "llm", vec![create_test_generation("2")])
@@ -670,6 +1793,154 @@ mod tests {
         assert!(cache.lookup("test", "llm").unwrap().is_none());
     }
 
+    #[test]
+    fn test_ttl_cache_per_entry_ttl() {
+        let cache = TtlCache::new(Duration::from_millis(100), None);
+        cache
+            .update("default", "llm", vec![create_test_generation("1")])
+            .unwrap();
+        cache
+            .update_with_ttl(
+                "long",
+                "llm",
+                vec![create_test_generation("2")],
+                Duration::from_secs(60),
+            )
+            .unwrap();
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(cache.lookup("default", "llm").unwrap().is_none());
+        assert!(cache.lookup("long", "llm").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_ttl_cache_unbounded_durations() {
+        let cache = TtlCache::new(Duration::MAX, None).with_stale_while_revalidate(Duration::MAX);
+        cache
+            .update("forever", "llm", vec![create_test_generation("1")])
+            .unwrap();
+        cache
+            .update_with_ttl(
+                "short",
+                "llm",
+                vec![create_test_generation("2")],
+                Duration::from_millis(10),
+            )
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(cache.lookup("forever", "llm").unwrap().is_some());
+        // An unbounded window keeps expired entries until they are served stale
+        assert_eq!(cache.purge_expired(), 0);
+        let lookup = cache.lookup_with_status("short", "llm").unwrap().unwrap();
+        assert!(lookup.stale);
+        assert!(cache.lookup("short", "llm").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ttl_cache_stale_while_revalidate() {
+        let cache = TtlCache::new(Duration::from_millis(50), None)
+            .with_stale_while_revalidate(Duration::from_secs(60));
+        cache
+            .aupdate("test", "llm", vec![create_test_generation("old")])
+            .await
+            .unwrap();
+
+        let fresh = cache.alookup_with_status("test", "llm").await.unwrap();
+        assert!(!fresh.unwrap().stale);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        let stale = cache.alookup_with_status("test", "llm").await.unwrap();
+        assert_eq!(
+            stale,
+            Some(TtlLookup {
+                generations: vec![create_test_generation("old")],
+                stale: true,
+            })
+        );
+        // The stale value is returned only once
+        assert!(cache.alookup("test", "llm").await.unwrap().is_none());
+
+        // Refreshing the entry makes it fresh again
+        cache
+            .aupdate("test", "llm", vec![create_test_generation("new")])
+            .await
+            .unwrap();
+        let fresh = cache.alookup_with_status("test", "llm").await.unwrap();
+        assert!(!fresh.unwrap().stale);
+
+        let stats = cache.stats().await;
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.stale_hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.expirations, 1);
+    }
+
+    #[test]
+    fn test_ttl_cache_purge_expired() {
+        let cache = TtlCache::new(Duration::from_millis(50), None)
+            .with_stale_while_revalidate(Duration::from_millis(50));
+        for i in 0..3 {
+            cache
+                .update(&format!("p{i}"), "llm", vec![create_test_generation("x")])
+                .unwrap();
+        }
+        cache
+            .update_with_ttl(
+                "kept",
+                "llm",
+                vec![create_test_generation("y")],
+                Duration::from_secs(60),
+            )
+            .unwrap();
+
+        // Within the stale window, entries are kept until served
+        std::thread::sleep(Duration::from_millis(70));
+        assert!(
+            cache
+                .lookup_with_status("p0", "llm")
+                .unwrap()
+                .unwrap()
+                .stale
+        );
+        assert_eq!(cache.purge_expired(), 1);
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(cache.purge_expired(), 2);
+        assert!(cache.lookup("kept", "llm").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_ttl_cache_sweeper() {
+        let cache = TtlCache::new(Duration::from_millis(30), None);
+        for i in 0..4 {
+            cache
+                .aupdate(&format!("p{i}"), "llm", vec![create_test_generation("x")])
+                .await
+                .unwrap();
+        }
+        assert_eq!(cache.stats().await.current_size, 4);
+
+        let sweeper = cache.start_sweeper(Duration::from_millis(10));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while cache.stats().await.current_size > 0 {
+            assert!(Instant::now() < deadline, "sweeper did not remove entries");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let stats = cache.stats().await;
+        assert_eq!(stats.expirations, 4);
+        assert_eq!(stats.misses, 0);
+        assert_eq!(stats.current_bytes, 0);
+
+        sweeper.stop();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !sweeper.is_finished() {
+            assert!(Instant::now() < deadline, "sweeper did not stop");
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
     #[test]
     fn test_cache_key_generation() {
         let key1 = InMemoryCache::generate_key("prompt1", "llm1");
@@ -681,4 +1952,521 @@ mod tests {
         assert_ne!(key1, key3);
         assert_eq!(key1, key4);
     }
+
+    #[test]
+    fn test_in_memory_cache_keys_do_not_collide() {
+        let cache = InMemoryCache::new();
+        cache
+            .update("a|||b", "c", vec![create_test_generation("1")])
+            .unwrap();
+        cache
+            .update("a", "b|||c", vec![create_test_generation("2")])
+            .unwrap();
+
+        assert_eq!(
+            cache.lookup("a|||b", "c").unwrap(),
+            Some(vec![create_test_generation("1")])
+        );
+        assert_eq!(
+            cache.lookup("a", "b|||c").unwrap(),
+            Some(vec![create_test_generation("2")])
+        );
+
+        // Keys are fixed-size hashes, whatever the prompt size
+        let key = InMemoryCache::generate_key(&"x".repeat(10_000), "llm");
+        assert_eq!(key.len(), 64);
+    }
+
+    #[test]
+    fn test_in_memory_cache_lru_eviction() {
+        let cache = InMemoryCache::with_max_size(Some(2));
+        cache
+            .update("a", "llm", vec![create_test_generation("a")])
+            .unwrap();
+        cache
+            .update("b", "llm", vec![create_test_generation("b")])
+            .unwrap();
+
+        // Reading "a" makes "b" the least recently used entry
+        assert!(cache.lookup("a", "llm").unwrap().is_some());
+        cache
+            .update("c", "llm", vec![create_test_generation("c")])
+            .unwrap();
+
+        assert!(cache.lookup("a", "llm").unwrap().is_some());
+        assert!(cache.lookup("b", "llm").unwrap().is_none());
+        assert!(cache.lookup("c", "llm").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_lfu_eviction() {
+        let cache = InMemoryCache::with_max_size(Some(2)).with_eviction_policy(EvictionPolicy::Lfu);
+        assert_eq!(cache.eviction_policy(), EvictionPolicy::Lfu);
+        cache
+            .aupdate("a", "llm", vec![create_test_generation("a")])
+            .await
+            .unwrap();
+        cache
+            .aupdate("b", "llm", vec![create_test_generation("b")])
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        }
+        assert!(cache.alookup("b", "llm").await.unwrap().is_some());
+
+        // "b" is more recent but less frequently used than "a"
+        cache
+            .aupdate("c", "llm", vec![create_test_generation("c")])
+            .await
+            .unwrap();
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        assert!(cache.alookup("b", "llm").await.unwrap().is_none());
+
+        let stats = cache.stats().await;
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.current_size, 2);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_max_bytes() {
+        let cache = InMemoryCache::new().with_max_bytes(200);
+        let generation = |size: usize| vec![create_test_generation(&"x".repeat(size))];
+
+        cache.aupdate("a", "llm", generation(50)).await.unwrap();
+        cache.aupdate("b", "llm", generation(50)).await.unwrap();
+        let stats = cache.stats().await;
+        assert_eq!(stats.current_size, 1);
+        assert!(stats.current_bytes <= 200);
+        assert_eq!(stats.evictions, 1);
+
+        // Entries larger than the limit are not stored
+        cache.aupdate("c", "llm", generation(500)).await.unwrap();
+        assert!(cache.alookup("c", "llm").await.unwrap().is_none());
+        assert!(cache.alookup("b", "llm").await.unwrap().is_some());
+
+        cache.aclear().await.unwrap();
+        assert_eq!(cache.stats().await.current_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_namespaces() {
+        let cache = InMemoryCache::new();
+        let team_a = cache.namespaced("team-a");
+        let team_b = cache.namespaced("team-b");
+        assert_eq!(team_a.namespace(), Some("team-a"));
+
+        team_a
+            .aupdate("p", "llm", vec![create_test_generation("a")])
+            .await
+            .unwrap();
+        team_b
+            .aupdate("p", "llm", vec![create_test_generation("b")])
+            .await
+            .unwrap();
+        cache
+            .aupdate("p", "llm", vec![create_test_generation("root")])
+            .await
+            .unwrap();
+        assert_eq!(cache.size().await, 3);
+        assert_eq!(
+            team_b.alookup("p", "llm").await.unwrap(),
+            Some(vec![create_test_generation("b")])
+        );
+
+        // Clearing a namespaced handle leaves the other namespaces alone
+        team_a.aclear().await.unwrap();
+        assert!(team_a.alookup("p", "llm").await.unwrap().is_none());
+        assert_eq!(cache.size().await, 2);
+
+        cache.clear_namespace("team-b").unwrap();
+        assert!(team_b.alookup("p", "llm").await.unwrap().is_none());
+        assert!(cache.alookup("p", "llm").await.unwrap().is_some());
+
+        cache.aclear().await.unwrap();
+        assert!(cache.is_empty().await);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_in_memory_cache_concurrent_stress() {
+        const TASKS: usize = 8;
+        const THREADS: usize = 4;
+        const OPS: usize = 500;
+        const MAX_SIZE: usize = 64;
+
+        let cache = Arc::new(
+            InMemoryCache::with_max_size(Some(MAX_SIZE)).with_eviction_policy(EvictionPolicy::Lfu),
+        );
+
+        // Async tasks and OS threads using the sync methods run side by side
+        let mut tasks = Vec::new();
+        for task in 0..TASKS {
+            let cache = cache.clone();
+            tasks.push(tokio::spawn(async move {
+                for op in 0..OPS {
+                    let prompt = format!("prompt {}", (task * 7 + op) % 100);
+                    if op % 2 == 0 {
+                        cache
+                            .aupdate(&prompt, "llm", vec![create_test_generation(&prompt)])
+                            .await?;
+                    } else if let Some(cached) = cache.alookup(&prompt, "llm").await? {
+                        assert_eq!(cached[0].text, prompt);
+                    }
+                }
+                Ok::<_, crate::errors::FerricLinkError>(())
+            }));
+        }
+        let threads: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    for op in 0..OPS {
+                        let prompt = format!("prompt {}", (thread * 13 + op) % 100);
+                        if op % 2 == 0 {
+                            cache.update(&prompt, "llm", vec![create_test_generation(&prompt)])?;
+                        } else if let Some(cached) = cache.lookup(&prompt, "llm")? {
+                            assert_eq!(cached[0].text, prompt);
+                        }
+                    }
+                    Ok::<_, crate::errors::FerricLinkError>(())
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+
+        let stats = cache.stats().await;
+        let workers = (TASKS + THREADS) as u64;
+        let half = (OPS / 2) as u64;
+        assert_eq!(stats.updates, workers * half);
+        assert_eq!(stats.total_requests(), workers * half);
+        assert!(stats.current_size <= MAX_SIZE);
+        assert!(stats.evictions > 0);
+
+        // The counters match the entries actually stored
+        let (stored, bytes) = (0..CACHE_SHARDS)
+            .map(|index| {
+                let shard = cache.state.lock(index);
+                assert_eq!(shard.entries.len(), shard.eviction_order.len());
+                let bytes: usize = shard.entries.values().map(|e| e.size_bytes).sum();
+                (shard.entries.len(), bytes)
+            })
+            .fold((0, 0), |(n, b), (sn, sb)| (n + sn, b + sb));
+        assert_eq!(stats.current_size, stored);
+        assert_eq!(stats.current_bytes, bytes);
+    }
+
+    #[tokio::test]
+    async fn test_file_cache_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let generations = vec![create_test_generation("Hello, world!")];
+
+        let cache = FileCache::new(dir.path()).unwrap();
+        assert!(cache.alookup("test", "llm").await.unwrap().is_none());
+        cache
+            .aupdate("test", "llm", generations.clone())
+            .await
+            .unwrap();
+        cache
+            .update("test", "other-llm", vec![create_test_generation("other")])
+            .unwrap();
+
+        // Entries live in sharded directories named after the hashed key
+        let key = FileCache::hash_key("test", "llm");
+        let path = dir
+            .path()
+            .join(&key[0..2])
+            .join(&key[2..4])
+            .join(format!("{key}.json"));
+        assert!(path.exists());
+        assert_ne!(key, FileCache::hash_key("tes", "tllm"));
+
+        // A new cache over the same directory sees the entries
+        let reopened = FileCache::new(dir.path()).unwrap();
+        assert_eq!(reopened.lookup("test", "llm").unwrap(), Some(generations));
+        assert!(reopened.alookup("test", "llm2").await.unwrap().is_none());
+
+        let stats = reopened.stats().await.unwrap();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.current_size, 2);
+        let stats = cache.stats().await.unwrap();
+        assert_eq!((stats.misses, stats.updates), (1, 2));
+
+        // Corrupted entries are misses
+        std::fs::write(&path, "not json").unwrap();
+        assert!(cache.lookup("test", "llm").unwrap().is_none());
+
+        cache.aclear().await.unwrap();
+        assert_eq!(reopened.size().await.unwrap(), 0);
+        assert!(dir.path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_file_cache_ttl_and_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let cache = FileCache::new(dir.path().join("ttl"))
+            .unwrap()
+            .with_ttl(Duration::from_millis(50));
+        cache
+            .aupdate("a", "llm", vec![create_test_generation("a")])
+            .await
+            .unwrap();
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(cache.alookup("a", "llm").await.unwrap().is_none());
+        cache
+            .aupdate("b", "llm", vec![create_test_generation("b")])
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(80)).await;
+
+        // A crashed writer left a temporary file behind
+        let entry_path = cache.entry_path(&FileCache::hash_key("b", "llm"));
+        let shard = entry_path.parent().unwrap();
+        let stale = shard.join(".crashed.tmp");
+        std::fs::File::create(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_TEMP_FILE_AGE * 2)
+            .unwrap();
+        let fresh = shard.join(".writing.tmp");
+        std::fs::File::create(&fresh).unwrap();
+
+        assert_eq!(cache.purge_expired().await.unwrap(), 1);
+        assert!(!stale.exists());
+        assert!(fresh.exists());
+        // Entries are not listed on update without a maximum size
+        assert_eq!(cache.stats().await.unwrap().max_size_reached, 0);
+
+        let cache = FileCache::new(dir.path().join("lru"))
+            .unwrap()
+            .with_max_size(2);
+        for prompt in ["a", "b"] {
+            cache
+                .aupdate(prompt, "llm", vec![create_test_generation(prompt)])
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // Reading "a" makes "b" the least recently used entry
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache
+            .aupdate("c", "llm", vec![create_test_generation("c")])
+            .await
+            .unwrap();
+
+        assert_eq!(cache.size().await.unwrap(), 2);
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        assert!(cache.alookup("b", "llm").await.unwrap().is_none());
+        assert!(cache.alookup("c", "llm").await.unwrap().is_some());
+        let stats = cache.stats().await.unwrap();
+        assert_eq!((stats.max_size_reached, stats.evictions), (2, 1));
+
+        // Going over the maximum size frees a tenth of it at once
+        let cache = FileCache::new(dir.path().join("watermark"))
+            .unwrap()
+            .with_max_size(10);
+        for i in 0..11 {
+            let prompt = format!("prompt {i}");
+            cache
+                .aupdate(&prompt, "llm", vec![create_test_generation(&prompt)])
+                .await
+                .unwrap();
+        }
+        assert_eq!(cache.size().await.unwrap(), 9);
+        cache
+            .aupdate("prompt 11", "llm", vec![create_test_generation("11")])
+            .await
+            .unwrap();
+        // Replacing an entry does not count as a new one
+        cache
+            .aupdate("prompt 11", "llm", vec![create_test_generation("11")])
+            .await
+            .unwrap();
+        assert_eq!(cache.size().await.unwrap(), 10);
+        assert_eq!(cache.stats().await.unwrap().evictions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_file_cache_concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let caches: Vec<FileCache> = (0..4)
+            .map(|_| FileCache::new(dir.path()).unwrap())
+            .collect();
+
+        let tasks = caches.iter().enumerate().flat_map(|(i, cache)| {
+            (0..10).map(move |j| async move {
+                let text = format!("writer {i}");
+                cache
+                    .aupdate(
+                        &format!("prompt {j}"),
+                        "llm",
+                        vec![create_test_generation(&text)],
+                    )
+                    .await
+            })
+        });
+        for result in futures::future::join_all(tasks).await {
+            result.unwrap();
+        }
+
+        // Every entry is complete, and no temporary file is left behind
+        assert_eq!(caches[0].size().await.unwrap(), 10);
+        for shard in FileCache::shard_dirs(dir.path()).unwrap() {
+            for sub_shard in FileCache::shard_dirs(&shard).unwrap() {
+                for file in std::fs::read_dir(sub_shard).unwrap() {
+                    let name = file.unwrap().file_name();
+                    assert!(name.to_string_lossy().ends_with(".json"));
+                }
+            }
+        }
+        for j in 0..10 {
+            let result = caches[0].lookup(&format!("prompt {j}"), "llm").unwrap();
+            assert!(result.unwrap()[0].text.starts_with("writer "));
+        }
+    }
+
+    /// Embeds texts as bags of lowercase words, so that rewordings are similar
+    struct BagOfWords;
+
+    #[async_trait]
+    impl Embeddings for BagOfWords {
+        fn dimension(&self) -> usize {
+            256
+        }
+
+        async fn embed_query(&self, text: &str) -> Result<crate::embeddings::Embedding> {
+            let mut values = vec![0.0; 256];
+            for word in text.to_lowercase().split(|c: char| !c.is_alphanumeric()) {
+                if !word.is_empty() {
+                    let hash = word
+                        .bytes()
+                        .fold(7usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+                    values[hash % 256] += 1.0;
+                }
+            }
+            Ok(crate::embeddings::Embedding::new(values))
+        }
+
+        fn model_name(&self) -> &str {
+            "bag-of-words"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_semantic_cache() {
+        let cache = SemanticCache::new(Arc::new(BagOfWords)).with_similarity_threshold(0.9);
+        let generations = vec![create_test_generation("Paris")];
+
+        assert!(
+            cache
+                .alookup("What is the capital of France?", "llm")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        cache
+            .aupdate("What is the capital of France?", "llm", generations.clone())
+            .await
+            .unwrap();
+
+        // Exact and paraphrased prompts hit, unrelated ones miss
+        assert_eq!(
+            cache
+                .alookup("What is the capital of France?", "llm")
+                .await
+                .unwrap(),
+            Some(generations.clone())
+        );
+        assert_eq!(
+            cache
+                .alookup("what is the capital of france, please", "llm")
+                .await
+                .unwrap(),
+            Some(generations.clone())
+        );
+        assert!(
+            cache
+                .alookup("How tall is Mount Everest?", "llm")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Partitions are separated by LLM string
+        assert!(
+            cache
+                .alookup("What is the capital of France?", "other-llm")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Updating a prompt replaces its entry
+        cache
+            .aupdate(
+                "What is the capital of France?",
+                "llm",
+                vec![create_test_generation("Paris, France")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(cache.size().await, 1);
+
+        let stats = cache.stats().await;
+        assert_eq!((stats.hits, stats.near_hits, stats.misses), (2, 1, 3));
+        assert_eq!(stats.exact_hits(), 1);
+        assert_eq!(stats.updates, 2);
+        assert_eq!(stats.current_size, 1);
+        let similarity = stats.average_near_hit_similarity().unwrap();
+        assert!((0.9..1.0).contains(&similarity));
+
+        cache.aclear().await.unwrap();
+        assert_eq!(cache.size().await, 0);
+        assert!(
+            cache
+                .alookup("What is the capital of France?", "llm")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_semantic_cache_sync_methods() {
+        let partitions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let created = partitions.clone();
+        let cache =
+            SemanticCache::new(Arc::new(BagOfWords)).with_vectorstore_factory(move |llm_string| {
+                created.lock().unwrap().push(llm_string.to_string());
+                Arc::new(InMemoryVectorStore::new()) as Arc<dyn VectorStore>
+            });
+
+        cache
+            .update("hello there", "llm-a", vec![create_test_generation("hi")])
+            .unwrap();
+        cache
+            .update("hello there", "llm-b", vec![create_test_generation("hey")])
+            .unwrap();
+        assert_eq!(
+            cache.lookup("hello there", "llm-b").unwrap(),
+            Some(vec![create_test_generation("hey")])
+        );
+        assert_eq!(*partitions.lock().unwrap(), vec!["llm-a", "llm-b"]);
+
+        cache.clear().unwrap();
+        assert!(cache.lookup("hello there", "llm-a").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_semantic_cache_sync_methods_on_current_thread() {
+        let cache = SemanticCache::new(Arc::new(BagOfWords));
+        assert!(cache.lookup("hello", "llm").is_err());
+    }
 }
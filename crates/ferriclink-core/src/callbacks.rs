@@ -9,7 +9,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::errors::Result;
+use crate::errors::{FerricLinkError, Result};
 use crate::impl_serializable;
 use crate::utils::{colors, print_bold_text, print_colored_text};
 
@@ -426,11 +426,31 @@ impl CallbackHandler for MemoryCallbackHandler {
         }
         Ok(())
     }
+
+    async fn on_run_cancel(&self, run_info: &RunInfo) -> Result<()> {
+        // Update the existing run info
+        if let Some(existing_run) = self
+            .runs
+            .write()
+            .await
+            .iter_mut()
+            .find(|run| run.run_id.id == run_info.run_id.id)
+        {
+            *existing_run = run_info.clone();
+        }
+        Ok(())
+    }
 }
 
 /// A callback manager that manages multiple callback handlers
+///
+/// Handlers are either inheritable, in which case they also receive events
+/// from nested runs, or local, in which case they only see the run they were
+/// attached to.
+#[derive(Clone)]
 pub struct CallbackManager {
     handlers: Vec<Arc<dyn CallbackHandler>>,
+    local_handlers: Vec<Arc<dyn CallbackHandler>>,
 }
 
 impl CallbackManager {
@@ -438,32 +458,54 @@ impl CallbackManager {
     pub fn new() -> Self {
         Self {
             handlers: Vec::new(),
+            local_handlers: Vec::new(),
         }
     }
 
-    /// Add a callback handler
+    /// Add a callback handler that is inherited by nested runs
     pub fn add_handler(&mut self, handler: Arc<dyn CallbackHandler>) {
         self.handlers.push(handler);
     }
 
+    /// Add a callback handler that only receives events for the current run
+    pub fn add_local_handler(&mut self, handler: Arc<dyn CallbackHandler>) {
+        self.local_handlers.push(handler);
+    }
+
+    /// Get the callback manager for a nested run
+    ///
+    /// Only inheritable handlers are passed on to the child.
+    pub fn get_child(&self) -> Self {
+        Self {
+            handlers: self.handlers.clone(),
+            local_handlers: Vec::new(),
+        }
+    }
+
     /// Remove all handlers
     pub fn clear(&mut self) {
         self.handlers.clear();
+        self.local_handlers.clear();
     }
 
     /// Get the number of handlers
     pub fn len(&self) -> usize {
-        self.handlers.len()
+        self.handlers.len() + self.local_handlers.len()
     }
 
     /// Check if there are any handlers
     pub fn is_empty(&self) -> bool {
-        self.handlers.is_empty()
+        self.handlers.is_empty() && self.local_handlers.is_empty()
+    }
+
+    /// Iterate over all handlers, inheritable ones first
+    pub fn handlers(&self) -> impl Iterator<Item = &Arc<dyn CallbackHandler>> {
+        self.handlers.iter().chain(self.local_handlers.iter())
     }
 
     /// Call all handlers for run start
     pub async fn on_run_start(&self, run_info: &RunInfo) -> Result<()> {
-        for handler in &self.handlers {
+        for handler in self.handlers() {
             handler.on_run_start(run_info).await?;
         }
         Ok(())
@@ -471,7 +513,7 @@ impl CallbackManager {
 
     /// Call all handlers for run success
     pub async fn on_run_success(&self, run_info: &RunInfo) -> Result<()> {
-        for handler in &self.handlers {
+        for handler in self.handlers() {
             handler.on_run_success(run_info).await?;
         }
         Ok(())
@@ -479,7 +521,7 @@ impl CallbackManager {
 
     /// Call all handlers for run error
     pub async fn on_run_error(&self, run_info: &RunInfo) -> Result<()> {
-        for handler in &self.handlers {
+        for handler in self.handlers() {
             handler.on_run_error(run_info).await?;
         }
         Ok(())
@@ -487,7 +529,7 @@ impl CallbackManager {
 
     /// Call all handlers for run stream
     pub async fn on_run_stream(&self, run_info: &RunInfo, chunk: &serde_json::Value) -> Result<()> {
-        for handler in &self.handlers {
+        for handler in self.handlers() {
             handler.on_run_stream(run_info, chunk).await?;
         }
         Ok(())
@@ -495,11 +537,20 @@ impl CallbackManager {
 
     /// Call all handlers for run cancel
     pub async fn on_run_cancel(&self, run_info: &RunInfo) -> Result<()> {
-        for handler in &self.handlers {
+        for handler in self.handlers() {
             handler.on_run_cancel(run_info).await?;
         }
         Ok(())
     }
+
+    /// Start a run, notifying all handlers, and return its run manager
+    pub async fn start_run(&self, run_info: RunInfo) -> Result<CallbackRunManager> {
+        self.on_run_start(&run_info).await?;
+        Ok(CallbackRunManager {
+            run_info,
+            callbacks: self.clone(),
+        })
+    }
 }
 
 impl Default for CallbackManager {
@@ -508,6 +559,350 @@ impl Default for CallbackManager {
     }
 }
 
+/// Reports the progress of a single run to a [`CallbackManager`]
+///
+/// Created by [`CallbackManager::start_run`] once the start event has been
+/// emitted.
+pub struct CallbackRunManager {
+    run_info: RunInfo,
+    callbacks: CallbackManager,
+}
+
+impl CallbackRunManager {
+    /// Get the ID of the run
+    pub fn run_id(&self) -> &RunId {
+        &self.run_info.run_id
+    }
+
+    /// Get the run information as recorded at start
+    pub fn run_info(&self) -> &RunInfo {
+        &self.run_info
+    }
+
+    /// Report a streamed chunk of output
+    pub async fn on_stream(&self, chunk: &serde_json::Value) -> Result<()> {
+        self.callbacks.on_run_stream(&self.run_info, chunk).await
+    }
+
+    /// Report that the run completed successfully
+    pub async fn on_success(&self, output: serde_json::Value) -> Result<()> {
+        let run_info = self.run_info.clone().complete_with_output(output);
+        self.callbacks.on_run_success(&run_info).await
+    }
+
+    /// Report that the run failed
+    ///
+    /// Timeouts and cancellations are reported through `on_run_cancel`, every
+    /// other error through `on_run_error`.
+    pub async fn on_error(&self, error: &FerricLinkError) -> Result<()> {
+        let run_info = self.run_info.clone().complete_with_error(error.to_string());
+        if error.is_timeout() || error.is_cancelled() {
+            self.callbacks.on_run_cancel(&run_info).await
+        } else {
+            self.callbacks.on_run_error(&run_info).await
+        }
+    }
+}
+
+/// The lifecycle stage reported by a [`StreamEvent`]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEventKind {
+    /// The component started
+    Start,
+    /// The component produced a chunk of output
+    Stream,
+    /// The component completed successfully
+    End,
+    /// The component failed or was cancelled
+    Error,
+}
+
+impl StreamEventKind {
+    /// Get the string representation used in event names
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamEventKind::Start => "start",
+            StreamEventKind::Stream => "stream",
+            StreamEventKind::End => "end",
+            StreamEventKind::Error => "error",
+        }
+    }
+}
+
+/// Payload of a [`StreamEvent`]
+///
+/// Runnables over generic values cannot always serialize them, so they report
+/// `null` inputs, outputs and chunks unless built with their
+/// `with_serialized_values` builder; see
+/// [`CallbackValues`](crate::runnables::CallbackValues).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct StreamEventData {
+    /// Input of the component (start events)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<serde_json::Value>,
+    /// Output of the component (end events)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    /// Streamed chunk (stream events)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<serde_json::Value>,
+    /// Error message (error events)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A structured event emitted by a component during a run
+///
+/// Events are produced from callbacks, so every runnable that reports its runs
+/// through a [`CallbackManager`] participates automatically.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StreamEvent {
+    /// Event name, e.g. `on_chain_start` or `on_chat_model_stream`
+    pub event: String,
+    /// Lifecycle stage of the event
+    pub kind: StreamEventKind,
+    /// Type of the component (`chain`, `chat_model`, `tool`, ...)
+    pub component_type: String,
+    /// Name of the component
+    pub name: String,
+    /// ID of the run that emitted the event
+    pub run_id: String,
+    /// Tags of the run
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Metadata of the run
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
+    /// IDs of the ancestor runs, from the root to the direct parent
+    #[serde(default)]
+    pub parent_ids: Vec<String>,
+    /// Event payload
+    #[serde(default)]
+    pub data: StreamEventData,
+}
+
+impl StreamEvent {
+    /// Create a new stream event for a run
+    pub fn new(
+        kind: StreamEventKind,
+        run_info: &RunInfo,
+        parent_ids: Vec<String>,
+        data: StreamEventData,
+    ) -> Self {
+        Self {
+            event: format!("on_{}_{}", run_info.component_type, kind.as_str()),
+            kind,
+            component_type: run_info.component_type.clone(),
+            name: run_info.name.clone(),
+            run_id: run_info.run_id.id.clone(),
+            tags: run_info.tags.clone(),
+            metadata: run_info.metadata.clone(),
+            parent_ids,
+            data,
+        }
+    }
+}
+
+impl_serializable!(StreamEvent, ["ferriclink", "callbacks", "stream_event"]);
+
+/// Filters selecting which [`StreamEvent`]s are emitted
+///
+/// An event is emitted if no include filter is set or it matches at least one
+/// of them, and it matches none of the exclude filters.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct StreamEventFilter {
+    /// Only include events from components with these names
+    #[serde(default)]
+    pub include_names: Vec<String>,
+    /// Only include events from components of these types
+    #[serde(default)]
+    pub include_types: Vec<String>,
+    /// Only include events from runs with any of these tags
+    #[serde(default)]
+    pub include_tags: Vec<String>,
+    /// Exclude events from components with these names
+    #[serde(default)]
+    pub exclude_names: Vec<String>,
+    /// Exclude events from components of these types
+    #[serde(default)]
+    pub exclude_types: Vec<String>,
+    /// Exclude events from runs with any of these tags
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+impl StreamEventFilter {
+    /// Create a filter that lets every event through
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include events from components with the given name
+    pub fn include_name(mut self, name: impl Into<String>) -> Self {
+        self.include_names.push(name.into());
+        self
+    }
+
+    /// Include events from components of the given type
+    pub fn include_type(mut self, component_type: impl Into<String>) -> Self {
+        self.include_types.push(component_type.into());
+        self
+    }
+
+    /// Include events from runs with the given tag
+    pub fn include_tag(mut self, tag: impl Into<String>) -> Self {
+        self.include_tags.push(tag.into());
+        self
+    }
+
+    /// Exclude events from components with the given name
+    pub fn exclude_name(mut self, name: impl Into<String>) -> Self {
+        self.exclude_names.push(name.into());
+        self
+    }
+
+    /// Exclude events from components of the given type
+    pub fn exclude_type(mut self, component_type: impl Into<String>) -> Self {
+        self.exclude_types.push(component_type.into());
+        self
+    }
+
+    /// Exclude events from runs with the given tag
+    pub fn exclude_tag(mut self, tag: impl Into<String>) -> Self {
+        self.exclude_tags.push(tag.into());
+        self
+    }
+
+    /// Check if events from the given run pass the filter
+    pub fn matches(&self, run_info: &RunInfo) -> bool {
+        let has_includes = !self.include_names.is_empty()
+            || !self.include_types.is_empty()
+            || !self.include_tags.is_empty();
+        let included = !has_includes
+            || self.include_names.contains(&run_info.name)
+            || self.include_types.contains(&run_info.component_type)
+            || run_info
+                .tags
+                .iter()
+                .any(|tag| self.include_tags.contains(tag));
+
+        let excluded = self.exclude_names.contains(&run_info.name)
+            || self.exclude_types.contains(&run_info.component_type)
+            || run_info
+                .tags
+                .iter()
+                .any(|tag| self.exclude_tags.contains(tag));
+
+        included && !excluded
+    }
+}
+
+impl_serializable!(
+    StreamEventFilter,
+    ["ferriclink", "callbacks", "stream_event_filter"]
+);
+
+/// A callback handler that turns run callbacks into [`StreamEvent`]s
+///
+/// Events passing the filter are sent to the receiver returned by
+/// [`EventStreamCallbackHandler::new`].
+pub struct EventStreamCallbackHandler {
+    filter: StreamEventFilter,
+    sender: tokio::sync::mpsc::UnboundedSender<StreamEvent>,
+    parent_ids: std::sync::Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl EventStreamCallbackHandler {
+    /// Create a new handler and the receiver its events are sent to
+    pub fn new(
+        filter: StreamEventFilter,
+    ) -> (Self, tokio::sync::mpsc::UnboundedReceiver<StreamEvent>) {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let handler = Self {
+            filter,
+            sender,
+            parent_ids: std::sync::Mutex::new(HashMap::new()),
+        };
+        (handler, receiver)
+    }
+
+    /// Get the ancestor IDs of a run, root first
+    fn parent_ids(&self, run_info: &RunInfo) -> Vec<String> {
+        let mut known = self.parent_ids.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ids) = known.get(&run_info.run_id.id) {
+            return ids.clone();
+        }
+
+        let ids = match &run_info.parent_run_id {
+            Some(parent) => {
+                let mut ids = known.get(&parent.id).cloned().unwrap_or_default();
+                ids.push(parent.id.clone());
+                ids
+            }
+            None => Vec::new(),
+        };
+        known.insert(run_info.run_id.id.clone(), ids.clone());
+        ids
+    }
+
+    /// Send an event if it passes the filter
+    fn emit(&self, kind: StreamEventKind, run_info: &RunInfo, data: StreamEventData) {
+        let parent_ids = self.parent_ids(run_info);
+        if self.filter.matches(run_info) {
+            // The receiver may have been dropped, in which case nobody is listening
+            let _ = self
+                .sender
+                .send(StreamEvent::new(kind, run_info, parent_ids, data));
+        }
+    }
+}
+
+#[async_trait]
+impl CallbackHandler for EventStreamCallbackHandler {
+    async fn on_run_start(&self, run_info: &RunInfo) -> Result<()> {
+        let data = StreamEventData {
+            input: Some(run_info.input.clone()),
+            ..Default::default()
+        };
+        self.emit(StreamEventKind::Start, run_info, data);
+        Ok(())
+    }
+
+    async fn on_run_success(&self, run_info: &RunInfo) -> Result<()> {
+        let data = StreamEventData {
+            input: Some(run_info.input.clone()),
+            output: run_info.output.clone(),
+            ..Default::default()
+        };
+        self.emit(StreamEventKind::End, run_info, data);
+        Ok(())
+    }
+
+    async fn on_run_error(&self, run_info: &RunInfo) -> Result<()> {
+        let data = StreamEventData {
+            input: Some(run_info.input.clone()),
+            error: run_info.error.clone(),
+            ..Default::default()
+        };
+        self.emit(StreamEventKind::Error, run_info, data);
+        Ok(())
+    }
+
+    async fn on_run_stream(&self, run_info: &RunInfo, chunk: &serde_json::Value) -> Result<()> {
+        let data = StreamEventData {
+            chunk: Some(chunk.clone()),
+            ..Default::default()
+        };
+        self.emit(StreamEventKind::Stream, run_info, data);
+        Ok(())
+    }
+
+    async fn on_run_cancel(&self, run_info: &RunInfo) -> Result<()> {
+        self.on_run_error(run_info).await
+    }
+}
+
 /// Helper function to create a console callback handler
 pub fn console_callback_handler() -> Arc<ConsoleCallbackHandler> {
     Arc::new(ConsoleCallbackHandler::new())
@@ -666,6 +1061,136 @@ mod tests {
         manager.on_run_success(&run_info).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_callback_manager_inheritance() {
+        let inherited = Arc::new(MemoryCallbackHandler::new());
+        let local = Arc::new(MemoryCallbackHandler::new());
+
+        let mut manager = CallbackManager::new();
+        manager.add_handler(inherited.clone());
+        manager.add_local_handler(local.clone());
+        assert_eq!(manager.len(), 2);
+
+        let child = manager.get_child();
+        assert_eq!(child.len(), 1);
+
+        let run_info = RunInfo::new(RunId::new(), "child", "chain", serde_json::json!(1));
+        child.on_run_start(&run_info).await.unwrap();
+
+        assert_eq!(inherited.len().await, 1);
+        assert!(local.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_callback_run_manager() {
+        let handler = Arc::new(MemoryCallbackHandler::new());
+        let mut manager = CallbackManager::new();
+        manager.add_handler(handler.clone());
+
+        let ok_run = manager
+            .start_run(RunInfo::new(
+                RunId::new(),
+                "ok",
+                "chain",
+                serde_json::json!(1),
+            ))
+            .await
+            .unwrap();
+        ok_run.on_success(serde_json::json!(2)).await.unwrap();
+
+        let cancelled_run = manager
+            .start_run(RunInfo::new(
+                RunId::new(),
+                "slow",
+                "chain",
+                serde_json::json!(1),
+            ))
+            .await
+            .unwrap();
+        cancelled_run
+            .on_error(&FerricLinkError::timeout("too slow"))
+            .await
+            .unwrap();
+
+        let successful = handler.get_successful_runs().await;
+        assert_eq!(successful.len(), 1);
+        assert_eq!(successful[0].output, Some(serde_json::json!(2)));
+
+        let failed = handler.get_failed_runs().await;
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].name, "slow");
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_callback_handler() {
+        let filter = StreamEventFilter::new().exclude_tag("hidden");
+        let (handler, mut receiver) = EventStreamCallbackHandler::new(filter);
+
+        let root = RunInfo::new(RunId::new(), "root", "chain", serde_json::json!("in"));
+        let child = RunInfo::new(RunId::new(), "model", "chat_model", serde_json::json!("in"))
+            .with_parent(root.run_id.clone());
+        let hidden = RunInfo::new(RunId::new(), "secret", "tool", serde_json::json!({}))
+            .with_parent(child.run_id.clone())
+            .add_tag("hidden");
+
+        handler.on_run_start(&root).await.unwrap();
+        handler.on_run_start(&child).await.unwrap();
+        handler.on_run_start(&hidden).await.unwrap();
+        handler
+            .on_run_stream(&child, &serde_json::json!("tok"))
+            .await
+            .unwrap();
+        handler
+            .on_run_success(&root.clone().complete_with_output(serde_json::json!("out")))
+            .await
+            .unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+
+        let names: Vec<_> = events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "on_chain_start",
+                "on_chat_model_start",
+                "on_chat_model_stream",
+                "on_chain_end"
+            ]
+        );
+        assert_eq!(events[1].parent_ids, vec![root.run_id.id.clone()]);
+        assert_eq!(events[2].data.chunk, Some(serde_json::json!("tok")));
+        assert_eq!(events[3].data.output, Some(serde_json::json!("out")));
+    }
+
+    #[test]
+    fn test_stream_event_filter() {
+        let run = RunInfo::new(
+            RunId::new(),
+            "retriever",
+            "retriever",
+            serde_json::json!(""),
+        )
+        .add_tag("rag");
+
+        assert!(StreamEventFilter::new().matches(&run));
+        assert!(StreamEventFilter::new().include_tag("rag").matches(&run));
+        assert!(
+            StreamEventFilter::new()
+                .include_type("retriever")
+                .matches(&run)
+        );
+        assert!(!StreamEventFilter::new().include_name("other").matches(&run));
+        assert!(
+            !StreamEventFilter::new()
+                .include_type("retriever")
+                .exclude_name("retriever")
+                .matches(&run)
+        );
+    }
+
     #[test]
     fn test_serialization() {
         let run_id = RunId::new();
@@ -0,0 +1,778 @@
+//! Chat message history for FerricLink Core
+//!
+//! **Chat message history** stores the messages of a conversation, so that a
+//! chain can be given the previous turns as context.
+//!
+//! **Class hierarchy:**
+//!
+//! ```text
+//! BaseChatMessageHistory --> <name>ChatMessageHistory  # Examples: InMemoryChatMessageHistory, FileChatMessageHistory
+//! ```
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, Weak};
+
+use crate::errors::{FerricLinkError, Result};
+use crate::messages::AnyMessage;
+use crate::runnables::{
+    Runnable, RunnableConfig, merge_config_schemas, run_with_callbacks, to_callback_value,
+};
+
+/// Key of the session ID in [`RunnableConfig::configurable`]
+pub const SESSION_ID_KEY: &str = "session_id";
+
+/// Interface for storing the message history of a conversation.
+///
+/// The interface consists of the following methods:
+///
+/// - messages: Get all messages, oldest first.
+/// - add_messages: Append messages.
+/// - clear: Remove all messages.
+/// - replace_messages: Replace all messages at once.
+/// - replace_messages_if: Replace the oldest messages if they are unchanged.
+///
+/// In addition, the interface provides an async version of each method.
+///
+/// The default implementation of the async methods is to call the synchronous
+/// method. Implementations doing I/O should override the async methods.
+#[async_trait]
+pub trait BaseChatMessageHistory: Send + Sync {
+    /// Get all messages, oldest first.
+    fn messages(&self) -> Result<Vec<AnyMessage>>;
+
+    /// Append messages to the history.
+    fn add_messages(&self, messages: Vec<AnyMessage>) -> Result<()>;
+
+    /// Remove all messages from the history.
+    fn clear(&self) -> Result<()>;
+
+    /// Append a single message to the history.
+    fn add_message(&self, message: AnyMessage) -> Result<()> {
+        self.add_messages(vec![message])
+    }
+
+    /// Append a human message to the history.
+    fn add_user_message(&self, content: &str) -> Result<()> {
+        self.add_message(AnyMessage::human(content))
+    }
+
+    /// Append an AI message to the history.
+    fn add_ai_message(&self, content: &str) -> Result<()> {
+        self.add_message(AnyMessage::ai(content))
+    }
+
+    /// Async get all messages, oldest first.
+    async fn amessages(&self) -> Result<Vec<AnyMessage>> {
+        self.messages()
+    }
+
+    /// Async append messages to the history.
+    async fn aadd_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.add_messages(messages)
+    }
+
+    /// Async remove all messages from the history.
+    async fn aclear(&self) -> Result<()> {
+        self.clear()
+    }
+
+    /// Replace all messages of the history.
+    ///
+    /// The default implementation clears the history and then adds the
+    /// messages, so the history is lost if adding fails. Persistent histories
+    /// should override it to replace the messages atomically.
+    fn replace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.clear()?;
+        self.add_messages(messages)
+    }
+
+    /// Async replace all messages of the history.
+    async fn areplace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.replace_messages(messages)
+    }
+
+    /// Replace the oldest messages of the history if they are still
+    /// `expected_prefix`.
+    ///
+    /// The prefix is replaced by `replacement` and the messages added after it
+    /// are kept. Returns `false`, leaving the history unchanged, if the history
+    /// does not start with `expected_prefix`.
+    ///
+    /// The default implementation reads the messages and then replaces them,
+    /// so changes made in between are lost. Implementations should override it
+    /// to check and replace the messages atomically.
+    fn replace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        let current = self.messages()?;
+        let Some(messages) = replace_prefix(&current, expected_prefix, replacement) else {
+            return Ok(false);
+        };
+        self.replace_messages(messages)?;
+        Ok(true)
+    }
+
+    /// Async replace the oldest messages of the history if they are still
+    /// `expected_prefix`.
+    async fn areplace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        self.replace_messages_if(expected_prefix, replacement)
+    }
+}
+
+/// Get the messages with `expected_prefix` replaced by `replacement`, or `None`
+/// if the messages do not start with `expected_prefix`.
+pub(crate) fn replace_prefix(
+    messages: &[AnyMessage],
+    expected_prefix: &[AnyMessage],
+    replacement: Vec<AnyMessage>,
+) -> Option<Vec<AnyMessage>> {
+    let added = messages.strip_prefix(expected_prefix)?;
+    let mut messages = replacement;
+    messages.extend_from_slice(added);
+    Some(messages)
+}
+
+/// Chat message history that stores messages in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryChatMessageHistory {
+    /// ID of the session the history belongs to
+    session_id: String,
+    /// The stored messages
+    messages: RwLock<Vec<AnyMessage>>,
+}
+
+impl InMemoryChatMessageHistory {
+    /// Create a new empty history for a session.
+    pub fn new(session_id: impl Into<String>) -> Self {
+        Self {
+            session_id: session_id.into(),
+            messages: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Get the session ID.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Get the number of stored messages.
+    pub fn len(&self) -> usize {
+        self.messages
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Check if the history is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl BaseChatMessageHistory for InMemoryChatMessageHistory {
+    fn messages(&self) -> Result<Vec<AnyMessage>> {
+        Ok(self
+            .messages
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone())
+    }
+
+    fn add_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.messages
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(messages);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.messages
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        Ok(())
+    }
+
+    fn replace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        *self.messages.write().unwrap_or_else(|e| e.into_inner()) = messages;
+        Ok(())
+    }
+
+    fn replace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        let mut messages = self.messages.write().unwrap_or_else(|e| e.into_inner());
+        match replace_prefix(&messages, expected_prefix, replacement) {
+            Some(replaced) => {
+                *messages = replaced;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// In-memory histories of many sessions, keyed by session ID.
+///
+/// Cloning the store shares the underlying sessions.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryChatHistoryStore {
+    sessions: Arc<RwLock<HashMap<String, Arc<InMemoryChatMessageHistory>>>>,
+}
+
+impl InMemoryChatHistoryStore {
+    /// Create a new empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the history of a session, creating it if needed.
+    pub fn session(&self, session_id: &str) -> Arc<InMemoryChatMessageHistory> {
+        if let Some(history) = self
+            .sessions
+            .read()
+            .ok()
+            .and_then(|sessions| sessions.get(session_id).cloned())
+        {
+            return history;
+        }
+
+        let mut sessions = self.sessions.write().unwrap_or_else(|e| e.into_inner());
+        sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| Arc::new(InMemoryChatMessageHistory::new(session_id)))
+            .clone()
+    }
+
+    /// Get the IDs of all sessions.
+    pub fn session_ids(&self) -> Vec<String> {
+        let sessions = self.sessions.read().unwrap_or_else(|e| e.into_inner());
+        sessions.keys().cloned().collect()
+    }
+
+    /// Remove the history of a session.
+    pub fn remove(&self, session_id: &str) -> bool {
+        let mut sessions = self.sessions.write().unwrap_or_else(|e| e.into_inner());
+        sessions.remove(session_id).is_some()
+    }
+
+    /// Get a function returning the history of a session, for [`RunnableWithMessageHistory`].
+    pub fn history_factory(
+        &self,
+    ) -> impl Fn(&str) -> Result<Arc<dyn BaseChatMessageHistory>> + Send + Sync + 'static {
+        let store = self.clone();
+        move |session_id| Ok(store.session(session_id) as Arc<dyn BaseChatMessageHistory>)
+    }
+}
+
+/// Chat message history that stores messages in a JSON lines file.
+///
+/// Every line holds one serialized [`AnyMessage`]; new messages are appended.
+/// Writes to the same path are serialized within the process, so
+/// [`replace_messages_if`](BaseChatMessageHistory::replace_messages_if) is
+/// atomic with respect to other histories of this process, but not to other
+/// processes writing the file.
+#[derive(Debug, Clone)]
+pub struct FileChatMessageHistory {
+    path: PathBuf,
+}
+
+impl FileChatMessageHistory {
+    /// Create a new history stored in the given file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Create a new history for a session, stored as `<session_id>.jsonl` in a directory.
+    pub fn for_session(directory: impl AsRef<Path>, session_id: &str) -> Result<Self> {
+        if !crate::utils::is_safe_file_stem(session_id) {
+            return Err(FerricLinkError::validation(format!(
+                "Invalid session ID '{session_id}', only ASCII letters, digits, '-' and '_' are allowed"
+            )));
+        }
+        Ok(Self::new(
+            directory.as_ref().join(format!("{session_id}.jsonl")),
+        ))
+    }
+
+    /// Get the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn parse(content: &str) -> Result<Vec<AnyMessage>> {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Get the path of a temporary file next to the history file.
+    fn temp_path(&self) -> PathBuf {
+        let name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.path
+            .with_file_name(format!(".{name}.{}.tmp", uuid::Uuid::new_v4()))
+    }
+
+    fn encode(messages: &[AnyMessage]) -> Result<String> {
+        let mut lines = String::new();
+        for message in messages {
+            lines.push_str(&serde_json::to_string(message)?);
+            lines.push('\n');
+        }
+        Ok(lines)
+    }
+
+    /// Get the lock serializing the writes to the file within this process.
+    fn write_lock(&self) -> Arc<Mutex<()>> {
+        static LOCKS: OnceLock<Mutex<HashMap<PathBuf, Weak<Mutex<()>>>>> = OnceLock::new();
+
+        let mut locks = LOCKS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(lock) = locks.get(&self.path).and_then(Weak::upgrade) {
+            return lock;
+        }
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(Mutex::new(()));
+        locks.insert(self.path.clone(), Arc::downgrade(&lock));
+        lock
+    }
+
+    /// Write all messages, replacing the file atomically.
+    fn write(&self, messages: &[AnyMessage]) -> Result<()> {
+        let lines = Self::encode(messages)?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first, so the history is never left partial
+        let temp_path = self.temp_path();
+        let result = std::fs::write(&temp_path, lines)
+            .and_then(|()| std::fs::rename(&temp_path, &self.path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        Ok(result?)
+    }
+
+    /// Run a function with a clone of the history on the blocking thread pool.
+    async fn run_blocking<T, F>(&self, func: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(FileChatMessageHistory) -> Result<T> + Send + 'static,
+    {
+        let history = self.clone();
+        tokio::task::spawn_blocking(move || func(history))
+            .await
+            .map_err(|e| FerricLinkError::runtime(format!("History task failed: {e}")))?
+    }
+}
+
+#[async_trait]
+impl BaseChatMessageHistory for FileChatMessageHistory {
+    fn messages(&self) -> Result<Vec<AnyMessage>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn add_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        let lines = Self::encode(&messages)?;
+        let lock = self.write_lock();
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        let lock = self.write_lock();
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn replace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        let lock = self.write_lock();
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.write(&messages)
+    }
+
+    fn replace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        let lock = self.write_lock();
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let current = self.messages()?;
+        match replace_prefix(&current, expected_prefix, replacement) {
+            Some(messages) => {
+                self.write(&messages)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn amessages(&self) -> Result<Vec<AnyMessage>> {
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Writes run on the blocking thread pool, as they hold the file's lock
+    async fn aadd_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.run_blocking(move |history| history.add_messages(messages))
+            .await
+    }
+
+    async fn aclear(&self) -> Result<()> {
+        self.run_blocking(|history| history.clear()).await
+    }
+
+    async fn areplace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.run_blocking(move |history| history.replace_messages(messages))
+            .await
+    }
+
+    async fn areplace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        let expected_prefix = expected_prefix.to_vec();
+        self.run_blocking(move |history| history.replace_messages_if(&expected_prefix, replacement))
+            .await
+    }
+}
+
+/// Function returning the history of a session
+pub type GetSessionHistory =
+    Arc<dyn Fn(&str) -> Result<Arc<dyn BaseChatMessageHistory>> + Send + Sync>;
+
+/// A runnable that keeps the message history of a chat chain.
+///
+/// The session is selected by the `session_id` configurable value. On every
+/// invocation the stored history is prepended to the new input messages, and
+/// the input messages and the chain's reply are appended to the history.
+pub struct RunnableWithMessageHistory {
+    runnable: Arc<dyn Runnable<Vec<AnyMessage>, AnyMessage>>,
+    get_session_history: GetSessionHistory,
+}
+
+impl RunnableWithMessageHistory {
+    /// Create a new runnable with message history.
+    pub fn new<F>(
+        runnable: Arc<dyn Runnable<Vec<AnyMessage>, AnyMessage>>,
+        get_session_history: F,
+    ) -> Self
+    where
+        F: Fn(&str) -> Result<Arc<dyn BaseChatMessageHistory>> + Send + Sync + 'static,
+    {
+        Self {
+            runnable,
+            get_session_history: Arc::new(get_session_history),
+        }
+    }
+
+    /// Get the history of the session selected by a configuration.
+    pub fn session_history(
+        &self,
+        config: &RunnableConfig,
+    ) -> Result<Arc<dyn BaseChatMessageHistory>> {
+        let session_id = config
+            .configurable
+            .get(SESSION_ID_KEY)
+            .and_then(|id| id.as_str())
+            .ok_or_else(|| {
+                FerricLinkError::configuration(format!(
+                    "Missing '{SESSION_ID_KEY}' in the configurable values"
+                ))
+            })?;
+        (self.get_session_history)(session_id)
+    }
+}
+
+#[async_trait]
+impl Runnable<Vec<AnyMessage>, AnyMessage> for RunnableWithMessageHistory {
+    async fn invoke(
+        &self,
+        input: Vec<AnyMessage>,
+        config: Option<RunnableConfig>,
+    ) -> Result<AnyMessage> {
+        let mut config = config.unwrap_or_default();
+        config.validate_configurable(|| self.config_schema())?;
+        let history = self.session_history(&config)?;
+
+        run_with_callbacks(
+            Some(config),
+            self.name(),
+            "chain",
+            to_callback_value(&input),
+            to_callback_value,
+            |run_config| async move {
+                let mut messages = history.amessages().await?;
+                messages.extend(input.iter().cloned());
+
+                let output = self
+                    .runnable
+                    .invoke(messages, Some(run_config.child()))
+                    .await?;
+
+                let mut turn = input;
+                turn.push(output.clone());
+                history.aadd_messages(turn).await?;
+                Ok(output)
+            },
+        )
+        .await
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        let session = serde_json::json!({
+            "properties": {
+                SESSION_ID_KEY: {
+                    "type": "string",
+                    "description": "ID of the conversation whose history is used",
+                },
+            },
+        });
+        merge_config_schemas([Some(session), self.runnable.config_schema()])
+    }
+
+    fn get_graph(&self) -> crate::graph::Graph {
+        self.runnable.get_graph()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language_models::{MockChatModel, RunnableChatModel};
+    use crate::messages::BaseMessage;
+
+    fn texts(messages: &[AnyMessage]) -> Vec<String> {
+        messages.iter().map(|m| m.text()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_history() {
+        let history = InMemoryChatMessageHistory::new("s1");
+        assert_eq!(history.session_id(), "s1");
+        assert!(history.is_empty());
+
+        history.add_user_message("Hello").unwrap();
+        history
+            .aadd_messages(vec![AnyMessage::ai("Hi!")])
+            .await
+            .unwrap();
+        assert_eq!(
+            texts(&history.amessages().await.unwrap()),
+            vec!["Hello", "Hi!"]
+        );
+
+        // The oldest messages are only replaced while they are unchanged
+        let read = history.messages().unwrap();
+        history.add_user_message("Late").unwrap();
+        assert!(
+            history
+                .areplace_messages_if(&read, vec![AnyMessage::system("Summary")])
+                .await
+                .unwrap()
+        );
+        assert_eq!(texts(&history.messages().unwrap()), vec!["Summary", "Late"]);
+        assert!(
+            !history
+                .replace_messages_if(&read, vec![AnyMessage::system("Stale")])
+                .unwrap()
+        );
+        assert_eq!(texts(&history.messages().unwrap()), vec!["Summary", "Late"]);
+
+        history.aclear().await.unwrap();
+        assert!(history.messages().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_in_memory_history_store() {
+        let store = InMemoryChatHistoryStore::new();
+        store.session("a").add_user_message("for a").unwrap();
+        store
+            .clone()
+            .session("b")
+            .add_user_message("for b")
+            .unwrap();
+
+        assert_eq!(
+            texts(&store.session("a").messages().unwrap()),
+            vec!["for a"]
+        );
+        let mut ids = store.session_ids();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        assert!(store.remove("a"));
+        assert!(store.session("a").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_file_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = FileChatMessageHistory::for_session(dir.path(), "chat-1").unwrap();
+
+        history.add_user_message("Hello").unwrap();
+        history
+            .aadd_messages(vec![
+                AnyMessage::ai("Hi!"),
+                AnyMessage::tool("42", "call_1"),
+            ])
+            .await
+            .unwrap();
+
+        // The file holds one serialized message per line
+        let content = std::fs::read_to_string(history.path()).unwrap();
+        assert_eq!(content.lines().count(), 3);
+
+        let reopened = FileChatMessageHistory::new(dir.path().join("chat-1.jsonl"));
+        let messages = reopened.amessages().await.unwrap();
+        assert_eq!(texts(&messages), vec!["Hello", "Hi!", "42"]);
+        assert!(messages[2].is_tool());
+        assert_eq!(reopened.messages().unwrap(), messages);
+
+        // Replacing rewrites the file without leaving temporary files
+        history
+            .areplace_messages(vec![AnyMessage::system("Summary")])
+            .await
+            .unwrap();
+        reopened
+            .replace_messages(vec![AnyMessage::system("Summary"), AnyMessage::ai("Hi!")])
+            .unwrap();
+        assert_eq!(texts(&history.messages().unwrap()), vec!["Summary", "Hi!"]);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Replacing the oldest messages keeps the ones added after them
+        let read = history.messages().unwrap();
+        reopened.add_user_message("Late").unwrap();
+        assert!(
+            history
+                .areplace_messages_if(&read[..1], vec![AnyMessage::system("New")])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            texts(&reopened.messages().unwrap()),
+            vec!["New", "Hi!", "Late"]
+        );
+        assert!(
+            !reopened
+                .replace_messages_if(&read, vec![AnyMessage::system("Stale")])
+                .unwrap()
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        reopened.clear().unwrap();
+        assert!(history.amessages().await.unwrap().is_empty());
+
+        assert!(FileChatMessageHistory::for_session(dir.path(), "../x").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_runnable_with_message_history() {
+        let store = InMemoryChatHistoryStore::new();
+        let echo: Arc<dyn Runnable<Vec<AnyMessage>, AnyMessage>> =
+            crate::runnables::runnable(|messages: Vec<AnyMessage>| {
+                Ok(AnyMessage::ai(format!("seen {} messages", messages.len())))
+            });
+        let chain = RunnableWithMessageHistory::new(echo, store.history_factory());
+
+        let config = RunnableConfig::new().with_configurable(SESSION_ID_KEY, "s1".into());
+        let reply = chain
+            .invoke(vec![AnyMessage::human("one")], Some(config.clone()))
+            .await
+            .unwrap();
+        assert_eq!(reply.text(), "seen 1 messages");
+
+        let reply = chain
+            .invoke(vec![AnyMessage::human("two")], Some(config))
+            .await
+            .unwrap();
+        assert_eq!(reply.text(), "seen 3 messages");
+        assert_eq!(
+            texts(&store.session("s1").messages().unwrap()),
+            vec!["one", "seen 1 messages", "two", "seen 3 messages"]
+        );
+
+        // Sessions are independent
+        let other = RunnableConfig::new().with_configurable(SESSION_ID_KEY, "s2".into());
+        let reply = chain
+            .invoke(vec![AnyMessage::human("hi")], Some(other))
+            .await
+            .unwrap();
+        assert_eq!(reply.text(), "seen 1 messages");
+
+        // The session ID is required
+        assert!(
+            chain
+                .invoke(vec![AnyMessage::human("hi")], None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_runnable_with_message_history_and_chat_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_path_buf();
+        let model = Arc::new(RunnableChatModel::new(
+            MockChatModel::new("mock").add_response("Nice to meet you"),
+        ));
+        let chain = RunnableWithMessageHistory::new(model, move |session_id| {
+            Ok(
+                Arc::new(FileChatMessageHistory::for_session(&path, session_id)?)
+                    as Arc<dyn BaseChatMessageHistory>,
+            )
+        });
+
+        let config = RunnableConfig::new().with_configurable(SESSION_ID_KEY, "user-7".into());
+        chain
+            .invoke(vec![AnyMessage::human("I'm Ada")], Some(config))
+            .await
+            .unwrap();
+
+        let history = FileChatMessageHistory::for_session(dir.path(), "user-7").unwrap();
+        assert_eq!(
+            texts(&history.messages().unwrap()),
+            vec!["I'm Ada", "Nice to meet you"]
+        );
+    }
+}
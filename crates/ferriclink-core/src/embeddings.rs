@@ -4,9 +4,13 @@
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::errors::Result;
+use crate::errors::{FerricLinkError, Result};
 use crate::impl_serializable;
+use crate::stores::ByteStore;
+use crate::utils::hash_key_parts;
 
 /// A text embedding
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -164,6 +168,190 @@ impl Embeddings for MockEmbeddings {
     }
 }
 
+/// Embedding model caching the embeddings of another model in a byte store
+///
+/// Embeddings are stored as JSON under a hash of the namespace, the kind of
+/// embedding (query or document) and the text. The namespace defaults to the
+/// model name of the underlying model, so models sharing a store never read
+/// each other's embeddings. Only texts missing from the store are sent to the
+/// underlying model. Queries are not cached unless a query store is set; they
+/// are then embedded with [`Embeddings::embed_query`] and never share entries
+/// with documents, even in the same store.
+pub struct CacheBackedEmbeddings {
+    /// The underlying embedding model
+    embeddings: Arc<dyn Embeddings>,
+    /// Store for document embeddings
+    document_store: Arc<dyn ByteStore>,
+    /// Store for query embeddings
+    query_store: Option<Arc<dyn ByteStore>>,
+    /// Namespace of the cache keys
+    namespace: String,
+}
+
+impl CacheBackedEmbeddings {
+    /// Create a new cache-backed embedding model
+    pub fn new(embeddings: Arc<dyn Embeddings>, document_store: Arc<dyn ByteStore>) -> Self {
+        let namespace = embeddings.model_name().to_string();
+        Self {
+            embeddings,
+            document_store,
+            query_store: None,
+            namespace,
+        }
+    }
+
+    /// Also cache query embeddings, in the given store
+    pub fn with_query_store(mut self, query_store: Arc<dyn ByteStore>) -> Self {
+        self.query_store = Some(query_store);
+        self
+    }
+
+    /// Also cache query embeddings, in the document store
+    pub fn with_query_caching(mut self) -> Self {
+        self.query_store = Some(self.document_store.clone());
+        self
+    }
+
+    /// Set the namespace of the cache keys, instead of the model name
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Get the namespace of the cache keys
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Get the store key of the document embedding of a text
+    pub fn cache_key(&self, text: &str) -> String {
+        hash_key_parts(&[&self.namespace, "document", text])
+    }
+
+    /// Get the store key of the query embedding of a text
+    pub fn query_cache_key(&self, text: &str) -> String {
+        hash_key_parts(&[&self.namespace, "query", text])
+    }
+
+    /// Get the embeddings of texts, from the store when possible
+    ///
+    /// Missing texts are embedded as queries if `query` is set, and as
+    /// documents otherwise.
+    async fn embed_cached(
+        &self,
+        store: &dyn ByteStore,
+        texts: &[String],
+        query: bool,
+    ) -> Result<Vec<Embedding>> {
+        let cache_key = |text: &str| {
+            if query {
+                self.query_cache_key(text)
+            } else {
+                self.cache_key(text)
+            }
+        };
+        let keys: Vec<String> = texts.iter().map(|text| cache_key(text)).collect();
+        let mut embeddings = store
+            .amget(&keys)
+            .await?
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|bytes| serde_json::from_slice(&bytes))
+                    .transpose()
+            })
+            .collect::<std::result::Result<Vec<Option<Embedding>>, _>>()?;
+
+        // Embed every missing text once, even if it is repeated
+        let mut missing: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut missing_texts = Vec::new();
+        for (index, embedding) in embeddings.iter().enumerate() {
+            if embedding.is_none() {
+                let positions = missing.entry(texts[index].as_str()).or_default();
+                if positions.is_empty() {
+                    missing_texts.push(texts[index].clone());
+                }
+                positions.push(index);
+            }
+        }
+
+        if !missing_texts.is_empty() {
+            let computed = if query {
+                let mut computed = Vec::with_capacity(missing_texts.len());
+                for text in &missing_texts {
+                    computed.push(self.embeddings.embed_query(text).await?);
+                }
+                computed
+            } else {
+                self.embeddings.embed_documents(&missing_texts).await?
+            };
+            if computed.len() != missing_texts.len() {
+                return Err(FerricLinkError::runtime(format!(
+                    "Embedding model '{}' returned {} embeddings for {} texts",
+                    self.embeddings.model_name(),
+                    computed.len(),
+                    missing_texts.len()
+                )));
+            }
+
+            let mut items = Vec::with_capacity(computed.len());
+            for (text, embedding) in missing_texts.iter().zip(computed) {
+                items.push((cache_key(text), serde_json::to_vec(&embedding)?));
+                for &index in &missing[text.as_str()] {
+                    embeddings[index] = Some(embedding.clone());
+                }
+            }
+            store.amset(items).await?;
+        }
+
+        Ok(embeddings.into_iter().flatten().collect())
+    }
+}
+
+impl std::fmt::Debug for CacheBackedEmbeddings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheBackedEmbeddings")
+            .field("model_name", &self.embeddings.model_name())
+            .field("namespace", &self.namespace)
+            .field("query_caching", &self.query_store.is_some())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Embeddings for CacheBackedEmbeddings {
+    fn dimension(&self) -> usize {
+        self.embeddings.dimension()
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Embedding> {
+        match &self.query_store {
+            Some(store) => {
+                let mut embeddings = self
+                    .embed_cached(store.as_ref(), &[text.to_string()], true)
+                    .await?;
+                embeddings.pop().ok_or_else(|| {
+                    FerricLinkError::runtime("Embedding model returned no embedding")
+                })
+            }
+            None => self.embeddings.embed_query(text).await,
+        }
+    }
+
+    async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Embedding>> {
+        self.embed_cached(self.document_store.as_ref(), texts, false)
+            .await
+    }
+
+    fn model_name(&self) -> &str {
+        self.embeddings.model_name()
+    }
+
+    fn model_type(&self) -> &str {
+        "cache_backed_embeddings"
+    }
+}
+
 /// Helper function to create a mock embedding model
 pub fn mock_embeddings(model_name: impl Into<String>, dimension: usize) -> MockEmbeddings {
     MockEmbeddings::new(model_name, dimension)
@@ -244,4 +432,183 @@ mod tests {
         let deserialized: Embedding = Embedding::from_json(&json).unwrap();
         assert_eq!(embedding, deserialized);
     }
+
+    /// Embedding model recording the texts it embeds
+    struct CountingEmbeddings {
+        inner: MockEmbeddings,
+        calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl CountingEmbeddings {
+        fn new(model_name: &str) -> Arc<Self> {
+            Arc::new(Self {
+                inner: MockEmbeddings::new(model_name, 8),
+                calls: std::sync::Mutex::new(Vec::new()),
+            })
+        }
+
+        fn take_calls(&self) -> Vec<String> {
+            std::mem::take(&mut *self.calls.lock().unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl Embeddings for CountingEmbeddings {
+        fn dimension(&self) -> usize {
+            self.inner.dimension()
+        }
+
+        async fn embed_query(&self, text: &str) -> Result<Embedding> {
+            self.calls.lock().unwrap().push(text.to_string());
+            self.inner.embed_query(text).await
+        }
+
+        fn model_name(&self) -> &str {
+            self.inner.model_name()
+        }
+    }
+
+    fn texts(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_cache_backed_embeddings_only_embeds_misses() {
+        let model = CountingEmbeddings::new("model-a");
+        let store = Arc::new(crate::stores::InMemoryByteStore::new());
+        let cached = CacheBackedEmbeddings::new(model.clone(), store.clone());
+
+        let first = cached
+            .embed_documents(&texts(&["a", "b", "a"]))
+            .await
+            .unwrap();
+        assert_eq!(model.take_calls(), texts(&["a", "b"]));
+        assert_eq!(first[0], first[2]);
+        assert_eq!(store.len(), 2);
+
+        let second = cached
+            .embed_documents(&texts(&["b", "c", "a"]))
+            .await
+            .unwrap();
+        assert_eq!(model.take_calls(), texts(&["c"]));
+        assert_eq!(second[0], first[1]);
+        assert_eq!(second[2], first[0]);
+        assert_eq!(second[1], model.inner.embed_query("c").await.unwrap());
+
+        // Queries are not cached by default
+        cached.embed_query("a").await.unwrap();
+        cached.embed_query("a").await.unwrap();
+        assert_eq!(model.take_calls(), texts(&["a", "a"]));
+        assert_eq!(store.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cache_backed_embeddings_query_caching() {
+        let model = CountingEmbeddings::new("model-a");
+        let store = Arc::new(crate::stores::InMemoryByteStore::new());
+        let query_store = Arc::new(crate::stores::InMemoryByteStore::new());
+        let cached = CacheBackedEmbeddings::new(model.clone(), store.clone())
+            .with_query_store(query_store.clone());
+
+        let first = cached.embed_query("q").await.unwrap();
+        let second = cached.embed_query("q").await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(model.take_calls(), texts(&["q"]));
+        assert_eq!(query_store.len(), 1);
+        assert!(store.is_empty());
+    }
+
+    /// Embedding model whose document embeddings differ from its query embeddings
+    struct AsymmetricEmbeddings {
+        inner: MockEmbeddings,
+    }
+
+    #[async_trait]
+    impl Embeddings for AsymmetricEmbeddings {
+        fn dimension(&self) -> usize {
+            self.inner.dimension()
+        }
+
+        async fn embed_query(&self, text: &str) -> Result<Embedding> {
+            self.inner.embed_query(text).await
+        }
+
+        async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Embedding>> {
+            let mut embeddings = Vec::with_capacity(texts.len());
+            for text in texts {
+                embeddings.push(self.inner.embed_query(&format!("document: {text}")).await?);
+            }
+            Ok(embeddings)
+        }
+
+        fn model_name(&self) -> &str {
+            self.inner.model_name()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_backed_embeddings_keeps_queries_and_documents_apart() {
+        let model = Arc::new(AsymmetricEmbeddings {
+            inner: MockEmbeddings::new("model-a", 8),
+        });
+        let store = Arc::new(crate::stores::InMemoryByteStore::new());
+        let cached = CacheBackedEmbeddings::new(model.clone(), store.clone()).with_query_caching();
+        assert_ne!(cached.cache_key("x"), cached.query_cache_key("x"));
+
+        let document = model.embed_documents(&texts(&["x"])).await.unwrap();
+        let query = model.embed_query("x").await.unwrap();
+        assert_ne!(document[0], query);
+
+        // Each kind is computed by its own method and cached separately
+        assert_eq!(
+            cached.embed_documents(&texts(&["x"])).await.unwrap(),
+            document
+        );
+        assert_eq!(cached.embed_query("x").await.unwrap(), query);
+        assert_eq!(store.len(), 2);
+        assert_eq!(cached.embed_query("x").await.unwrap(), query);
+        assert_eq!(
+            cached.embed_documents(&texts(&["x"])).await.unwrap(),
+            document
+        );
+        assert_eq!(store.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_backed_embeddings_namespaces() {
+        let store = Arc::new(crate::stores::InMemoryByteStore::new());
+        let model_a = CountingEmbeddings::new("model-a");
+        let model_b = CountingEmbeddings::new("model-b");
+        let cached_a = CacheBackedEmbeddings::new(model_a.clone(), store.clone());
+        let cached_b = CacheBackedEmbeddings::new(model_b.clone(), store.clone());
+        assert_eq!(cached_a.namespace(), "model-a");
+        assert_ne!(cached_a.cache_key("x"), cached_b.cache_key("x"));
+
+        cached_a.embed_documents(&texts(&["x"])).await.unwrap();
+        cached_b.embed_documents(&texts(&["x"])).await.unwrap();
+        assert_eq!(model_a.take_calls(), texts(&["x"]));
+        assert_eq!(model_b.take_calls(), texts(&["x"]));
+        assert_eq!(store.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_backed_embeddings_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let model = CountingEmbeddings::new("model-a");
+        let store = Arc::new(crate::stores::LocalFileStore::new(dir.path()).unwrap());
+        let first = CacheBackedEmbeddings::new(model.clone(), store)
+            .embed_documents(&texts(&["a", "b"]))
+            .await
+            .unwrap();
+        assert_eq!(model.take_calls(), texts(&["a", "b"]));
+
+        // A new store over the same directory reuses the embeddings
+        let store = Arc::new(crate::stores::LocalFileStore::new(dir.path()).unwrap());
+        let second = CacheBackedEmbeddings::new(model.clone(), store)
+            .embed_documents(&texts(&["a", "b"]))
+            .await
+            .unwrap();
+        assert!(model.take_calls().is_empty());
+        assert_eq!(first, second);
+    }
 }
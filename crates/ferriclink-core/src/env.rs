@@ -274,6 +274,8 @@ fn get_relevant_env_vars() -> HashMap<String, String> {
 }
 
 /// Get enabled features at compile time
+// The pushes depend on the enabled features, so they cannot be a `vec![]`
+#[allow(clippy::vec_init_then_push)]
 fn get_enabled_features() -> Vec<String> {
     let mut features = Vec::new();
 
@@ -284,6 +286,9 @@ fn get_enabled_features() -> Vec<String> {
     #[cfg(feature = "validation")]
     features.push("validation".to_string());
 
+    #[cfg(feature = "sqlite")]
+    features.push("sqlite".to_string());
+
     #[cfg(feature = "all")]
     features.push("all".to_string());
 
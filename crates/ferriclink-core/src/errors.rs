@@ -38,6 +38,10 @@ pub enum ErrorCode {
     ConfigurationError,
     /// Runtime error
     RuntimeError,
+    /// Operation exceeded its deadline
+    Timeout,
+    /// Operation was cancelled
+    Cancelled,
     /// Feature not implemented
     NotImplemented,
     /// Generic error
@@ -61,6 +65,8 @@ impl ErrorCode {
             ErrorCode::ValidationError => "VALIDATION_ERROR",
             ErrorCode::ConfigurationError => "CONFIGURATION_ERROR",
             ErrorCode::RuntimeError => "RUNTIME_ERROR",
+            ErrorCode::Timeout => "TIMEOUT",
+            ErrorCode::Cancelled => "CANCELLED",
             ErrorCode::NotImplemented => "NOT_IMPLEMENTED",
             ErrorCode::GenericError => "GENERIC_ERROR",
         }
@@ -109,6 +115,11 @@ pub enum FerricLinkError {
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
 
+    /// SQLite errors
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
     /// Validation errors
     #[error("Validation error: {0}")]
     Validation(String),
@@ -121,6 +132,14 @@ pub enum FerricLinkError {
     #[error("Runtime error: {0}")]
     Runtime(String),
 
+    /// Timeout errors, raised when a run exceeds its deadline
+    #[error("Timeout: {0}")]
+    Timeout(String),
+
+    /// Cancellation errors, raised when a run is cancelled
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
     /// Not implemented errors
     #[error("Not implemented: {0}")]
     NotImplemented(String),
@@ -254,6 +273,26 @@ impl FerricLinkError {
         Self::Runtime(msg.into())
     }
 
+    /// Create a new timeout error
+    pub fn timeout(msg: impl Into<String>) -> Self {
+        Self::Timeout(msg.into())
+    }
+
+    /// Create a new cancellation error
+    pub fn cancelled(msg: impl Into<String>) -> Self {
+        Self::Cancelled(msg.into())
+    }
+
+    /// Check if this error was caused by a timeout
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, FerricLinkError::Timeout(_))
+    }
+
+    /// Check if this error was caused by cancellation
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, FerricLinkError::Cancelled(_))
+    }
+
     /// Create a new not implemented error
     pub fn not_implemented(msg: impl Into<String>) -> Self {
         Self::NotImplemented(msg.into())
@@ -286,6 +325,8 @@ impl FerricLinkError {
             ErrorCode::ValidationError => Self::validation(msg),
             ErrorCode::ConfigurationError => Self::configuration(msg),
             ErrorCode::RuntimeError => Self::runtime(msg),
+            ErrorCode::Timeout => Self::timeout(msg),
+            ErrorCode::Cancelled => Self::cancelled(msg),
             ErrorCode::NotImplemented => Self::not_implemented(msg),
             ErrorCode::GenericError => Self::generic(msg),
         }
@@ -300,9 +341,13 @@ impl FerricLinkError {
             FerricLinkError::Io(_) => Some(ErrorCode::IoError),
             #[cfg(feature = "http")]
             FerricLinkError::Http(_) => Some(ErrorCode::HttpError),
+            #[cfg(feature = "sqlite")]
+            FerricLinkError::Sqlite(_) => Some(ErrorCode::IoError),
             FerricLinkError::Validation(_) => Some(ErrorCode::ValidationError),
             FerricLinkError::Configuration(_) => Some(ErrorCode::ConfigurationError),
             FerricLinkError::Runtime(_) => Some(ErrorCode::RuntimeError),
+            FerricLinkError::Timeout(_) => Some(ErrorCode::Timeout),
+            FerricLinkError::Cancelled(_) => Some(ErrorCode::Cancelled),
             FerricLinkError::NotImplemented(_) => Some(ErrorCode::NotImplemented),
             FerricLinkError::General(msg) => {
                 // Try to determine error code from message content
@@ -437,6 +482,19 @@ mod tests {
         assert!(matches!(runtime_err, FerricLinkError::Runtime(_)));
     }
 
+    #[test]
+    fn test_timeout_and_cancelled_errors() {
+        let timeout = FerricLinkError::timeout("took too long");
+        assert!(timeout.is_timeout());
+        assert!(!timeout.is_cancelled());
+        assert_eq!(timeout.error_code(), Some(ErrorCode::Timeout));
+
+        let cancelled = FerricLinkError::with_error_code("stopped", ErrorCode::Cancelled);
+        assert!(cancelled.is_cancelled());
+        assert!(!matches!(cancelled, FerricLinkError::Runtime(_)));
+        assert_eq!(cancelled.error_code(), Some(ErrorCode::Cancelled));
+    }
+
     #[test]
     fn test_error_display() {
         let err = FerricLinkError::validation("test error");
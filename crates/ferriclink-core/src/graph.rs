@@ -0,0 +1,741 @@
+//! Graph representation of runnables for FerricLink Core
+//!
+//! Runnables describe their structure as a [`Graph`] of nodes and edges, which
+//! can be rendered as Mermaid, DOT or a terminal ASCII diagram.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::impl_serializable;
+
+/// A node in a runnable graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    /// Unique ID of the node within its graph
+    pub id: String,
+    /// Display name of the node
+    pub name: String,
+    /// Component type of the node (e.g. `tool`, `retriever`, `chat_model`)
+    pub component_type: Option<String>,
+    /// JSON Schema of the node's input
+    pub input_schema: Option<serde_json::Value>,
+    /// JSON Schema of the node's output
+    pub output_schema: Option<serde_json::Value>,
+}
+
+impl Node {
+    /// Create a new node, using the name as its ID
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            id: name.clone(),
+            name,
+            component_type: None,
+            input_schema: None,
+            output_schema: None,
+        }
+    }
+
+    /// Set the component type of the node
+    pub fn with_component_type(mut self, component_type: impl Into<String>) -> Self {
+        self.component_type = Some(component_type.into());
+        self
+    }
+
+    /// Set the input schema of the node
+    pub fn with_input_schema(mut self, schema: Option<serde_json::Value>) -> Self {
+        self.input_schema = schema;
+        self
+    }
+
+    /// Set the output schema of the node
+    pub fn with_output_schema(mut self, schema: Option<serde_json::Value>) -> Self {
+        self.output_schema = schema;
+        self
+    }
+}
+
+impl_serializable!(Node, ["ferriclink", "graph", "node"]);
+
+/// A directed edge between two nodes of a runnable graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    /// ID of the source node
+    pub source: String,
+    /// ID of the target node
+    pub target: String,
+    /// Optional label of the edge
+    pub label: Option<String>,
+    /// Whether the edge is only taken under some condition
+    pub conditional: bool,
+}
+
+impl Edge {
+    /// Create a new unconditional edge
+    pub fn new(source: impl Into<String>, target: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            target: target.into(),
+            label: None,
+            conditional: false,
+        }
+    }
+
+    /// Set the label of the edge
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Mark the edge as conditional
+    pub fn with_conditional(mut self, conditional: bool) -> Self {
+        self.conditional = conditional;
+        self
+    }
+}
+
+impl_serializable!(Edge, ["ferriclink", "graph", "edge"]);
+
+/// A graph of nodes and edges describing the structure of a runnable
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Graph {
+    /// Nodes of the graph, in insertion order
+    pub nodes: Vec<Node>,
+    /// Edges of the graph, in insertion order
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    /// Create a new empty graph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a graph with a single node
+    pub fn from_node(node: Node) -> Self {
+        let mut graph = Self::new();
+        graph.add_node(node);
+        graph
+    }
+
+    /// Add a node, renaming its ID if it is already taken, and return the ID
+    pub fn add_node(&mut self, mut node: Node) -> String {
+        if self.node(&node.id).is_some() {
+            let base = node.id.clone();
+            node.id = (1..)
+                .map(|n| format!("{base}_{n}"))
+                .find(|id| self.node(id).is_none())
+                .unwrap_or(base);
+        }
+        let id = node.id.clone();
+        self.nodes.push(node);
+        id
+    }
+
+    /// Add an edge between two nodes
+    pub fn add_edge(&mut self, edge: Edge) {
+        self.edges.push(edge);
+    }
+
+    /// Get a node by ID
+    pub fn node(&self, id: &str) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Get the IDs of the nodes without incoming edges
+    pub fn first_nodes(&self) -> Vec<String> {
+        let targets: HashSet<_> = self.edges.iter().map(|e| e.target.as_str()).collect();
+        self.nodes
+            .iter()
+            .filter(|node| !targets.contains(node.id.as_str()))
+            .map(|node| node.id.clone())
+            .collect()
+    }
+
+    /// Get the IDs of the nodes without outgoing edges
+    pub fn last_nodes(&self) -> Vec<String> {
+        let sources: HashSet<_> = self.edges.iter().map(|e| e.source.as_str()).collect();
+        self.nodes
+            .iter()
+            .filter(|node| !sources.contains(node.id.as_str()))
+            .map(|node| node.id.clone())
+            .collect()
+    }
+
+    /// Get the only node without incoming edges, if there is exactly one
+    pub fn first_node(&self) -> Option<&Node> {
+        match self.first_nodes().as_slice() {
+            [id] => self.node(id),
+            _ => None,
+        }
+    }
+
+    /// Get the only node without outgoing edges, if there is exactly one
+    pub fn last_node(&self) -> Option<&Node> {
+        match self.last_nodes().as_slice() {
+            [id] => self.node(id),
+            _ => None,
+        }
+    }
+
+    /// Add all nodes and edges of another graph
+    ///
+    /// Node IDs are renamed where they clash. Returns the IDs of the first
+    /// and last nodes of the added graph, so it can be connected.
+    pub fn extend(&mut self, other: Graph) -> (Vec<String>, Vec<String>) {
+        let first = other.first_nodes();
+        let last = other.last_nodes();
+
+        let mut ids = HashMap::new();
+        for node in other.nodes {
+            let old_id = node.id.clone();
+            let new_id = self.add_node(node);
+            ids.insert(old_id, new_id);
+        }
+        for mut edge in other.edges {
+            if let (Some(source), Some(target)) = (ids.get(&edge.source), ids.get(&edge.target)) {
+                edge.source = source.clone();
+                edge.target = target.clone();
+                self.edges.push(edge);
+            }
+        }
+
+        let rename = |list: Vec<String>| {
+            list.into_iter()
+                .filter_map(|id| ids.get(&id).cloned())
+                .collect()
+        };
+        (rename(first), rename(last))
+    }
+
+    /// Add an edge from every source to every target
+    pub fn connect(
+        &mut self,
+        sources: &[String],
+        targets: &[String],
+        label: Option<&str>,
+        conditional: bool,
+    ) {
+        for source in sources {
+            for target in targets {
+                self.edges.push(Edge {
+                    source: source.clone(),
+                    target: target.clone(),
+                    label: label.map(str::to_string),
+                    conditional,
+                });
+            }
+        }
+    }
+
+    /// Render the graph as a Mermaid flowchart
+    pub fn draw_mermaid(&self) -> String {
+        let ids = self.index();
+        let mut out = String::from("graph TD;\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(out, "    n{i}[\"{}\"];", escape_mermaid(&node.name));
+        }
+        for edge in &self.edges {
+            let (Some(source), Some(target)) =
+                (ids.get(edge.source.as_str()), ids.get(edge.target.as_str()))
+            else {
+                continue;
+            };
+            let arrow = match (&edge.label, edge.conditional) {
+                (Some(label), true) => format!("-. \"{}\" .->", escape_mermaid(label)),
+                (Some(label), false) => format!("-- \"{}\" -->", escape_mermaid(label)),
+                (None, true) => "-.->".to_string(),
+                (None, false) => "-->".to_string(),
+            };
+            let _ = writeln!(out, "    n{source} {arrow} n{target};");
+        }
+        out
+    }
+
+    /// Render the graph in the Graphviz DOT language
+    pub fn draw_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        for node in &self.nodes {
+            let _ = writeln!(
+                out,
+                "    \"{}\" [label=\"{}\"];",
+                escape_dot(&node.id),
+                escape_dot(&node.name)
+            );
+        }
+        for edge in &self.edges {
+            let mut attributes = Vec::new();
+            if let Some(label) = &edge.label {
+                attributes.push(format!("label=\"{}\"", escape_dot(label)));
+            }
+            if edge.conditional {
+                attributes.push("style=dashed".to_string());
+            }
+            let attributes = if attributes.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", attributes.join(", "))
+            };
+            let _ = writeln!(
+                out,
+                "    \"{}\" -> \"{}\"{attributes};",
+                escape_dot(&edge.source),
+                escape_dot(&edge.target)
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render the graph as an ASCII diagram for the terminal
+    ///
+    /// Nodes are drawn top to bottom in layers. Edges that point back to an
+    /// earlier layer (cycles) are listed below the diagram.
+    pub fn draw_ascii(&self) -> String {
+        AsciiLayout::new(self).render()
+    }
+
+    fn index(&self) -> HashMap<&str, usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id.as_str(), i))
+            .collect()
+    }
+}
+
+impl_serializable!(Graph, ["ferriclink", "graph", "graph"]);
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+const GAP: usize = 3;
+
+/// An item in a layer of the ASCII layout
+#[derive(Clone, Copy, PartialEq)]
+enum Item {
+    /// A node of the graph
+    Node(usize),
+    /// A vertical line carrying an edge across a layer
+    Dummy { conditional: bool },
+}
+
+/// Layered layout of a graph for ASCII rendering
+struct AsciiLayout<'a> {
+    graph: &'a Graph,
+    layers: Vec<Vec<Item>>,
+    /// Edges between items of consecutive layers, as `(layer, from, to, conditional)`
+    links: Vec<(usize, usize, usize, bool)>,
+    back_edges: Vec<&'a Edge>,
+}
+
+impl<'a> AsciiLayout<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        let ids = graph.index();
+        let n = graph.nodes.len();
+
+        let mut edges = Vec::new();
+        for edge in &graph.edges {
+            if let (Some(&s), Some(&t)) =
+                (ids.get(edge.source.as_str()), ids.get(edge.target.as_str()))
+            {
+                edges.push((s, t, edge));
+            }
+        }
+
+        // Find back edges with a depth-first search, so the rest is acyclic
+        let mut state = vec![0u8; n];
+        let mut back = HashSet::new();
+        for start in 0..n {
+            if state[start] != 0 {
+                continue;
+            }
+            let mut stack = vec![(start, 0usize)];
+            state[start] = 1;
+            while let Some((node, next)) = stack.pop() {
+                let outgoing: Vec<_> = edges
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (s, _, _))| *s == node)
+                    .collect();
+                if let Some(&(i, &(_, target, _))) = outgoing.get(next) {
+                    stack.push((node, next + 1));
+                    match state[target] {
+                        0 => {
+                            state[target] = 1;
+                            stack.push((target, 0));
+                        }
+                        1 => {
+                            back.insert(i);
+                        }
+                        _ => {}
+                    }
+                } else {
+                    state[node] = 2;
+                }
+            }
+        }
+
+        // Assign layers by longest path from the sources
+        let forward: Vec<_> = edges
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !back.contains(i))
+            .map(|(_, edge)| *edge)
+            .collect();
+        let mut layer = vec![0usize; n];
+        let mut indegree = vec![0usize; n];
+        for (_, t, _) in &forward {
+            indegree[*t] += 1;
+        }
+        let mut queue: Vec<_> = (0..n).filter(|&i| indegree[i] == 0).collect();
+        let mut head = 0;
+        while head < queue.len() {
+            let node = queue[head];
+            head += 1;
+            for (s, t, _) in &forward {
+                if *s == node {
+                    layer[*t] = layer[*t].max(layer[node] + 1);
+                    indegree[*t] -= 1;
+                    if indegree[*t] == 0 {
+                        queue.push(*t);
+                    }
+                }
+            }
+        }
+
+        let depth = layer.iter().max().map_or(0, |max| max + 1);
+        let mut layers: Vec<Vec<Item>> = vec![Vec::new(); depth];
+        for (i, l) in layer.iter().enumerate() {
+            layers[*l].push(Item::Node(i));
+        }
+
+        // Route edges spanning several layers through dummy items
+        let mut links = Vec::new();
+        for (s, t, edge) in &forward {
+            let mut from = (layer[*s], position(&layers[layer[*s]], Item::Node(*s)));
+            let spanned = layers.iter_mut().enumerate();
+            for (l, items) in spanned.take(layer[*t]).skip(layer[*s] + 1) {
+                items.push(Item::Dummy {
+                    conditional: edge.conditional,
+                });
+                links.push((from.0, from.1, items.len() - 1, edge.conditional));
+                from = (l, items.len() - 1);
+            }
+            let to = position(&layers[layer[*t]], Item::Node(*t));
+            links.push((from.0, from.1, to, edge.conditional));
+        }
+
+        let mut layout = Self {
+            graph,
+            layers,
+            links,
+            back_edges: edges
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| back.contains(i))
+                .map(|(_, (_, _, edge))| *edge)
+                .collect(),
+        };
+        layout.order();
+        layout
+    }
+
+    /// Order each layer by the mean position of its predecessors
+    fn order(&mut self) {
+        for l in 1..self.layers.len() {
+            let len = self.layers[l].len();
+            let mut keys: Vec<(f64, usize)> = (0..len)
+                .map(|i| {
+                    let parents: Vec<_> = self
+                        .links
+                        .iter()
+                        .filter(|(pl, _, to, _)| *pl == l - 1 && *to == i)
+                        .map(|(_, from, _, _)| *from as f64)
+                        .collect();
+                    let key = if parents.is_empty() {
+                        f64::MAX
+                    } else {
+                        parents.iter().sum::<f64>() / parents.len() as f64
+                    };
+                    (key, i)
+                })
+                .collect();
+            keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut new_pos = vec![0; len];
+            for (pos, (_, old)) in keys.iter().enumerate() {
+                new_pos[*old] = pos;
+            }
+            self.layers[l] = keys.iter().map(|(_, old)| self.layers[l][*old]).collect();
+            for link in &mut self.links {
+                if link.0 == l - 1 {
+                    link.2 = new_pos[link.2];
+                }
+                if link.0 == l {
+                    link.1 = new_pos[link.1];
+                }
+            }
+        }
+    }
+
+    fn width(&self, item: Item) -> usize {
+        match item {
+            Item::Node(i) => self.graph.nodes[i].name.chars().count() + 4,
+            Item::Dummy { .. } => 1,
+        }
+    }
+
+    /// Left column of every item, centering each layer
+    fn columns(&self) -> Vec<Vec<usize>> {
+        let widths: Vec<usize> = self
+            .layers
+            .iter()
+            .map(|items| {
+                let total: usize = items.iter().map(|item| self.width(*item)).sum();
+                total + GAP * items.len().saturating_sub(1)
+            })
+            .collect();
+        let max = widths.iter().copied().max().unwrap_or(0);
+
+        self.layers
+            .iter()
+            .zip(&widths)
+            .map(|(items, width)| {
+                let mut x = (max - width) / 2;
+                items
+                    .iter()
+                    .map(|item| {
+                        let column = x;
+                        x += self.width(*item) + GAP;
+                        column
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn render(&self) -> String {
+        let columns = self.columns();
+        let center = |l: usize, i: usize| columns[l][i] + (self.width(self.layers[l][i]) - 1) / 2;
+        let mut rows: Vec<Vec<char>> = Vec::new();
+
+        for (l, items) in self.layers.iter().enumerate() {
+            let mut boxes = vec![Vec::new(), Vec::new(), Vec::new()];
+            for (i, item) in items.iter().enumerate() {
+                let x = columns[l][i];
+                match item {
+                    Item::Node(node) => {
+                        let name = &self.graph.nodes[*node].name;
+                        let border = format!("+{}+", "-".repeat(name.chars().count() + 2));
+                        put(&mut boxes[0], x, &border);
+                        put(&mut boxes[1], x, &format!("| {name} |"));
+                        put(&mut boxes[2], x, &border);
+                    }
+                    Item::Dummy { conditional } => {
+                        let line = if *conditional { ":" } else { "|" };
+                        for row in &mut boxes {
+                            put(row, x, line);
+                        }
+                    }
+                }
+            }
+            rows.extend(boxes);
+
+            if l + 1 == self.layers.len() {
+                break;
+            }
+
+            // Connectors to the next layer, one bus per connected group of links
+            let links: Vec<_> = self.links.iter().filter(|link| link.0 == l).collect();
+            let mut group: Vec<usize> = (0..links.len()).collect();
+            for a in 0..links.len() {
+                for b in 0..a {
+                    if links[a].1 == links[b].1 || links[a].2 == links[b].2 {
+                        let (ga, gb) = (find(&mut group, a), find(&mut group, b));
+                        group[ga] = gb;
+                    }
+                }
+            }
+
+            let mut connectors = vec![Vec::new(), Vec::new(), Vec::new()];
+            let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for i in 0..links.len() {
+                let root = find(&mut group, i);
+                groups.entry(root).or_default().push(i);
+            }
+            for members in groups.values() {
+                let mut xs = Vec::new();
+                for &m in members {
+                    let (_, from, to, conditional) = *links[m];
+                    let line = if conditional { ":" } else { "|" };
+                    let (sx, tx) = (center(l, from), center(l + 1, to));
+                    put(&mut connectors[0], sx, line);
+                    let arrow = match self.layers[l + 1][to] {
+                        Item::Node(_) => "v",
+                        Item::Dummy { .. } => line,
+                    };
+                    put(&mut connectors[2], tx, arrow);
+                    xs.push((sx, line));
+                    xs.push((tx, line));
+                }
+                let min = xs.iter().map(|(x, _)| *x).min().unwrap_or(0);
+                let max = xs.iter().map(|(x, _)| *x).max().unwrap_or(0);
+                if min == max {
+                    put(&mut connectors[1], min, xs[0].1);
+                } else {
+                    put(&mut connectors[1], min, &"-".repeat(max - min + 1));
+                    for (x, _) in &xs {
+                        put(&mut connectors[1], *x, "+");
+                    }
+                }
+            }
+            rows.extend(connectors);
+        }
+
+        let mut out = String::new();
+        for row in rows {
+            let line: String = row.into_iter().collect();
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        for edge in &self.back_edges {
+            let name = |id: &str| {
+                self.graph
+                    .node(id)
+                    .map_or(id.to_string(), |n| n.name.clone())
+            };
+            let _ = write!(out, "{} --> {}", name(&edge.source), name(&edge.target));
+            if let Some(label) = &edge.label {
+                let _ = write!(out, " ({label})");
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn position(items: &[Item], item: Item) -> usize {
+    items.iter().position(|i| *i == item).unwrap_or(0)
+}
+
+fn find(group: &mut [usize], mut i: usize) -> usize {
+    while group[i] != i {
+        group[i] = group[group[i]];
+        i = group[i];
+    }
+    i
+}
+
+/// Write text into a row at a column, padding with spaces
+fn put(row: &mut Vec<char>, x: usize, text: &str) {
+    for (offset, c) in text.chars().enumerate() {
+        let column = x + offset;
+        if row.len() <= column {
+            row.resize(column + 1, ' ');
+        }
+        row[column] = c;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain() -> Graph {
+        let mut graph = Graph::new();
+        let prompt = graph.add_node(Node::new("Prompt"));
+        let model = graph.add_node(Node::new("Model").with_component_type("chat_model"));
+        let tool = graph.add_node(Node::new("Search").with_component_type("tool"));
+        let answer = graph.add_node(Node::new("Answer"));
+        graph.add_edge(Edge::new(&prompt, &model));
+        graph.add_edge(
+            Edge::new(&model, &tool)
+                .with_label("tool call")
+                .with_conditional(true),
+        );
+        graph.add_edge(Edge::new(&model, &answer));
+        graph.add_edge(Edge::new(&tool, &answer));
+        graph
+    }
+
+    #[test]
+    fn test_graph_nodes_and_extend() {
+        let mut graph = chain();
+        assert_eq!(graph.first_node().unwrap().name, "Prompt");
+        assert_eq!(graph.last_node().unwrap().name, "Answer");
+
+        let (first, last) = graph.extend(chain());
+        assert_eq!(first, vec!["Prompt_1".to_string()]);
+        assert_eq!(last, vec!["Answer_1".to_string()]);
+        assert_eq!(graph.nodes.len(), 8);
+        assert_eq!(graph.first_nodes().len(), 2);
+
+        graph.connect(&["Answer".to_string()], &first, None, false);
+        assert_eq!(graph.first_node().unwrap().id, "Prompt");
+        assert_eq!(graph.last_node().unwrap().id, "Answer_1");
+    }
+
+    #[test]
+    fn test_draw_mermaid_and_dot() {
+        let graph = chain();
+
+        let mermaid = graph.draw_mermaid();
+        assert!(mermaid.starts_with("graph TD;\n"));
+        assert!(mermaid.contains("    n0[\"Prompt\"];"));
+        assert!(mermaid.contains("    n0 --> n1;"));
+        assert!(mermaid.contains("    n1 -. \"tool call\" .-> n2;"));
+
+        let dot = graph.draw_dot();
+        assert!(dot.starts_with("digraph {\n"));
+        assert!(dot.contains("    \"Model\" [label=\"Model\"];"));
+        assert!(dot.contains("    \"Model\" -> \"Search\" [label=\"tool call\", style=dashed];"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_draw_ascii() {
+        let expected = "  +--------+
+  | Prompt |
+  +--------+
+      |
+      |
+      v
+  +-------+
+  | Model |
+  +-------+
+      |
+    +-+------+
+    v        |
++--------+   |
+| Search |   |
++--------+   |
+    |        |
+    +-+------+
+      v
+  +--------+
+  | Answer |
+  +--------+
+";
+        assert_eq!(chain().draw_ascii(), expected);
+    }
+
+    #[test]
+    fn test_draw_ascii_cycle() {
+        let mut graph = Graph::new();
+        graph.add_node(Node::new("agent"));
+        graph.add_node(Node::new("tools"));
+        graph.add_edge(Edge::new("agent", "tools"));
+        graph.add_edge(Edge::new("tools", "agent").with_label("continue"));
+
+        let ascii = graph.draw_ascii();
+        assert!(ascii.find("agent").unwrap() < ascii.find("tools").unwrap());
+        assert!(ascii.ends_with("tools --> agent (continue)\n"));
+    }
+}
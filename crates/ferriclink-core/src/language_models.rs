@@ -7,11 +7,19 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use crate::errors::Result;
+use crate::graph::{Graph, Node};
 use crate::impl_serializable;
-use crate::messages::AnyMessage;
-use crate::runnables::RunnableConfig;
+use crate::messages::{AnyMessage, BaseMessage};
+use crate::rate_limiters::BaseRateLimiter;
+use crate::runnables::{
+    Runnable, RunnableConfig, acquire_rate_limiter, run_with_callbacks, stream_with_callbacks,
+    to_callback_value,
+};
+use crate::tools::ToolSchema;
+use crate::utils::approximate_token_count;
 
 /// Configuration for language model generation
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -96,6 +104,23 @@ impl GenerationConfig {
         self.extra.insert(key.into(), value);
         self
     }
+
+    /// Bind tools the model may call, stored under `extra["tools"]`
+    pub fn with_tools(self, tools: impl IntoIterator<Item = ToolSchema>) -> Self {
+        let tools: Vec<_> = tools
+            .into_iter()
+            .filter_map(|tool| serde_json::to_value(tool).ok())
+            .collect();
+        self.with_extra("tools", serde_json::Value::Array(tools))
+    }
+
+    /// Get the schemas of the tools bound to the model
+    pub fn tools(&self) -> Vec<ToolSchema> {
+        self.extra
+            .get("tools")
+            .and_then(|tools| serde_json::from_value(tools.clone()).ok())
+            .unwrap_or_default()
+    }
 }
 
 impl_serializable!(
@@ -299,6 +324,355 @@ pub trait BaseChatModel: BaseLanguageModel {
     }
 }
 
+/// A language model that can be used as a runnable
+///
+/// Runs are reported to callbacks with the `llm` component type.
+pub struct RunnableLLM<M> {
+    model: M,
+    generation_config: Option<GenerationConfig>,
+    rate_limiter: Option<Arc<dyn BaseRateLimiter>>,
+}
+
+impl<M> RunnableLLM<M>
+where
+    M: BaseLLM,
+{
+    /// Create a new runnable LLM
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            generation_config: None,
+            rate_limiter: None,
+        }
+    }
+
+    /// Set the generation config used for every call
+    pub fn with_generation_config(mut self, config: GenerationConfig) -> Self {
+        self.generation_config = Some(config);
+        self
+    }
+
+    /// Set a rate limiter acquired before every call
+    ///
+    /// The time spent waiting is reported in the run's metadata under
+    /// [`RATE_LIMIT_WAIT_METADATA_KEY`](crate::runnables::RATE_LIMIT_WAIT_METADATA_KEY).
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<dyn BaseRateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+}
+
+#[async_trait]
+impl<M> Runnable<String, LLMResult> for RunnableLLM<M>
+where
+    M: BaseLLM,
+{
+    async fn invoke(&self, input: String, config: Option<RunnableConfig>) -> Result<LLMResult> {
+        let config = acquire_rate_limiter(self.rate_limiter.as_deref(), config).await?;
+        run_with_callbacks(
+            config,
+            self.name(),
+            "llm",
+            serde_json::Value::String(input.clone()),
+            to_callback_value,
+            |run_config| async move {
+                self.model
+                    .generate(&input, self.generation_config.clone(), Some(run_config))
+                    .await
+            },
+        )
+        .await
+    }
+
+    fn name(&self) -> String {
+        self.model.model_name().to_string()
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        self.model.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<serde_json::Value> {
+        self.model.output_schema()
+    }
+
+    fn get_graph(&self) -> Graph {
+        Graph::from_node(
+            Node::new(self.name())
+                .with_component_type("llm")
+                .with_input_schema(self.input_schema())
+                .with_output_schema(self.output_schema()),
+        )
+    }
+}
+
+/// A chat model that can be used as a runnable
+///
+/// Runs are reported to callbacks with the `chat_model` component type, and
+/// streamed chunks are reported as stream events.
+pub struct RunnableChatModel<M> {
+    model: M,
+    generation_config: Option<GenerationConfig>,
+    rate_limiter: Option<Arc<dyn BaseRateLimiter>>,
+}
+
+impl<M> RunnableChatModel<M>
+where
+    M: BaseChatModel,
+{
+    /// Create a new runnable chat model
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            generation_config: None,
+            rate_limiter: None,
+        }
+    }
+
+    /// Set the generation config used for every call
+    pub fn with_generation_config(mut self, config: GenerationConfig) -> Self {
+        self.generation_config = Some(config);
+        self
+    }
+
+    /// Set a rate limiter acquired before every call
+    ///
+    /// The time spent waiting is reported in the run's metadata under
+    /// [`RATE_LIMIT_WAIT_METADATA_KEY`](crate::runnables::RATE_LIMIT_WAIT_METADATA_KEY).
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<dyn BaseRateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Get the wrapped chat model
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+}
+
+#[async_trait]
+impl<M> Runnable<Vec<AnyMessage>, AnyMessage> for RunnableChatModel<M>
+where
+    M: BaseChatModel,
+{
+    async fn invoke(
+        &self,
+        input: Vec<AnyMessage>,
+        config: Option<RunnableConfig>,
+    ) -> Result<AnyMessage> {
+        let config = acquire_rate_limiter(self.rate_limiter.as_deref(), config).await?;
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chat_model",
+            to_callback_value(&input),
+            to_callback_value,
+            |run_config| async move {
+                self.model
+                    .generate_chat(input, self.generation_config.clone(), Some(run_config))
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn stream(
+        &self,
+        input: Vec<AnyMessage>,
+        config: Option<RunnableConfig>,
+    ) -> Result<Pin<Box<dyn futures::Stream<Item = Result<AnyMessage>> + Send>>> {
+        let config = acquire_rate_limiter(self.rate_limiter.as_deref(), config)
+            .await?
+            .unwrap_or_default();
+        let (run_manager, run_config) = config
+            .start_run(self.name(), "chat_model", to_callback_value(&input))
+            .await?;
+
+        let inner = self
+            .model
+            .stream_chat(input, self.generation_config.clone(), Some(run_config))
+            .await;
+        match inner {
+            Ok(inner) => Ok(stream_with_callbacks(
+                inner,
+                run_manager,
+                to_callback_value::<AnyMessage>,
+            )),
+            Err(error) => {
+                run_manager.on_error(&error).await?;
+                Err(error)
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        self.model.model_name().to_string()
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        self.model.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<serde_json::Value> {
+        self.model.output_schema()
+    }
+
+    fn get_graph(&self) -> Graph {
+        Graph::from_node(
+            Node::new(self.name())
+                .with_component_type("chat_model")
+                .with_input_schema(self.input_schema())
+                .with_output_schema(self.output_schema()),
+        )
+    }
+}
+
+/// A chat model reserving the tokens of every call from a rate limiter
+///
+/// Before each call, the estimated prompt tokens plus the `max_tokens` of the
+/// generation config are acquired from the rate limiter with
+/// [`BaseRateLimiter::aacquire_n`]. Once the response is received, the
+/// difference with the actual usage is refunded, or charged when the call used
+/// more than reserved. The actual usage is read from the `total_tokens` of the
+/// `token_usage` response metadata when present, and estimated otherwise.
+/// Failed calls keep their reservation, as providers may count them.
+///
+/// Calls reserving more tokens than the rate limiter can ever allow fail
+/// instead of waiting. An [`InMemoryRateLimiter`](crate::rate_limiters::InMemoryRateLimiter)
+/// counts every token in its request bucket, so it only allows reservations
+/// up to its `max_bucket_size`; prefer a
+/// [`TokenRateLimiter`](crate::rate_limiters::TokenRateLimiter), which keeps
+/// separate request and token budgets.
+pub struct RateLimitedChatModel<M> {
+    model: M,
+    rate_limiter: Arc<dyn BaseRateLimiter>,
+    token_counter: fn(&str) -> usize,
+}
+
+impl<M> RateLimitedChatModel<M>
+where
+    M: BaseChatModel,
+{
+    /// Create a new rate-limited chat model
+    pub fn new(model: M, rate_limiter: Arc<dyn BaseRateLimiter>) -> Self {
+        Self {
+            model,
+            rate_limiter,
+            token_counter: approximate_token_count,
+        }
+    }
+
+    /// Set the function estimating the number of tokens of a text
+    pub fn with_token_counter(mut self, token_counter: fn(&str) -> usize) -> Self {
+        self.token_counter = token_counter;
+        self
+    }
+
+    /// Get the wrapped chat model
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    /// Get the rate limiter
+    pub fn rate_limiter(&self) -> &Arc<dyn BaseRateLimiter> {
+        &self.rate_limiter
+    }
+
+    /// Estimate the number of prompt tokens of messages
+    pub fn estimate_prompt_tokens(&self, messages: &[AnyMessage]) -> usize {
+        messages
+            .iter()
+            .map(|message| (self.token_counter)(&message.text()))
+            .sum()
+    }
+
+    /// Acquire the prompt tokens and the maximum completion tokens, returning their sum
+    async fn reserve(
+        &self,
+        prompt_tokens: usize,
+        config: Option<&GenerationConfig>,
+    ) -> Result<f64> {
+        let max_tokens = config.and_then(|config| config.max_tokens).unwrap_or(0);
+        let reserved = (prompt_tokens + max_tokens as usize) as f64;
+        self.rate_limiter.aacquire_n(reserved, true).await?;
+        Ok(reserved)
+    }
+
+    /// Get the number of tokens used by a call
+    fn used_tokens(&self, prompt_tokens: usize, response: &AnyMessage) -> f64 {
+        response
+            .response_metadata()
+            .get("token_usage")
+            .and_then(|usage| usage.get("total_tokens"))
+            .and_then(serde_json::Value::as_f64)
+            .unwrap_or_else(|| (prompt_tokens + (self.token_counter)(&response.text())) as f64)
+    }
+}
+
+#[async_trait]
+impl<M> BaseLanguageModel for RateLimitedChatModel<M>
+where
+    M: BaseChatModel,
+{
+    fn model_name(&self) -> &str {
+        self.model.model_name()
+    }
+
+    fn model_type(&self) -> &str {
+        self.model.model_type()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.model.supports_streaming()
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        self.model.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<serde_json::Value> {
+        self.model.output_schema()
+    }
+}
+
+#[async_trait]
+impl<M> BaseChatModel for RateLimitedChatModel<M>
+where
+    M: BaseChatModel,
+{
+    async fn generate_chat(
+        &self,
+        messages: Vec<AnyMessage>,
+        config: Option<GenerationConfig>,
+        runnable_config: Option<RunnableConfig>,
+    ) -> Result<AnyMessage> {
+        let prompt_tokens = self.estimate_prompt_tokens(&messages);
+        let reserved = self.reserve(prompt_tokens, config.as_ref()).await?;
+
+        let response = self
+            .model
+            .generate_chat(messages, config, runnable_config)
+            .await?;
+        self.rate_limiter
+            .refund(reserved - self.used_tokens(prompt_tokens, &response))?;
+        Ok(response)
+    }
+
+    async fn stream_chat(
+        &self,
+        messages: Vec<AnyMessage>,
+        config: Option<GenerationConfig>,
+        runnable_config: Option<RunnableConfig>,
+    ) -> Result<Pin<Box<dyn futures::Stream<Item = Result<AnyMessage>> + Send>>> {
+        // The usage of a stream is unknown, so the reservation is kept
+        let prompt_tokens = self.estimate_prompt_tokens(&messages);
+        self.reserve(prompt_tokens, config.as_ref()).await?;
+        self.model
+            .stream_chat(messages, config, runnable_config)
+            .await
+    }
+}
+
 /// A simple mock LLM for testing
 pub struct MockLLM {
     model_name: String,
@@ -356,8 +730,11 @@ impl BaseLLM for MockLLM {
         &self,
         _prompt: &str,
         _config: Option<GenerationConfig>,
-        _runnable_config: Option<RunnableConfig>,
+        runnable_config: Option<RunnableConfig>,
     ) -> Result<LLMResult> {
+        if let Some(runnable_config) = &runnable_config {
+            runnable_config.check_cancelled()?;
+        }
         let response = self.get_next_response();
         let generation = Generation::new(response);
         Ok(LLMResult::new(vec![vec![generation]]))
@@ -421,8 +798,11 @@ impl BaseChatModel for MockChatModel {
         &self,
         _messages: Vec<AnyMessage>,
         _config: Option<GenerationConfig>,
-        _runnable_config: Option<RunnableConfig>,
+        runnable_config: Option<RunnableConfig>,
     ) -> Result<AnyMessage> {
+        if let Some(runnable_config) = &runnable_config {
+            runnable_config.check_cancelled()?;
+        }
         let response = self.get_next_response();
         Ok(AnyMessage::ai(response))
     }
@@ -548,6 +928,178 @@ mod tests {
         assert_eq!(results[1].text(), "Chat 2");
     }
 
+    #[tokio::test]
+    async fn test_runnable_chat_model_stream_events() {
+        use crate::callbacks::StreamEventFilter;
+        use futures::StreamExt;
+
+        let model = RunnableChatModel::new(MockChatModel::new("mock-chat").add_response("Hi!"));
+        assert_eq!(model.name(), "mock-chat");
+
+        let graph = model.get_graph();
+        assert_eq!(graph.nodes.len(), 1);
+        assert_eq!(graph.nodes[0].name, "mock-chat");
+        assert_eq!(graph.nodes[0].component_type.as_deref(), Some("chat_model"));
+
+        let reply = model
+            .invoke(vec![AnyMessage::human("Hello")], None)
+            .await
+            .unwrap();
+        assert_eq!(reply.text(), "Hi!");
+
+        let events: Vec<_> = model
+            .stream_events(
+                vec![AnyMessage::human("Hello")],
+                None,
+                StreamEventFilter::new(),
+            )
+            .collect()
+            .await;
+        let names: Vec<_> = events
+            .iter()
+            .map(|e| e.as_ref().unwrap().event.as_str())
+            .collect();
+        assert_eq!(names, vec!["on_chat_model_start", "on_chat_model_end"]);
+
+        let handler = std::sync::Arc::new(crate::callbacks::MemoryCallbackHandler::new());
+        let config = RunnableConfig::new().with_callback(handler.clone());
+        let chunks: Vec<_> = model
+            .stream(vec![AnyMessage::human("Hello")], Some(config))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 1);
+        let runs = handler.get_successful_runs().await;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].component_type, "chat_model");
+    }
+
+    #[tokio::test]
+    async fn test_runnable_llm() {
+        let llm = RunnableLLM::new(MockLLM::new("mock-llm").add_response("Done"));
+        let result = llm.invoke("Prompt".to_string(), None).await.unwrap();
+        assert_eq!(result.first_text(), Some("Done"));
+        assert_eq!(llm.name(), "mock-llm");
+    }
+
+    #[tokio::test]
+    async fn test_runnable_models_share_rate_limiter() {
+        use crate::callbacks::{CallbackHandler, RunInfo};
+        use crate::runnables::RATE_LIMIT_WAIT_METADATA_KEY;
+
+        type Waits = Arc<std::sync::Mutex<Vec<(String, Option<f64>)>>>;
+
+        struct WaitRecorder {
+            waits: Waits,
+        }
+
+        #[async_trait]
+        impl CallbackHandler for WaitRecorder {
+            async fn on_run_start(&self, run_info: &RunInfo) -> Result<()> {
+                let wait = run_info
+                    .metadata
+                    .get(RATE_LIMIT_WAIT_METADATA_KEY)
+                    .and_then(|value| value.as_f64());
+                self.waits
+                    .lock()
+                    .unwrap()
+                    .push((run_info.component_type.clone(), wait));
+                Ok(())
+            }
+        }
+
+        let rate_limiter: Arc<dyn BaseRateLimiter> =
+            Arc::new(crate::rate_limiters::GcraRateLimiter::new(20.0, 1.0));
+        let chat = RunnableChatModel::new(MockChatModel::new("mock-chat").add_response("Hi!"))
+            .with_rate_limiter(rate_limiter.clone());
+        let llm = RunnableLLM::new(MockLLM::new("mock-llm").add_response("Done"))
+            .with_rate_limiter(rate_limiter);
+
+        let waits = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let config = RunnableConfig::new().with_callback(Arc::new(WaitRecorder {
+            waits: waits.clone(),
+        }));
+
+        // Rate limiters sleep on the Tokio clock, so the paused clock
+        // advances by exactly the time waited
+        tokio::time::pause();
+        let messages = vec![AnyMessage::human("Hello")];
+        let replies = chat
+            .batch(vec![messages.clone(), messages], Some(config.clone()))
+            .await
+            .unwrap();
+        assert_eq!(replies.len(), 2);
+        let result = llm
+            .invoke("Prompt".to_string(), Some(config))
+            .await
+            .unwrap();
+        assert_eq!(result.first_text(), Some("Done"));
+
+        // At 20 requests per second without burst, the calls are 50 ms apart
+        let waits = waits.lock().unwrap().clone();
+        let kinds: Vec<&str> = waits.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(kinds, vec!["chat_model", "chat_model", "llm"]);
+        let mut waits: Vec<f64> = waits.into_iter().map(|(_, wait)| wait.unwrap()).collect();
+        waits.sort_by(f64::total_cmp);
+        assert!(waits[0] < 1.0, "{waits:?}");
+        assert!((49.0..=51.0).contains(&waits[1]), "{waits:?}");
+        assert!((99.0..=101.0).contains(&waits[2]), "{waits:?}");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_chat_model() {
+        let rate_limiter = Arc::new(
+            crate::rate_limiters::TokenRateLimiter::new(100.0, 600.0).with_max_request_burst(10.0),
+        );
+        let model = RateLimitedChatModel::new(
+            MockChatModel::new("mock-chat").add_response("r".repeat(20)),
+            rate_limiter.clone(),
+        );
+        let messages = vec![AnyMessage::human("p".repeat(40))];
+        assert_eq!(model.estimate_prompt_tokens(&messages), 10);
+
+        // 110 tokens are reserved, then the unused 95 are refunded
+        let response = model
+            .generate_chat(
+                messages.clone(),
+                Some(GenerationConfig::new().with_max_tokens(100)),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "r".repeat(20));
+        let available = rate_limiter.available_tokens();
+        assert!((585.0..590.0).contains(&available), "{available}");
+
+        // Calls larger than the token budget can never be made
+        let error = model
+            .generate_chat(
+                messages,
+                Some(GenerationConfig::new().with_max_tokens(1_000)),
+                None,
+            )
+            .await;
+        assert!(error.is_err());
+
+        // An in-memory rate limiter bounds reservations by its bucket size
+        let model = RateLimitedChatModel::new(
+            MockChatModel::new("mock-chat").add_response("r"),
+            Arc::new(crate::rate_limiters::InMemoryRateLimiter::new(
+                100.0, 0.01, 200.0,
+            )),
+        );
+        let error = model
+            .generate_chat(
+                vec![AnyMessage::human("p".repeat(40))],
+                Some(GenerationConfig::new().with_max_tokens(1_000)),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("bucket"), "{error}");
+    }
+
     #[test]
     fn test_serialization() {
         let config = GenerationConfig::new().with_temperature(0.8);
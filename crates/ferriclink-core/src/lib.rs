@@ -4,27 +4,38 @@
 //! This crate provides the fundamental building blocks for building AI applications
 //! with language models, tools, vector stores, and more.
 
+pub mod agents;
 pub mod caches;
 pub mod callbacks;
+pub mod chat_history;
 pub mod documents;
 pub mod embeddings;
 pub mod env;
 pub mod errors;
 pub mod example_selectors;
 pub mod globals;
+pub mod graph;
 pub mod language_models;
+pub mod memory;
 pub mod messages;
 pub mod rate_limiters;
 pub mod retrievers;
 pub mod runnables;
 pub mod serializable;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod state_graph;
+pub mod stores;
 pub mod structured_query;
 pub mod tools;
 pub mod utils;
 pub mod vectorstores;
 
 // Re-exports for convenience
-pub use caches::{BaseCache, CacheStats, CachedGenerations, InMemoryCache, TtlCache};
+pub use caches::{
+    BaseCache, CacheStats, CachedGenerations, EvictionPolicy, FileCache, InMemoryCache,
+    SemanticCache, TtlCache, TtlLookup, TtlSweeper,
+};
 pub use env::{RuntimeEnvironment, get_fresh_runtime_environment, get_runtime_environment};
 pub use errors::{
     ErrorCode, FerricLinkError, IntoFerricLinkError, OutputParserException, Result,
@@ -40,8 +51,12 @@ pub use globals::{
     reset_globals, set_debug, set_llm_cache, set_verbose, toggle_debug, toggle_verbose,
 };
 pub use rate_limiters::{
-    AdvancedRateLimiter, BaseRateLimiter, InMemoryRateLimiter, InMemoryRateLimiterConfig,
-    RateLimiterConfig,
+    AdvancedRateLimiter, BaseKeyedRateLimiter, BaseRateLimiter, FairRateLimiter, GcraRateLimiter,
+    GcraRateLimiterConfig, InMemoryRateLimitBackend, InMemoryRateLimiter,
+    InMemoryRateLimiterConfig, KeyRateLimitMetrics, KeyRateLimiter, KeyedRateLimiter,
+    KeyedRateLimiterConfig, RateLimitBackend, RateLimitStrategy, RateLimiterConfig,
+    RedisRateLimitBackend, SlidingWindowRateLimiter, SlidingWindowRateLimiterConfig,
+    TokenBucketParams, TokenRateLimiter, TokenRateLimiterConfig,
 };
 pub use serializable::Serializable;
 
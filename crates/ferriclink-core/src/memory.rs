@@ -0,0 +1,493 @@
+//! Memory for FerricLink Core
+//!
+//! **Memory** keeps the context of a conversation within the limits of a
+//! model's context window.
+//!
+//! [`ConversationSummaryBufferMemory`] keeps the most recent messages verbatim
+//! and progressively summarizes older ones with a chat model.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::chat_history::BaseChatMessageHistory;
+use crate::errors::Result;
+use crate::language_models::BaseChatModel;
+use crate::messages::{AnyMessage, BaseMessage, SystemMessage, get_buffer_string};
+use crate::utils::approximate_token_count;
+
+/// Default maximum number of tokens kept verbatim
+pub const DEFAULT_MAX_TOKEN_LIMIT: usize = 2000;
+
+/// Name of the system message holding the running summary
+pub const SUMMARY_MESSAGE_NAME: &str = "conversation_summary";
+
+/// Default prompt used to extend the running summary
+///
+/// `{summary}` is replaced by the current summary and `{new_lines}` by the
+/// messages being summarized.
+pub const DEFAULT_SUMMARY_PROMPT: &str = "Progressively summarize the lines of conversation provided, adding onto the previous summary and returning a new summary.
+
+Current summary:
+{summary}
+
+New lines of conversation:
+{new_lines}
+
+New summary:";
+
+/// Memory keeping recent messages verbatim and summarizing older ones.
+///
+/// Messages are stored in a [`BaseChatMessageHistory`]. When the stored
+/// messages exceed the token limit, the oldest are removed and folded into a
+/// running summary by a chat model. The summary is stored as the first
+/// message of the history, a [`SystemMessage`] named
+/// [`SUMMARY_MESSAGE_NAME`], so it persists with the history store.
+///
+/// The memory is itself a history, so it can be given to
+/// [`RunnableWithMessageHistory`](crate::chat_history::RunnableWithMessageHistory).
+/// Only the async methods summarize; the sync methods access the store as is.
+pub struct ConversationSummaryBufferMemory {
+    llm: Arc<dyn BaseChatModel>,
+    chat_memory: Arc<dyn BaseChatMessageHistory>,
+    max_token_limit: usize,
+    get_num_tokens: fn(&str) -> usize,
+    summary_prompt: String,
+    human_prefix: String,
+    ai_prefix: String,
+}
+
+impl ConversationSummaryBufferMemory {
+    /// Create a new memory summarizing with `llm` and storing messages in `chat_memory`.
+    pub fn new(llm: Arc<dyn BaseChatModel>, chat_memory: Arc<dyn BaseChatMessageHistory>) -> Self {
+        Self {
+            llm,
+            chat_memory,
+            max_token_limit: DEFAULT_MAX_TOKEN_LIMIT,
+            get_num_tokens: approximate_token_count,
+            summary_prompt: DEFAULT_SUMMARY_PROMPT.to_string(),
+            human_prefix: "Human".to_string(),
+            ai_prefix: "AI".to_string(),
+        }
+    }
+
+    /// Set the maximum number of tokens kept verbatim.
+    pub fn with_max_token_limit(mut self, max_token_limit: usize) -> Self {
+        self.max_token_limit = max_token_limit;
+        self
+    }
+
+    /// Set the function counting the tokens of a text.
+    pub fn with_token_counter(mut self, get_num_tokens: fn(&str) -> usize) -> Self {
+        self.get_num_tokens = get_num_tokens;
+        self
+    }
+
+    /// Set the prompt used to extend the summary.
+    pub fn with_summary_prompt(mut self, summary_prompt: impl Into<String>) -> Self {
+        self.summary_prompt = summary_prompt.into();
+        self
+    }
+
+    /// Set the prefixes of human and AI messages in the summarized text.
+    pub fn with_prefixes(
+        mut self,
+        human_prefix: impl Into<String>,
+        ai_prefix: impl Into<String>,
+    ) -> Self {
+        self.human_prefix = human_prefix.into();
+        self.ai_prefix = ai_prefix.into();
+        self
+    }
+
+    /// Get the maximum number of tokens kept verbatim.
+    pub fn max_token_limit(&self) -> usize {
+        self.max_token_limit
+    }
+
+    /// Get the underlying history store.
+    pub fn chat_memory(&self) -> &Arc<dyn BaseChatMessageHistory> {
+        &self.chat_memory
+    }
+
+    /// Get the running summary, if older messages have been summarized.
+    pub async fn summary(&self) -> Result<Option<String>> {
+        let (summary, _) = Self::split_summary(self.chat_memory.amessages().await?);
+        Ok(summary)
+    }
+
+    /// Get the messages kept verbatim, without the summary.
+    pub async fn buffer(&self) -> Result<Vec<AnyMessage>> {
+        let (_, buffer) = Self::split_summary(self.chat_memory.amessages().await?);
+        Ok(buffer)
+    }
+
+    /// Save a turn of the conversation and summarize if over the token limit.
+    pub async fn save_context(&self, input: &str, output: &str) -> Result<()> {
+        self.aadd_messages(vec![AnyMessage::human(input), AnyMessage::ai(output)])
+            .await
+    }
+
+    /// Count the tokens of messages.
+    pub fn count_tokens(&self, messages: &[AnyMessage]) -> usize {
+        (self.get_num_tokens)(&get_buffer_string(
+            messages,
+            &self.human_prefix,
+            &self.ai_prefix,
+        ))
+    }
+
+    /// Extend a summary with new messages using the chat model.
+    pub async fn predict_new_summary(
+        &self,
+        messages: &[AnyMessage],
+        existing_summary: &str,
+    ) -> Result<String> {
+        let new_lines = get_buffer_string(messages, &self.human_prefix, &self.ai_prefix);
+        let prompt = self
+            .summary_prompt
+            .replace("{summary}", existing_summary)
+            .replace("{new_lines}", new_lines.trim_end());
+        let response = self
+            .llm
+            .generate_chat(vec![AnyMessage::human(prompt)], None, None)
+            .await?;
+        Ok(response.text().trim().to_string())
+    }
+
+    /// Summarize the oldest messages until the rest fit in the token limit.
+    ///
+    /// Messages added while the summary is generated are kept. If the history
+    /// was otherwise changed meanwhile, e.g. pruned by another caller, it is
+    /// left as is. The check and the replacement are atomic when the history
+    /// implements [`BaseChatMessageHistory::replace_messages_if`] atomically,
+    /// as the in-memory, file and SQLite histories do.
+    pub async fn prune(&self) -> Result<()> {
+        let read = self.chat_memory.amessages().await?;
+        let (summary, mut buffer) = Self::split_summary(read.clone());
+        if self.count_tokens(&buffer) <= self.max_token_limit {
+            return Ok(());
+        }
+
+        let mut pruned = Vec::new();
+        while !buffer.is_empty() && self.count_tokens(&buffer) > self.max_token_limit {
+            pruned.push(buffer.remove(0));
+        }
+        let summary = self
+            .predict_new_summary(&pruned, summary.as_deref().unwrap_or_default())
+            .await?;
+
+        let mut messages = vec![Self::summary_message(summary)];
+        messages.extend(buffer);
+        self.chat_memory
+            .areplace_messages_if(&read, messages)
+            .await?;
+        Ok(())
+    }
+
+    fn summary_message(summary: String) -> AnyMessage {
+        let mut message = SystemMessage::new(summary);
+        message.name = Some(SUMMARY_MESSAGE_NAME.to_string());
+        AnyMessage::System(message)
+    }
+
+    fn split_summary(mut messages: Vec<AnyMessage>) -> (Option<String>, Vec<AnyMessage>) {
+        match messages.first() {
+            Some(message @ AnyMessage::System(_))
+                if message.name() == Some(SUMMARY_MESSAGE_NAME) =>
+            {
+                let summary = messages.remove(0).text();
+                (Some(summary), messages)
+            }
+            _ => (None, messages),
+        }
+    }
+}
+
+#[async_trait]
+impl BaseChatMessageHistory for ConversationSummaryBufferMemory {
+    fn messages(&self) -> Result<Vec<AnyMessage>> {
+        self.chat_memory.messages()
+    }
+
+    fn add_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.chat_memory.add_messages(messages)
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.chat_memory.clear()
+    }
+
+    async fn amessages(&self) -> Result<Vec<AnyMessage>> {
+        self.chat_memory.amessages().await
+    }
+
+    async fn aadd_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.chat_memory.aadd_messages(messages).await?;
+        self.prune().await
+    }
+
+    async fn aclear(&self) -> Result<()> {
+        self.chat_memory.aclear().await
+    }
+
+    fn replace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.chat_memory.replace_messages(messages)
+    }
+
+    async fn areplace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.chat_memory.areplace_messages(messages).await
+    }
+
+    fn replace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        self.chat_memory
+            .replace_messages_if(expected_prefix, replacement)
+    }
+
+    async fn areplace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        self.chat_memory
+            .areplace_messages_if(expected_prefix, replacement)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_history::{
+        InMemoryChatHistoryStore, InMemoryChatMessageHistory, RunnableWithMessageHistory,
+        SESSION_ID_KEY,
+    };
+    use crate::language_models::MockChatModel;
+    use crate::runnables::{Runnable, RunnableConfig, runnable};
+
+    fn word_count(text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    #[tokio::test]
+    async fn test_summary_buffer_memory() {
+        let llm = Arc::new(
+            MockChatModel::new("summarizer")
+                .add_response("The user said hello.")
+                .add_response("The user said hello and asked about billing."),
+        );
+        let history = Arc::new(InMemoryChatMessageHistory::new("s1"));
+        let memory = ConversationSummaryBufferMemory::new(llm, history.clone())
+            .with_token_counter(word_count)
+            .with_max_token_limit(6);
+
+        // "Human: hi\nAI: hello\n" has 4 words
+        memory.save_context("hi", "hello").await.unwrap();
+        assert_eq!(memory.summary().await.unwrap(), None);
+        assert_eq!(memory.buffer().await.unwrap().len(), 2);
+
+        // The oldest messages are summarized until the rest fit
+        memory
+            .save_context("what about billing", "it is monthly")
+            .await
+            .unwrap();
+        assert_eq!(
+            memory.summary().await.unwrap().as_deref(),
+            Some("The user said hello.")
+        );
+        let buffer = memory.buffer().await.unwrap();
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer[0].text(), "it is monthly");
+
+        // The summary is stored as the first message of the history
+        let stored = history.messages().unwrap();
+        assert!(stored[0].is_system());
+        assert_eq!(stored[0].name(), Some(SUMMARY_MESSAGE_NAME));
+
+        memory
+            .save_context("and refunds", "within 30 days")
+            .await
+            .unwrap();
+        assert_eq!(
+            memory.summary().await.unwrap().as_deref(),
+            Some("The user said hello and asked about billing.")
+        );
+        assert!(memory.count_tokens(&memory.buffer().await.unwrap()) <= 6);
+
+        memory.aclear().await.unwrap();
+        assert_eq!(memory.summary().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_summary_buffer_prune_keeps_messages() {
+        /// A history whose appends fail, and whose summarizer adds a message
+        struct FailingAdds {
+            inner: Arc<InMemoryChatMessageHistory>,
+        }
+
+        impl BaseChatMessageHistory for FailingAdds {
+            fn messages(&self) -> Result<Vec<AnyMessage>> {
+                self.inner.messages()
+            }
+
+            fn add_messages(&self, _messages: Vec<AnyMessage>) -> Result<()> {
+                Err(crate::errors::FerricLinkError::runtime("disk full"))
+            }
+
+            fn clear(&self) -> Result<()> {
+                self.inner.clear()
+            }
+
+            fn replace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+                self.inner.replace_messages(messages)
+            }
+        }
+
+        struct InterruptedSummarizer {
+            history: Arc<InMemoryChatMessageHistory>,
+        }
+
+        #[async_trait]
+        impl crate::language_models::BaseLanguageModel for InterruptedSummarizer {
+            fn model_name(&self) -> &str {
+                "interrupted"
+            }
+
+            fn model_type(&self) -> &str {
+                "chat_model"
+            }
+        }
+
+        #[async_trait]
+        impl BaseChatModel for InterruptedSummarizer {
+            async fn generate_chat(
+                &self,
+                _messages: Vec<AnyMessage>,
+                _config: Option<crate::language_models::GenerationConfig>,
+                _runnable_config: Option<RunnableConfig>,
+            ) -> Result<AnyMessage> {
+                // Another turn is saved while the summary is generated
+                self.history.add_user_message("late question")?;
+                Ok(AnyMessage::ai("Earlier turns."))
+            }
+        }
+
+        let inner = Arc::new(InMemoryChatMessageHistory::new("s1"));
+        inner
+            .add_messages(vec![
+                AnyMessage::human("one two three"),
+                AnyMessage::ai("four five six"),
+                AnyMessage::human("seven"),
+            ])
+            .unwrap();
+        let memory = ConversationSummaryBufferMemory::new(
+            Arc::new(InterruptedSummarizer {
+                history: inner.clone(),
+            }),
+            Arc::new(FailingAdds {
+                inner: inner.clone(),
+            }),
+        )
+        .with_token_counter(word_count)
+        .with_max_token_limit(3);
+
+        memory.prune().await.unwrap();
+        let texts: Vec<_> = inner.messages().unwrap().iter().map(|m| m.text()).collect();
+        assert_eq!(texts, vec!["Earlier turns.", "seven", "late question"]);
+        assert_eq!(
+            memory.summary().await.unwrap().as_deref(),
+            Some("Earlier turns.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_summary_prompt() {
+        struct PromptEcho;
+
+        #[async_trait]
+        impl crate::language_models::BaseLanguageModel for PromptEcho {
+            fn model_name(&self) -> &str {
+                "echo"
+            }
+
+            fn model_type(&self) -> &str {
+                "chat_model"
+            }
+        }
+
+        #[async_trait]
+        impl BaseChatModel for PromptEcho {
+            async fn generate_chat(
+                &self,
+                messages: Vec<AnyMessage>,
+                _config: Option<crate::language_models::GenerationConfig>,
+                _runnable_config: Option<RunnableConfig>,
+            ) -> Result<AnyMessage> {
+                Ok(AnyMessage::ai(messages[0].text()))
+            }
+        }
+
+        let memory = ConversationSummaryBufferMemory::new(
+            Arc::new(PromptEcho),
+            Arc::new(InMemoryChatMessageHistory::new("s1")),
+        )
+        .with_summary_prompt("[{summary}] + [{new_lines}]")
+        .with_prefixes("User", "Bot");
+
+        let summary = memory
+            .predict_new_summary(
+                &[AnyMessage::human("hi"), AnyMessage::ai("hello")],
+                "before",
+            )
+            .await
+            .unwrap();
+        assert_eq!(summary, "[before] + [User: hi\nBot: hello]");
+    }
+
+    #[tokio::test]
+    async fn test_summary_memory_with_message_history() {
+        let store = InMemoryChatHistoryStore::new();
+        let sessions = store.clone();
+        let llm = Arc::new(MockChatModel::new("summarizer").add_response("Earlier small talk."));
+        let chain = RunnableWithMessageHistory::new(
+            runnable(|messages: Vec<AnyMessage>| {
+                let context: Vec<String> = messages.iter().map(|m| m.text()).collect();
+                Ok(AnyMessage::ai(format!("context: {}", context.join(" | "))))
+            }),
+            move |session_id| {
+                let memory =
+                    ConversationSummaryBufferMemory::new(llm.clone(), sessions.session(session_id))
+                        .with_token_counter(word_count)
+                        .with_max_token_limit(8);
+                Ok(Arc::new(memory) as Arc<dyn BaseChatMessageHistory>)
+            },
+        );
+
+        let config = RunnableConfig::new().with_configurable(SESSION_ID_KEY, "s1".into());
+        chain
+            .invoke(vec![AnyMessage::human("hi")], Some(config.clone()))
+            .await
+            .unwrap();
+        chain
+            .invoke(
+                vec![AnyMessage::human("tell me more")],
+                Some(config.clone()),
+            )
+            .await
+            .unwrap();
+
+        // The next turn sees the summary followed by the recent messages
+        let reply = chain
+            .invoke(vec![AnyMessage::human("thanks")], Some(config))
+            .await
+            .unwrap();
+        assert!(reply.text().starts_with("context: Earlier small talk. | "));
+        assert_eq!(
+            store.session("s1").messages().unwrap()[0].text(),
+            "Earlier small talk."
+        );
+    }
+}
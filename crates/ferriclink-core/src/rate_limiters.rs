@@ -5,12 +5,12 @@
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
 use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
 use tokio::time::sleep;
 
-use crate::errors::Result;
+use crate::errors::{FerricLinkError, Result};
 use crate::impl_serializable;
 
 /// Base trait for all rate limiters.
@@ -22,11 +22,10 @@ use crate::impl_serializable;
 /// to allow users to specify a timeout for acquiring the necessary tokens when
 /// using a blocking call.
 ///
-/// Current limitations:
-///
-/// - Rate limiting information is not surfaced in tracing or callbacks. This means
-///   that the total time it takes to invoke a chat model will encompass both
-///   the time spent waiting for tokens and the time spent making the request.
+/// Limiters attached to models or runnables with `with_rate_limiter` are
+/// acquired before the run starts, so the run's duration excludes the wait,
+/// which is reported in the run's metadata under
+/// [`RATE_LIMIT_WAIT_METADATA_KEY`](crate::runnables::RATE_LIMIT_WAIT_METADATA_KEY).
 #[async_trait]
 pub trait BaseRateLimiter: Send + Sync {
     /// Attempt to acquire the necessary tokens for the rate limiter.
@@ -66,6 +65,697 @@ pub trait BaseRateLimiter: Send + Sync {
     ///
     /// True if the tokens were successfully acquired, false otherwise.
     async fn aacquire(&self, blocking: bool) -> Result<bool>;
+
+    /// Attempt to acquire `cost` units, such as the tokens of a request.
+    ///
+    /// Rate limiters that only count requests ignore the cost and acquire a
+    /// single request.
+    fn acquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        let _ = cost;
+        self.acquire(blocking)
+    }
+
+    /// Attempt to acquire `cost` units, such as the tokens of a request. Async version.
+    ///
+    /// Rate limiters that only count requests ignore the cost and acquire a
+    /// single request.
+    async fn aacquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        let _ = cost;
+        self.aacquire(blocking).await
+    }
+
+    /// Give back units that were acquired but not used.
+    ///
+    /// A negative amount charges units used beyond what was acquired, which
+    /// may leave the rate limiter in debt and delay later acquisitions.
+    /// Rate limiters that only count requests ignore refunds.
+    fn refund(&self, amount: f64) -> Result<()> {
+        let _ = amount;
+        Ok(())
+    }
+}
+
+/// A token bucket filled continuously at a given rate
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    /// Number of units added per second
+    rate_per_second: f64,
+    /// Maximum number of units in the bucket
+    max_size: f64,
+    /// Number of units in the bucket, negative when in debt
+    available: f64,
+    /// The last time the bucket was refilled
+    last: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new(rate_per_second: f64, max_size: f64, available: f64) -> Self {
+        Self {
+            rate_per_second,
+            max_size,
+            available,
+            last: None,
+        }
+    }
+
+    /// Add the units accumulated since the last refill.
+    fn refill(&mut self, now: Instant) {
+        // Initialize on first call to avoid a burst
+        let last = *self.last.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(last).as_secs_f64();
+        self.available = (self.available + elapsed * self.rate_per_second).min(self.max_size);
+        self.last = Some(now);
+    }
+
+    fn has(&self, cost: f64) -> bool {
+        self.available >= cost
+    }
+
+    fn refund(&mut self, amount: f64) {
+        self.available = (self.available + amount).min(self.max_size);
+    }
+}
+
+/// Check that a cost can ever be acquired from a bucket.
+fn check_cost(cost: f64, max_size: f64) -> Result<()> {
+    if !cost.is_finite() || cost < 0.0 {
+        return Err(FerricLinkError::validation(format!(
+            "Rate limiter cost must be a non-negative number, got {cost}"
+        )));
+    }
+    if cost > max_size {
+        return Err(FerricLinkError::validation(format!(
+            "Rate limiter cost {cost} exceeds the maximum bucket size {max_size}"
+        )));
+    }
+    Ok(())
+}
+
+/// Threads and tasks waiting for a rate limiter, woken when units are given back
+///
+/// Threads wait on a condition variable and tasks on a [`tokio::sync::Notify`],
+/// so both can wait on the same rate limiter without blocking each other.
+#[derive(Debug, Default)]
+struct Waiters {
+    /// Incremented on every wake-up, so that waiters notice wake-ups they missed
+    generation: Mutex<u64>,
+    condvar: Condvar,
+    notify: tokio::sync::Notify,
+}
+
+impl Waiters {
+    fn generation(&self) -> u64 {
+        *self.generation.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wake all waiting threads and tasks.
+    fn wake(&self) {
+        *self.generation.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.condvar.notify_all();
+        self.notify.notify_waiters();
+    }
+
+    /// Block the thread for `timeout`, or until woken after `generation`.
+    fn wait_blocking(&self, generation: u64, timeout: Duration) {
+        let guard = self.generation.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = self
+            .condvar
+            .wait_timeout_while(guard, timeout, |current| *current == generation)
+            .unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+/// Run a blocking wait without starving the Tokio runtime of the current thread.
+///
+/// On a multi-threaded runtime, the other tasks of the worker are moved to
+/// other workers while waiting. On a current-thread runtime, nothing can run
+/// while the thread is blocked, so sync acquisitions there should not wait
+/// for other tasks.
+fn block_in_runtime<R>(wait: impl FnOnce() -> R) -> R {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(wait)
+        }
+        _ => wait(),
+    }
+}
+
+/// Try `try_acquire` until it succeeds, blocking the thread between attempts.
+///
+/// Attempts are made every `check_every` seconds, and whenever units are given back.
+fn poll_blocking(
+    waiters: &Waiters,
+    check_every: f64,
+    mut try_acquire: impl FnMut() -> bool,
+) -> bool {
+    let mut generation = waiters.generation();
+    if try_acquire() {
+        return true;
+    }
+    block_in_runtime(|| {
+        loop {
+            waiters.wait_blocking(generation, Duration::from_secs_f64(check_every));
+            generation = waiters.generation();
+            if try_acquire() {
+                return true;
+            }
+        }
+    })
+}
+
+/// Try `try_acquire` until it succeeds.
+///
+/// Attempts are made every `check_every` seconds, and whenever units are given back.
+async fn poll_async(
+    waiters: &Waiters,
+    check_every: f64,
+    mut try_acquire: impl FnMut() -> bool,
+) -> bool {
+    loop {
+        let notified = waiters.notify.notified();
+        tokio::pin!(notified);
+        // Register before trying, so that no wake-up is missed
+        notified.as_mut().enable();
+        if try_acquire() {
+            return true;
+        }
+        let _ = tokio::time::timeout(Duration::from_secs_f64(check_every), notified).await;
+    }
+}
+
+/// Parameters of a token bucket kept by a [`RateLimitBackend`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenBucketParams {
+    /// Number of units added per second
+    pub rate_per_second: f64,
+    /// Maximum number of units in the bucket
+    pub max_size: f64,
+    /// Number of units in the bucket when it is first used
+    pub initial: f64,
+}
+
+/// Storage of token buckets, shared by the rate limiters using it
+///
+/// Each operation refills and updates a bucket atomically, so rate limiters in
+/// different threads, or in different processes for a remote backend, can
+/// share the same quota by using the same bucket key.
+#[async_trait]
+pub trait RateLimitBackend: Send + Sync {
+    /// Refill the bucket `key` and take `cost` units from it if available.
+    ///
+    /// Returns whether the units were taken.
+    fn try_consume(&self, key: &str, params: TokenBucketParams, cost: f64) -> Result<bool>;
+
+    /// Refill the bucket `key` and give `amount` units back to it.
+    ///
+    /// A negative amount charges units, possibly leaving the bucket in debt.
+    fn refund(&self, key: &str, params: TokenBucketParams, amount: f64) -> Result<()>;
+
+    /// Get the number of units in the bucket `key` as of its last update.
+    fn available(&self, key: &str, params: TokenBucketParams) -> Result<f64>;
+
+    /// Async version of [`try_consume`](Self::try_consume).
+    async fn atry_consume(&self, key: &str, params: TokenBucketParams, cost: f64) -> Result<bool> {
+        self.try_consume(key, params, cost)
+    }
+
+    /// Async version of [`refund`](Self::refund).
+    async fn arefund(&self, key: &str, params: TokenBucketParams, amount: f64) -> Result<()> {
+        self.refund(key, params, amount)
+    }
+
+    /// Async version of [`available`](Self::available).
+    async fn aavailable(&self, key: &str, params: TokenBucketParams) -> Result<f64> {
+        self.available(key, params)
+    }
+}
+
+/// A rate limit backend keeping its buckets in memory
+///
+/// Clones share the same buckets. It cannot share a quota across processes.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryRateLimitBackend {
+    buckets: Arc<Mutex<HashMap<String, TokenBucket>>>,
+}
+
+impl InMemoryRateLimitBackend {
+    /// Create a new in-memory backend without buckets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `func` on the bucket `key`, creating it if needed.
+    fn with_bucket<R>(
+        &self,
+        key: &str,
+        params: TokenBucketParams,
+        func: impl FnOnce(&mut TokenBucket) -> R,
+    ) -> R {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.entry(key.to_string()).or_insert_with(|| {
+            TokenBucket::new(params.rate_per_second, params.max_size, params.initial)
+        });
+        func(bucket)
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for InMemoryRateLimitBackend {
+    fn try_consume(&self, key: &str, params: TokenBucketParams, cost: f64) -> Result<bool> {
+        Ok(self.with_bucket(key, params, |bucket| {
+            bucket.refill(Instant::now());
+            if bucket.has(cost) {
+                bucket.available -= cost;
+                true
+            } else {
+                false
+            }
+        }))
+    }
+
+    fn refund(&self, key: &str, params: TokenBucketParams, amount: f64) -> Result<()> {
+        self.with_bucket(key, params, |bucket| {
+            bucket.refill(Instant::now());
+            bucket.refund(amount);
+        });
+        Ok(())
+    }
+
+    fn available(&self, key: &str, params: TokenBucketParams) -> Result<f64> {
+        Ok(self.with_bucket(key, params, |bucket| bucket.available))
+    }
+}
+
+/// Lua script updating a token bucket stored in a Redis hash
+///
+/// Arguments are the refill rate, the maximum size, the initial size, the
+/// operation (`consume`, `refund` or `available`) and its amount. It returns
+/// whether the operation succeeded and the units left, as a string since
+/// Redis truncates Lua numbers to integers. Time is read from the server so
+/// that clients do not depend on their own clocks.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local rate = tonumber(ARGV[1])
+local max_size = tonumber(ARGV[2])
+local initial = tonumber(ARGV[3])
+local op = ARGV[4]
+local amount = tonumber(ARGV[5])
+local state = redis.call('HMGET', KEYS[1], 'available', 'last')
+local available = tonumber(state[1]) or initial
+if op == 'available' then
+  return {1, tostring(available)}
+end
+local time = redis.call('TIME')
+local now = tonumber(time[1]) + tonumber(time[2]) / 1000000
+local last = tonumber(state[2]) or now
+available = math.min(available + math.max(now - last, 0) * rate, max_size)
+local ok = 1
+if op == 'consume' then
+  if available >= amount then
+    available = available - amount
+  else
+    ok = 0
+  end
+else
+  available = math.min(available + amount, max_size)
+end
+redis.call('HSET', KEYS[1], 'available', tostring(available), 'last', tostring(now))
+redis.call('PEXPIRE', KEYS[1], math.ceil((max_size - available) / rate * 1000) + 60000)
+return {ok, tostring(available)}
+"#;
+
+/// A value of the Redis serialization protocol (RESP)
+#[derive(Debug, Clone, PartialEq)]
+enum RespValue {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<RespValue>>),
+}
+
+/// Largest length accepted in a RESP reply, the maximum size of a Redis string
+const MAX_RESP_LENGTH: i64 = 512 * 1024 * 1024;
+
+impl RespValue {
+    /// Encode a command as an array of bulk strings.
+    fn encode_command(args: &[&[u8]]) -> Vec<u8> {
+        let mut out = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            out.extend_from_slice(arg);
+            out.extend_from_slice(b"\r\n");
+        }
+        out
+    }
+
+    /// Read one value from a RESP stream.
+    fn read(reader: &mut impl std::io::BufRead) -> Result<Self> {
+        let line = Self::read_line(reader)?;
+        let Some((kind, rest)) = line.split_at_checked(1) else {
+            return Err(FerricLinkError::runtime(format!(
+                "Invalid RESP value: {line}"
+            )));
+        };
+        let integer = || {
+            rest.parse::<i64>()
+                .map_err(|_| FerricLinkError::runtime(format!("Invalid RESP integer: {rest}")))
+        };
+        let length = || {
+            integer()
+                .ok()
+                .filter(|length| *length <= MAX_RESP_LENGTH)
+                .ok_or_else(|| FerricLinkError::runtime(format!("Invalid RESP length: {rest}")))
+        };
+        match kind {
+            "+" => Ok(Self::Simple(rest.to_string())),
+            "-" => Ok(Self::Error(rest.to_string())),
+            ":" => Ok(Self::Integer(integer()?)),
+            "$" => {
+                let Ok(len) = usize::try_from(length()?) else {
+                    return Ok(Self::Bulk(None));
+                };
+                let mut data = vec![0; len + 2];
+                reader.read_exact(&mut data)?;
+                data.truncate(len);
+                Ok(Self::Bulk(Some(data)))
+            }
+            "*" => {
+                let Ok(len) = usize::try_from(length()?) else {
+                    return Ok(Self::Array(None));
+                };
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(Self::read(reader)?);
+                }
+                Ok(Self::Array(Some(items)))
+            }
+            _ => Err(FerricLinkError::runtime(format!(
+                "Invalid RESP value: {line}"
+            ))),
+        }
+    }
+
+    fn read_line(reader: &mut impl std::io::BufRead) -> Result<String> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(FerricLinkError::runtime("Redis connection closed"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            return Err(FerricLinkError::runtime("Empty RESP line"));
+        }
+        Ok(line.to_string())
+    }
+}
+
+/// A connection to a Redis server
+#[derive(Debug)]
+struct RedisConnection {
+    reader: std::io::BufReader<std::net::TcpStream>,
+    writer: std::net::TcpStream,
+}
+
+impl RedisConnection {
+    fn connect(address: &str, timeout: Duration) -> Result<Self> {
+        use std::net::ToSocketAddrs;
+
+        let mut last_error = None;
+        for addr in address.to_socket_addrs()? {
+            match std::net::TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Ok(Self {
+                        reader: std::io::BufReader::new(stream.try_clone()?),
+                        writer: stream,
+                    });
+                }
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.map_or_else(
+            || FerricLinkError::configuration(format!("No address found for {address}")),
+            FerricLinkError::from,
+        ))
+    }
+
+    /// Send a command and read its reply, including error replies.
+    fn send(&mut self, args: &[&[u8]]) -> Result<RespValue> {
+        use std::io::Write;
+
+        self.writer.write_all(&RespValue::encode_command(args))?;
+        RespValue::read(&mut self.reader)
+    }
+
+    /// Send a command and read its reply, turning error replies into errors.
+    fn command(&mut self, args: &[&[u8]]) -> Result<RespValue> {
+        Self::check_reply(self.send(args)?)
+    }
+
+    /// Turn an error reply into an error.
+    fn check_reply(reply: RespValue) -> Result<RespValue> {
+        match reply {
+            RespValue::Error(message) => {
+                Err(FerricLinkError::runtime(format!("Redis error: {message}")))
+            }
+            reply => Ok(reply),
+        }
+    }
+}
+
+/// A rate limit backend keeping its buckets on a Redis server
+///
+/// Buckets are Redis hashes updated by a Lua script, so every operation is
+/// atomic and rate limiters in different processes share their quota. A
+/// bucket expires a minute after it would be full again; it then restarts
+/// from its initial size.
+///
+/// The backend speaks the Redis protocol over a single connection, opened on
+/// first use and reopened after an error. The script is loaded once with
+/// `SCRIPT LOAD` and then run by its digest with `EVALSHA`, loading it again
+/// if the server answers `NOSCRIPT`. Its sync methods block on network I/O,
+/// and its async methods run them on the blocking thread pool.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use ferriclink_core::rate_limiters::{
+///     BaseRateLimiter, InMemoryRateLimiter, RedisRateLimitBackend,
+/// };
+/// use std::sync::Arc;
+///
+/// let backend = Arc::new(RedisRateLimitBackend::new("127.0.0.1:6379").with_database(1));
+///
+/// // Every worker using this key shares 10 requests per second
+/// let rate_limiter = InMemoryRateLimiter::new(10.0, 0.05, 10.0)
+///     .with_backend(backend)
+///     .with_key("openai");
+/// assert!(rate_limiter.acquire(true).unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RedisRateLimitBackend {
+    /// Address of the server, as `host:port`
+    address: String,
+    /// Password sent with `AUTH` when connecting
+    password: Option<String>,
+    /// Database selected when connecting
+    database: Option<u32>,
+    /// Prefix of the Redis keys of the buckets
+    key_prefix: String,
+    /// Timeout of connecting and of each command
+    timeout: Duration,
+    /// The connection, shared by clones
+    connection: Arc<Mutex<Option<RedisConnection>>>,
+    /// Digest of the loaded token bucket script, shared by clones
+    script_sha: Arc<Mutex<Option<String>>>,
+}
+
+impl RedisRateLimitBackend {
+    /// Create a new backend for the Redis server at `address` (`host:port`)
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            password: None,
+            database: None,
+            key_prefix: "ferriclink:rate_limit:".to_string(),
+            timeout: Duration::from_secs(5),
+            connection: Arc::new(Mutex::new(None)),
+            script_sha: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Authenticate with a password when connecting
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Select a database when connecting
+    pub fn with_database(mut self, database: u32) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// Set the prefix of the Redis keys of the buckets
+    pub fn with_key_prefix(mut self, key_prefix: impl Into<String>) -> Self {
+        self.key_prefix = key_prefix.into();
+        self
+    }
+
+    /// Set the timeout of connecting and of each command
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get the address of the server
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Get the prefix of the Redis keys of the buckets
+    pub fn key_prefix(&self) -> &str {
+        &self.key_prefix
+    }
+
+    fn connect(&self) -> Result<RedisConnection> {
+        let mut connection = RedisConnection::connect(&self.address, self.timeout)?;
+        if let Some(password) = &self.password {
+            connection.command(&[b"AUTH", password.as_bytes()])?;
+        }
+        if let Some(database) = self.database {
+            connection.command(&[b"SELECT", database.to_string().as_bytes()])?;
+        }
+        Ok(connection)
+    }
+
+    /// Run the token bucket script, returning whether it succeeded and the units left.
+    fn run_script(
+        &self,
+        key: &str,
+        params: TokenBucketParams,
+        op: &str,
+        amount: f64,
+    ) -> Result<(bool, f64)> {
+        let key = format!("{}{key}", self.key_prefix);
+        let args = [
+            params.rate_per_second.to_string(),
+            params.max_size.to_string(),
+            params.initial.to_string(),
+            op.to_string(),
+            amount.to_string(),
+        ];
+
+        let mut guard = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let connection = match guard.as_mut() {
+            Some(connection) => connection,
+            None => guard.insert(self.connect()?),
+        };
+        let reply = self.eval_script(connection, &key, &args);
+        if reply.is_err() {
+            // The connection may be out of sync with the server
+            *guard = None;
+        }
+        Self::parse_reply(reply?)
+    }
+
+    /// Run the token bucket script by its digest, loading it when the server
+    /// does not know it yet.
+    fn eval_script(
+        &self,
+        connection: &mut RedisConnection,
+        key: &str,
+        args: &[String],
+    ) -> Result<RespValue> {
+        let evalsha = |sha: &str, connection: &mut RedisConnection| {
+            let mut command: Vec<&[u8]> = vec![b"EVALSHA", sha.as_bytes(), b"1", key.as_bytes()];
+            command.extend(args.iter().map(|arg| arg.as_bytes()));
+            connection.send(&command)
+        };
+
+        let mut script_sha = self.script_sha.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sha) = script_sha.as_deref() {
+            match evalsha(sha, connection)? {
+                RespValue::Error(message) if message.starts_with("NOSCRIPT") => {}
+                reply => return RedisConnection::check_reply(reply),
+            }
+        }
+
+        let sha = match connection.command(&[b"SCRIPT", b"LOAD", TOKEN_BUCKET_SCRIPT.as_bytes()])? {
+            RespValue::Bulk(Some(sha)) => String::from_utf8(sha).map_err(|e| {
+                FerricLinkError::runtime(format!("Invalid digest of the rate limit script: {e}"))
+            })?,
+            reply => {
+                return Err(FerricLinkError::runtime(format!(
+                    "Unexpected reply when loading the rate limit script: {reply:?}"
+                )));
+            }
+        };
+        let reply = evalsha(&sha, connection)?;
+        *script_sha = Some(sha);
+        RedisConnection::check_reply(reply)
+    }
+
+    fn parse_reply(reply: RespValue) -> Result<(bool, f64)> {
+        if let RespValue::Array(Some(items)) = &reply {
+            if let [RespValue::Integer(ok), RespValue::Bulk(Some(available))] = items.as_slice() {
+                let available = std::str::from_utf8(available)
+                    .ok()
+                    .and_then(|available| available.parse::<f64>().ok());
+                if let Some(available) = available {
+                    return Ok((*ok == 1, available));
+                }
+            }
+        }
+        Err(FerricLinkError::runtime(format!(
+            "Unexpected reply from the rate limit script: {reply:?}"
+        )))
+    }
+
+    async fn run_blocking<T, F>(&self, func: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(RedisRateLimitBackend) -> Result<T> + Send + 'static,
+    {
+        let backend = self.clone();
+        tokio::task::spawn_blocking(move || func(backend))
+            .await
+            .map_err(|e| FerricLinkError::runtime(format!("Rate limit task failed: {e}")))?
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for RedisRateLimitBackend {
+    fn try_consume(&self, key: &str, params: TokenBucketParams, cost: f64) -> Result<bool> {
+        Ok(self.run_script(key, params, "consume", cost)?.0)
+    }
+
+    fn refund(&self, key: &str, params: TokenBucketParams, amount: f64) -> Result<()> {
+        self.run_script(key, params, "refund", amount).map(|_| ())
+    }
+
+    fn available(&self, key: &str, params: TokenBucketParams) -> Result<f64> {
+        Ok(self.run_script(key, params, "available", 0.0)?.1)
+    }
+
+    async fn atry_consume(&self, key: &str, params: TokenBucketParams, cost: f64) -> Result<bool> {
+        let key = key.to_string();
+        self.run_blocking(move |backend| backend.try_consume(&key, params, cost))
+            .await
+    }
+
+    async fn arefund(&self, key: &str, params: TokenBucketParams, amount: f64) -> Result<()> {
+        let key = key.to_string();
+        self.run_blocking(move |backend| backend.refund(&key, params, amount))
+            .await
+    }
+
+    async fn aavailable(&self, key: &str, params: TokenBucketParams) -> Result<f64> {
+        let key = key.to_string();
+        self.run_blocking(move |backend| backend.available(&key, params))
+            .await
+    }
 }
 
 /// An in-memory rate limiter based on a token bucket algorithm.
@@ -73,11 +763,17 @@ pub trait BaseRateLimiter: Send + Sync {
 /// This is an in-memory rate limiter, so it cannot rate limit across
 /// different processes.
 ///
-/// The rate limiter only allows time-based rate limiting and does not
-/// take into account any information about the input or the output, so it
-/// cannot be used to rate limit based on the size of the request.
+/// The rate limiter only allows time-based rate limiting over a single
+/// bucket. A request consumes one token, or `cost` tokens when acquired with
+/// [`acquire_n`](BaseRateLimiter::acquire_n) or
+/// [`aacquire_n`](BaseRateLimiter::aacquire_n), which return an error when
+/// `cost` exceeds `max_bucket_size` as such a request could never be allowed.
 ///
 /// It is thread safe and can be used in either a sync or async context.
+/// Sync and async callers share the same bucket: the sync methods never
+/// create or enter a Tokio runtime, so they may also be called from async
+/// code, where a waiting call lets a multi-threaded runtime move its other
+/// tasks to other workers.
 ///
 /// The in-memory rate limiter is based on a token bucket. The bucket is filled
 /// with tokens at a given rate. Each request consumes a token. If there are
@@ -86,13 +782,22 @@ pub trait BaseRateLimiter: Send + Sync {
 ///
 /// These *tokens* have NOTHING to do with LLM tokens. They are just
 /// a way to keep track of how many requests can be made at a given time.
+/// To rate limit both the requests and the LLM tokens of a model, such as
+/// with a [`RateLimitedChatModel`](crate::language_models::RateLimitedChatModel),
+/// use a [`TokenRateLimiter`] instead.
+///
+/// The bucket is kept by an [`InMemoryRateLimitBackend`] by default. With
+/// [`with_backend`](Self::with_backend), it can be kept by another
+/// [`RateLimitBackend`] such as a [`RedisRateLimitBackend`], so that rate
+/// limiters in different processes using the same [`with_key`](Self::with_key)
+/// share one quota. Waiters are woken by refunds made in this process, and
+/// otherwise check again every `check_every_n_seconds`.
 ///
 /// Current limitations:
 ///
-/// - The rate limiter is not designed to work across different processes. It is
-///   an in-memory rate limiter, but it is thread safe.
-/// - The rate limiter only supports time-based rate limiting. It does not take
-///   into account the size of the request or any other factors.
+/// - The rate limiter only supports time-based rate limiting. A cost is
+///   bounded by `max_bucket_size`, and requests and LLM tokens cannot be
+///   limited separately.
 ///
 /// # Example
 ///
@@ -110,22 +815,24 @@ pub trait BaseRateLimiter: Send + Sync {
 /// // let model = ChatAnthropic::new()
 /// //     .with_rate_limiter(rate_limiter);
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct InMemoryRateLimiter {
     /// Number of requests that we can make per second
     requests_per_second: f64,
-    /// Number of tokens in the bucket
-    available_tokens: Arc<Mutex<f64>>,
+    /// The backend keeping the bucket of tokens
+    backend: Arc<dyn RateLimitBackend>,
+    /// The key of the bucket in the backend
+    key: String,
+    /// Threads and tasks waiting for tokens
+    waiters: Arc<Waiters>,
     /// Maximum number of tokens that can be in the bucket
     max_bucket_size: f64,
-    /// The last time we tried to consume tokens
-    last: Arc<Mutex<Option<Instant>>>,
     /// Check whether tokens are available every this many seconds
     check_every_n_seconds: f64,
 }
 
 /// Serializable version of InMemoryRateLimiter for configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InMemoryRateLimiterConfig {
     /// Number of requests that we can make per second
     pub requests_per_second: f64,
@@ -177,152 +884,1473 @@ impl InMemoryRateLimiter {
 
         Self {
             requests_per_second,
-            available_tokens: Arc::new(Mutex::new(1.0)), // Start with 1 token to allow first request
+            backend: Arc::new(InMemoryRateLimitBackend::new()),
+            key: "default".to_string(),
+            waiters: Arc::new(Waiters::default()),
             max_bucket_size,
-            last: Arc::new(Mutex::new(None)),
             check_every_n_seconds,
         }
     }
 
-    /// Try to consume a token.
-    ///
-    /// # Returns
-    ///
-    /// True means that the tokens were consumed, and the caller can proceed to
-    /// make the request. False means that the tokens were not consumed, and
-    /// the caller should try again later.
-    async fn consume(&self) -> Result<bool> {
-        let mut available_tokens = self.available_tokens.lock().await;
-        let mut last = self.last.lock().await;
+    /// Keep the bucket in `backend` instead of a private in-memory backend
+    pub fn with_backend(mut self, backend: Arc<dyn RateLimitBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set the key of the bucket in the backend
+    ///
+    /// Rate limiters using the same backend and key share their tokens.
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Get the backend keeping the bucket
+    pub fn backend(&self) -> &Arc<dyn RateLimitBackend> {
+        &self.backend
+    }
+
+    /// Get the key of the bucket in the backend
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn params(&self) -> TokenBucketParams {
+        TokenBucketParams {
+            rate_per_second: self.requests_per_second,
+            max_size: self.max_bucket_size,
+            // Start with 1 token to allow first request
+            initial: 1.0,
+        }
+    }
+
+    /// Get the current number of available tokens
+    ///
+    /// Returns 0 when the backend cannot be reached.
+    pub async fn available_tokens(&self) -> f64 {
+        self.backend
+            .aavailable(&self.key, self.params())
+            .await
+            .unwrap_or(0.0)
+    }
+
+    /// Get the maximum bucket size
+    pub fn max_bucket_size(&self) -> f64 {
+        self.max_bucket_size
+    }
+
+    /// Get the requests per second rate
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests_per_second
+    }
+
+    /// Get the check interval in seconds
+    pub fn check_every_n_seconds(&self) -> f64 {
+        self.check_every_n_seconds
+    }
+
+    /// Convert to a serializable configuration
+    pub fn to_config(&self) -> InMemoryRateLimiterConfig {
+        InMemoryRateLimiterConfig {
+            requests_per_second: self.requests_per_second,
+            max_bucket_size: self.max_bucket_size,
+            check_every_n_seconds: self.check_every_n_seconds,
+        }
+    }
+
+    /// Create from a serializable configuration
+    pub fn from_config(config: InMemoryRateLimiterConfig) -> Self {
+        Self::new(
+            config.requests_per_second,
+            config.check_every_n_seconds,
+            config.max_bucket_size,
+        )
+    }
+}
+
+impl std::fmt::Debug for InMemoryRateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InMemoryRateLimiter")
+            .field("requests_per_second", &self.requests_per_second)
+            .field("key", &self.key)
+            .field("max_bucket_size", &self.max_bucket_size)
+            .field("check_every_n_seconds", &self.check_every_n_seconds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl_serializable!(
+    InMemoryRateLimiterConfig,
+    [
+        "ferriclink",
+        "rate_limiters",
+        "in_memory_rate_limiter_config"
+    ]
+);
+
+#[async_trait]
+impl BaseRateLimiter for InMemoryRateLimiter {
+    fn acquire(&self, blocking: bool) -> Result<bool> {
+        self.acquire_n(1.0, blocking)
+    }
+
+    async fn aacquire(&self, blocking: bool) -> Result<bool> {
+        self.aacquire_n(1.0, blocking).await
+    }
+
+    fn acquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.max_bucket_size)?;
+        let params = self.params();
+        if !blocking {
+            return self.backend.try_consume(&self.key, params, cost);
+        }
+        // A backend error stops the wait and is returned
+        let mut error = None;
+        let acquired = poll_blocking(&self.waiters, self.check_every_n_seconds, || {
+            match self.backend.try_consume(&self.key, params, cost) {
+                Ok(acquired) => acquired,
+                Err(e) => {
+                    error = Some(e);
+                    true
+                }
+            }
+        });
+        error.map_or(Ok(acquired), Err)
+    }
+
+    async fn aacquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.max_bucket_size)?;
+        let params = self.params();
+        if !blocking {
+            return self.backend.atry_consume(&self.key, params, cost).await;
+        }
+        loop {
+            let notified = self.waiters.notify.notified();
+            tokio::pin!(notified);
+            // Register before trying, so that no wake-up is missed
+            notified.as_mut().enable();
+            if self.backend.atry_consume(&self.key, params, cost).await? {
+                return Ok(true);
+            }
+            let _ = tokio::time::timeout(
+                Duration::from_secs_f64(self.check_every_n_seconds),
+                notified,
+            )
+            .await;
+        }
+    }
+
+    fn refund(&self, amount: f64) -> Result<()> {
+        self.backend.refund(&self.key, self.params(), amount)?;
+        self.waiters.wake();
+        Ok(())
+    }
+}
+
+/// An in-memory rate limiter combining request and LLM token budgets.
+///
+/// Unlike [`InMemoryRateLimiter`], whose tokens only count requests, this
+/// rate limiter keeps two token buckets: one refilled with requests per
+/// second, and one refilled with LLM tokens per minute. Acquiring `cost`
+/// units with [`BaseRateLimiter::aacquire_n`] consumes one request and `cost`
+/// LLM tokens, only when both are available. When a request used fewer
+/// tokens than acquired, the difference can be given back with
+/// [`BaseRateLimiter::refund`]; a negative refund charges tokens used beyond
+/// the estimate.
+///
+/// # Example
+///
+/// ```rust
+/// use ferriclink_core::rate_limiters::{BaseRateLimiter, TokenRateLimiter};
+///
+/// # tokio_test::block_on(async {
+/// // 2 requests per second and 10,000 tokens per minute
+/// let rate_limiter = TokenRateLimiter::new(2.0, 10_000.0);
+///
+/// // Reserve the estimated tokens of a request
+/// assert!(rate_limiter.aacquire_n(1_500.0, true).await.unwrap());
+///
+/// // The request used only 1,200 tokens
+/// rate_limiter.refund(300.0).unwrap();
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct TokenRateLimiter {
+    /// The request and LLM token buckets
+    buckets: Arc<Mutex<(TokenBucket, TokenBucket)>>,
+    /// Threads and tasks waiting for tokens
+    waiters: Arc<Waiters>,
+    /// The configuration
+    config: TokenRateLimiterConfig,
+}
+
+/// Serializable configuration of a [`TokenRateLimiter`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenRateLimiterConfig {
+    /// Number of requests that we can make per second
+    pub requests_per_second: f64,
+    /// Number of LLM tokens that we can use per minute
+    pub tokens_per_minute: f64,
+    /// Maximum number of requests that can be made in a burst
+    pub max_request_burst: f64,
+    /// Maximum number of LLM tokens that can be used in a burst
+    pub max_token_burst: f64,
+    /// Check whether tokens are available every this many seconds
+    pub check_every_n_seconds: f64,
+}
+
+impl TokenRateLimiter {
+    /// Create a new token-aware rate limiter.
+    ///
+    /// Bursts are limited to one request and to one minute of LLM tokens,
+    /// which are all available at first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a rate is not greater than 0.0.
+    pub fn new(requests_per_second: f64, tokens_per_minute: f64) -> Self {
+        Self::from_config(TokenRateLimiterConfig {
+            requests_per_second,
+            tokens_per_minute,
+            max_request_burst: 1.0,
+            max_token_burst: tokens_per_minute,
+            check_every_n_seconds: 0.1,
+        })
+    }
+
+    /// Set the maximum number of requests that can be made in a burst.
+    pub fn with_max_request_burst(self, max_request_burst: f64) -> Self {
+        Self::from_config(TokenRateLimiterConfig {
+            max_request_burst,
+            ..self.config
+        })
+    }
+
+    /// Set the maximum number of LLM tokens that can be used in a burst.
+    pub fn with_max_token_burst(self, max_token_burst: f64) -> Self {
+        Self::from_config(TokenRateLimiterConfig {
+            max_token_burst,
+            ..self.config
+        })
+    }
+
+    /// Set how often to check whether tokens are available, in seconds.
+    pub fn with_check_every_n_seconds(mut self, check_every_n_seconds: f64) -> Self {
+        assert!(
+            check_every_n_seconds > 0.0,
+            "check_every_n_seconds must be greater than 0.0"
+        );
+        self.config.check_every_n_seconds = check_every_n_seconds;
+        self
+    }
+
+    /// Create from a serializable configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a rate or the check interval is not greater than 0.0, or if
+    /// a burst is less than 1.0.
+    pub fn from_config(config: TokenRateLimiterConfig) -> Self {
+        assert!(
+            config.requests_per_second > 0.0,
+            "requests_per_second must be greater than 0.0"
+        );
+        assert!(
+            config.tokens_per_minute > 0.0,
+            "tokens_per_minute must be greater than 0.0"
+        );
+        assert!(
+            config.max_request_burst >= 1.0,
+            "max_request_burst must be at least 1.0"
+        );
+        assert!(
+            config.max_token_burst >= 1.0,
+            "max_token_burst must be at least 1.0"
+        );
+        assert!(
+            config.check_every_n_seconds > 0.0,
+            "check_every_n_seconds must be greater than 0.0"
+        );
+
+        let requests = TokenBucket::new(config.requests_per_second, config.max_request_burst, 1.0);
+        let tokens = TokenBucket::new(
+            config.tokens_per_minute / 60.0,
+            config.max_token_burst,
+            config.max_token_burst,
+        );
+        Self {
+            buckets: Arc::new(Mutex::new((requests, tokens))),
+            waiters: Arc::new(Waiters::default()),
+            config,
+        }
+    }
+
+    /// Convert to a serializable configuration
+    pub fn to_config(&self) -> TokenRateLimiterConfig {
+        self.config.clone()
+    }
+
+    /// Get the number of requests that can be made now
+    pub fn available_requests(&self) -> f64 {
+        let mut buckets = self.lock();
+        buckets.0.refill(Instant::now());
+        buckets.0.available
+    }
+
+    /// Get the number of LLM tokens that can be used now, negative when in debt
+    pub fn available_tokens(&self) -> f64 {
+        let mut buckets = self.lock();
+        buckets.1.refill(Instant::now());
+        buckets.1.available
+    }
+
+    /// Try to consume a request and `cost` LLM tokens, only if both are available.
+    fn consume(&self, cost: f64) -> bool {
+        let mut buckets = self.lock();
+        let (requests, tokens) = &mut *buckets;
+        let now = Instant::now();
+        requests.refill(now);
+        tokens.refill(now);
+
+        if requests.has(1.0) && tokens.has(cost) {
+            requests.available -= 1.0;
+            tokens.available -= cost;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (TokenBucket, TokenBucket)> {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl_serializable!(
+    TokenRateLimiterConfig,
+    ["ferriclink", "rate_limiters", "token_rate_limiter_config"]
+);
+
+#[async_trait]
+impl BaseRateLimiter for TokenRateLimiter {
+    fn acquire(&self, blocking: bool) -> Result<bool> {
+        self.acquire_n(0.0, blocking)
+    }
+
+    async fn aacquire(&self, blocking: bool) -> Result<bool> {
+        self.aacquire_n(0.0, blocking).await
+    }
+
+    fn acquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.config.max_token_burst)?;
+        if !blocking {
+            return Ok(self.consume(cost));
+        }
+        Ok(poll_blocking(
+            &self.waiters,
+            self.config.check_every_n_seconds,
+            || self.consume(cost),
+        ))
+    }
+
+    async fn aacquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.config.max_token_burst)?;
+        if !blocking {
+            return Ok(self.consume(cost));
+        }
+        Ok(
+            poll_async(&self.waiters, self.config.check_every_n_seconds, || {
+                self.consume(cost)
+            })
+            .await,
+        )
+    }
+
+    fn refund(&self, amount: f64) -> Result<()> {
+        if !amount.is_finite() {
+            return Err(FerricLinkError::validation(format!(
+                "Rate limiter refund must be a finite number, got {amount}"
+            )));
+        }
+        {
+            let mut buckets = self.lock();
+            buckets.1.refill(Instant::now());
+            buckets.1.refund(amount);
+        }
+        self.waiters.wake();
+        Ok(())
+    }
+}
+
+/// Serializable configuration of a [`GcraRateLimiter`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GcraRateLimiterConfig {
+    /// Number of requests that we can make per second
+    pub requests_per_second: f64,
+    /// Maximum number of requests that can be made in a burst
+    pub burst: f64,
+}
+
+impl_serializable!(
+    GcraRateLimiterConfig,
+    ["ferriclink", "rate_limiters", "gcra_rate_limiter_config"]
+);
+
+/// Longest interval a [`GcraRateLimiter`] adds to its theoretical arrival time
+const GCRA_MAX_INTERVAL: Duration = Duration::from_secs(30 * 365 * 24 * 60 * 60);
+
+/// An in-memory rate limiter based on the generic cell rate algorithm (GCRA).
+///
+/// Instead of counting tokens, GCRA tracks the theoretical arrival time of
+/// the next request: every unit of cost pushes it forward by one emission
+/// interval (`1 / requests_per_second`), and a request is allowed as long as
+/// it stays within `burst` intervals of now. Blocking acquisitions reserve
+/// their slot immediately and sleep exactly until it is allowed, so they
+/// never poll and proceed in the order they arrived. A reservation whose
+/// async acquisition is cancelled is given back.
+#[derive(Debug, Clone)]
+pub struct GcraRateLimiter {
+    /// Theoretical arrival time of the next request, `None` before the first one
+    tat: Arc<Mutex<Option<Instant>>>,
+    /// The configuration
+    config: GcraRateLimiterConfig,
+}
+
+impl GcraRateLimiter {
+    /// Create a new GCRA rate limiter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is not greater than 0.0 or if `burst`
+    /// is less than 1.0.
+    pub fn new(requests_per_second: f64, burst: f64) -> Self {
+        Self::from_config(GcraRateLimiterConfig {
+            requests_per_second,
+            burst,
+        })
+    }
+
+    /// Create from a serializable configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is not greater than 0.0 or if `burst`
+    /// is less than 1.0.
+    pub fn from_config(config: GcraRateLimiterConfig) -> Self {
+        assert!(
+            config.requests_per_second > 0.0,
+            "requests_per_second must be greater than 0.0"
+        );
+        assert!(config.burst >= 1.0, "burst must be at least 1.0");
+        Self {
+            tat: Arc::new(Mutex::new(None)),
+            config,
+        }
+    }
+
+    /// Convert to a serializable configuration
+    pub fn to_config(&self) -> GcraRateLimiterConfig {
+        self.config.clone()
+    }
+
+    /// Get how long an acquisition of `cost` units would have to wait now
+    pub fn wait_time(&self, cost: f64) -> Duration {
+        let tat = *self.lock();
+        self.delay(tat, Instant::now(), cost).1
+    }
+
+    /// Get the time taken by `units` units at the configured rate.
+    ///
+    /// Intervals are capped at [`GCRA_MAX_INTERVAL`], so that huge costs and
+    /// negative refunds cannot overflow instants.
+    fn interval(&self, units: f64) -> Duration {
+        Duration::try_from_secs_f64(units.max(0.0) / self.config.requests_per_second)
+            .map_or(GCRA_MAX_INTERVAL, |interval| {
+                interval.min(GCRA_MAX_INTERVAL)
+            })
+    }
+
+    /// Compute the new theoretical arrival time and the delay before a request of `cost` units.
+    fn delay(&self, tat: Option<Instant>, now: Instant, cost: f64) -> (Instant, Duration) {
+        let start = tat.map_or(now, |tat| tat.max(now));
+        let new_tat = start.checked_add(self.interval(cost)).unwrap_or(start);
+        let delay = new_tat
+            .saturating_duration_since(now)
+            .saturating_sub(self.interval(self.config.burst));
+        (new_tat, delay)
+    }
+
+    /// Reserve `cost` units, returning how long to wait before using them.
+    ///
+    /// When the units are not available now and `wait` is false, nothing is
+    /// reserved and `None` is returned.
+    fn reserve(&self, cost: f64, wait: bool) -> Option<Duration> {
+        let mut tat = self.lock();
+        let (new_tat, delay) = self.delay(*tat, Instant::now(), cost);
+        if delay.is_zero() || wait {
+            *tat = Some(new_tat);
+            Some(delay)
+        } else {
+            None
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        self.tat.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A reservation of a [`GcraRateLimiter`], given back if dropped before completion
+struct GcraReservation<'a> {
+    limiter: &'a GcraRateLimiter,
+    cost: f64,
+    completed: bool,
+}
+
+impl Drop for GcraReservation<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            let _ = self.limiter.refund(self.cost);
+        }
+    }
+}
+
+#[async_trait]
+impl BaseRateLimiter for GcraRateLimiter {
+    fn acquire(&self, blocking: bool) -> Result<bool> {
+        self.acquire_n(1.0, blocking)
+    }
+
+    async fn aacquire(&self, blocking: bool) -> Result<bool> {
+        self.aacquire_n(1.0, blocking).await
+    }
+
+    fn acquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.config.burst)?;
+        match self.reserve(cost, blocking) {
+            Some(delay) => {
+                if !delay.is_zero() {
+                    block_in_runtime(|| std::thread::sleep(delay));
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn aacquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.config.burst)?;
+        match self.reserve(cost, blocking) {
+            Some(delay) => {
+                let mut reservation = GcraReservation {
+                    limiter: self,
+                    cost,
+                    completed: false,
+                };
+                if !delay.is_zero() {
+                    sleep(delay).await;
+                }
+                reservation.completed = true;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn refund(&self, amount: f64) -> Result<()> {
+        if !amount.is_finite() {
+            return Err(FerricLinkError::validation(format!(
+                "Rate limiter refund must be a finite number, got {amount}"
+            )));
+        }
+        let mut tat = self.lock();
+        if let Some(current) = *tat {
+            *tat = Some(if amount >= 0.0 {
+                // Any arrival time in the past allows a full burst
+                Duration::try_from_secs_f64(amount / self.config.requests_per_second)
+                    .ok()
+                    .and_then(|interval| current.checked_sub(interval))
+                    .unwrap_or_else(|| current.min(Instant::now()))
+            } else {
+                let start = current.max(Instant::now());
+                start.checked_add(self.interval(-amount)).unwrap_or(start)
+            });
+        } else if amount < 0.0 {
+            let now = Instant::now();
+            *tat = Some(now.checked_add(self.interval(-amount)).unwrap_or(now));
+        }
+        Ok(())
+    }
+}
+
+/// Serializable configuration of a [`SlidingWindowRateLimiter`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlidingWindowRateLimiterConfig {
+    /// Maximum number of units acquired within any window
+    pub limit: f64,
+    /// Length of the window
+    pub window: Duration,
+}
+
+impl_serializable!(
+    SlidingWindowRateLimiterConfig,
+    [
+        "ferriclink",
+        "rate_limiters",
+        "sliding_window_rate_limiter_config"
+    ]
+);
+
+/// Log of the acquisitions of a [`SlidingWindowRateLimiter`]
+#[derive(Debug, Default)]
+struct SlidingWindowLog {
+    /// Time and cost of the acquisitions within the window, oldest first
+    entries: std::collections::VecDeque<(Instant, f64)>,
+    /// Total cost of the entries
+    total: f64,
+}
+
+impl SlidingWindowLog {
+    /// Remove the entries older than the window.
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some(&(time, cost)) = self.entries.front() {
+            if now.saturating_duration_since(time) < window {
+                break;
+            }
+            self.entries.pop_front();
+            self.total -= cost;
+        }
+        if self.entries.is_empty() {
+            self.total = 0.0;
+        }
+    }
+}
+
+/// An in-memory rate limiter based on a sliding-window log.
+///
+/// Every acquisition is logged with its time, and an acquisition is allowed
+/// when the costs logged within the last `window`, plus its own, stay within
+/// `limit`. Unlike a token bucket, this strictly enforces limits expressed
+/// over a window, such as requests per minute. Blocking acquisitions sleep
+/// exactly until enough logged acquisitions leave the window, instead of
+/// polling.
+#[derive(Debug, Clone)]
+pub struct SlidingWindowRateLimiter {
+    log: Arc<Mutex<SlidingWindowLog>>,
+    waiters: Arc<Waiters>,
+    config: SlidingWindowRateLimiterConfig,
+}
+
+impl SlidingWindowRateLimiter {
+    /// Create a new sliding-window rate limiter allowing `limit` units per `window`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is less than 1.0 or if `window` is zero.
+    pub fn new(limit: f64, window: Duration) -> Self {
+        Self::from_config(SlidingWindowRateLimiterConfig { limit, window })
+    }
+
+    /// Create from a serializable configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is less than 1.0 or if `window` is zero.
+    pub fn from_config(config: SlidingWindowRateLimiterConfig) -> Self {
+        assert!(config.limit >= 1.0, "limit must be at least 1.0");
+        assert!(!config.window.is_zero(), "window must be greater than zero");
+        Self {
+            log: Arc::new(Mutex::new(SlidingWindowLog::default())),
+            waiters: Arc::new(Waiters::default()),
+            config,
+        }
+    }
+
+    /// Convert to a serializable configuration
+    pub fn to_config(&self) -> SlidingWindowRateLimiterConfig {
+        self.config.clone()
+    }
+
+    /// Get the number of units acquired within the current window
+    pub fn used(&self) -> f64 {
+        let mut log = self.lock();
+        log.prune(Instant::now(), self.config.window);
+        log.total
+    }
+
+    /// Try to log an acquisition of `cost` units, returning how long to wait otherwise.
+    fn try_consume(&self, cost: f64) -> std::result::Result<(), Duration> {
+        let mut log = self.lock();
+        let now = Instant::now();
+        let window = self.config.window;
+        log.prune(now, window);
+
+        if log.total + cost <= self.config.limit {
+            log.entries.push_back((now, cost));
+            log.total += cost;
+            return Ok(());
+        }
+
+        // Wait until enough of the oldest entries leave the window
+        let mut remaining = log.total;
+        for &(time, entry_cost) in &log.entries {
+            remaining -= entry_cost;
+            if remaining + cost <= self.config.limit {
+                return Err((time + window).saturating_duration_since(now));
+            }
+        }
+        Err(window)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SlidingWindowLog> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl BaseRateLimiter for SlidingWindowRateLimiter {
+    fn acquire(&self, blocking: bool) -> Result<bool> {
+        self.acquire_n(1.0, blocking)
+    }
+
+    async fn aacquire(&self, blocking: bool) -> Result<bool> {
+        self.aacquire_n(1.0, blocking).await
+    }
+
+    fn acquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.config.limit)?;
+        let mut generation = self.waiters.generation();
+        let wait = match self.try_consume(cost) {
+            Ok(()) => return Ok(true),
+            Err(_) if !blocking => return Ok(false),
+            Err(wait) => wait,
+        };
+        Ok(block_in_runtime(|| {
+            let mut wait = wait;
+            loop {
+                self.waiters.wait_blocking(generation, wait);
+                generation = self.waiters.generation();
+                match self.try_consume(cost) {
+                    Ok(()) => return true,
+                    Err(next) => wait = next,
+                }
+            }
+        }))
+    }
+
+    async fn aacquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        check_cost(cost, self.config.limit)?;
+        loop {
+            let notified = self.waiters.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            match self.try_consume(cost) {
+                Ok(()) => return Ok(true),
+                Err(_) if !blocking => return Ok(false),
+                Err(wait) => {
+                    let _ = tokio::time::timeout(wait, notified).await;
+                }
+            }
+        }
+    }
+
+    fn refund(&self, amount: f64) -> Result<()> {
+        if !amount.is_finite() {
+            return Err(FerricLinkError::validation(format!(
+                "Rate limiter refund must be a finite number, got {amount}"
+            )));
+        }
+        let mut log = self.lock();
+        if amount < 0.0 {
+            log.entries.push_back((Instant::now(), -amount));
+            log.total -= amount;
+            return Ok(());
+        }
+
+        // Give back the most recent acquisitions first
+        let mut remaining = amount;
+        while remaining > 0.0 {
+            let Some(last) = log.entries.back_mut() else {
+                break;
+            };
+            let refunded = last.1.min(remaining);
+            last.1 -= refunded;
+            remaining -= refunded;
+            if last.1 <= 0.0 {
+                log.entries.pop_back();
+            }
+            log.total -= refunded;
+        }
+        drop(log);
+        self.waiters.wake();
+        Ok(())
+    }
+}
+
+/// Rate limiting strategy, selecting a rate limiter implementation from configuration
+///
+/// # Example
+///
+/// ```rust
+/// use ferriclink_core::rate_limiters::RateLimitStrategy;
+///
+/// let strategy: RateLimitStrategy = serde_json::from_str(
+///     r#"{"strategy": "gcra", "requests_per_second": 5.0, "burst": 10.0}"#,
+/// )
+/// .unwrap();
+/// let rate_limiter = strategy.build();
+/// assert!(rate_limiter.acquire(false).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum RateLimitStrategy {
+    /// A polling token bucket, see [`InMemoryRateLimiter`]
+    TokenBucket(InMemoryRateLimiterConfig),
+    /// The generic cell rate algorithm, see [`GcraRateLimiter`]
+    Gcra(GcraRateLimiterConfig),
+    /// A sliding-window log, see [`SlidingWindowRateLimiter`]
+    SlidingWindow(SlidingWindowRateLimiterConfig),
+}
+
+impl RateLimitStrategy {
+    /// Create the rate limiter of this strategy
+    pub fn build(&self) -> Arc<dyn BaseRateLimiter> {
+        match self {
+            Self::TokenBucket(config) => Arc::new(InMemoryRateLimiter::from_config(config.clone())),
+            Self::Gcra(config) => Arc::new(GcraRateLimiter::from_config(config.clone())),
+            Self::SlidingWindow(config) => {
+                Arc::new(SlidingWindowRateLimiter::from_config(config.clone()))
+            }
+        }
+    }
+
+    /// Create the rate limiter of this strategy, serving waiters in arrival order
+    pub fn build_fair(&self) -> Arc<dyn BaseRateLimiter> {
+        Arc::new(FairRateLimiter::new(self.build()))
+    }
+}
+
+impl_serializable!(
+    RateLimitStrategy,
+    ["ferriclink", "rate_limiters", "rate_limit_strategy"]
+);
+
+/// State of a [`FairQueue`]
+#[derive(Debug, Default)]
+struct FairQueueState {
+    /// Ticket given to the next waiter
+    next_ticket: u64,
+    /// Ticket of the waiter whose turn it is
+    serving: u64,
+    /// Tickets given up by waiters before their turn
+    abandoned: std::collections::HashSet<u64>,
+}
+
+/// A FIFO queue of waiters, usable from threads and tasks
+#[derive(Debug, Default)]
+struct FairQueue {
+    state: Mutex<FairQueueState>,
+    condvar: std::sync::Condvar,
+    notify: tokio::sync::Notify,
+}
+
+/// A place in a [`FairQueue`], released when dropped
+struct FairTurn<'a> {
+    queue: &'a FairQueue,
+    ticket: u64,
+}
+
+impl FairQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, FairQueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Join the end of the queue.
+    fn join(&self) -> FairTurn<'_> {
+        let mut state = self.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        FairTurn {
+            queue: self,
+            ticket,
+        }
+    }
+
+    /// Join the queue only if nobody is waiting.
+    fn try_join(&self) -> Option<FairTurn<'_>> {
+        let mut state = self.lock();
+        if state.next_ticket != state.serving {
+            return None;
+        }
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        Some(FairTurn {
+            queue: self,
+            ticket,
+        })
+    }
+
+    /// Block the thread until it is the turn of `ticket`.
+    fn wait_blocking(&self, ticket: u64) {
+        let mut state = self.lock();
+        if state.serving == ticket {
+            return;
+        }
+        block_in_runtime(|| {
+            while state.serving != ticket {
+                state = self.condvar.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+        });
+    }
+
+    /// Wait until it is the turn of `ticket`.
+    async fn wait(&self, ticket: u64) {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            // Register before checking, so that no release is missed
+            notified.as_mut().enable();
+            if self.lock().serving == ticket {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Leave the queue, passing the turn on if it was the turn of `ticket`.
+    fn release(&self, ticket: u64) {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        if state.serving != ticket {
+            state.abandoned.insert(ticket);
+            return;
+        }
+        state.serving += 1;
+        while state.abandoned.remove(&state.serving) {
+            state.serving += 1;
+        }
+        drop(guard);
+        self.condvar.notify_all();
+        self.notify.notify_waiters();
+    }
+}
+
+impl Drop for FairTurn<'_> {
+    fn drop(&mut self) {
+        self.queue.release(self.ticket);
+    }
+}
+
+/// A rate limiter serving waiters in arrival order
+///
+/// Blocking acquisitions wait in a FIFO queue shared by threads and tasks,
+/// and only the first waiter acquires from the inner rate limiter, so that
+/// waiters cannot overtake each other by racing for tokens. Non-blocking
+/// acquisitions fail while anybody is waiting. A cancelled async acquisition
+/// leaves the queue without blocking the waiters behind it.
+#[derive(Clone)]
+pub struct FairRateLimiter {
+    inner: Arc<dyn BaseRateLimiter>,
+    queue: Arc<FairQueue>,
+}
+
+impl FairRateLimiter {
+    /// Create a new fair rate limiter around another rate limiter
+    pub fn new(inner: Arc<dyn BaseRateLimiter>) -> Self {
+        Self {
+            inner,
+            queue: Arc::new(FairQueue::default()),
+        }
+    }
+
+    /// Get the number of acquisitions waiting or in progress
+    pub fn queue_len(&self) -> usize {
+        let state = self.queue.lock();
+        (state.next_ticket - state.serving) as usize - state.abandoned.len()
+    }
+}
+
+impl std::fmt::Debug for FairRateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FairRateLimiter")
+            .field("queue_len", &self.queue_len())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl BaseRateLimiter for FairRateLimiter {
+    fn acquire(&self, blocking: bool) -> Result<bool> {
+        self.acquire_n(1.0, blocking)
+    }
+
+    async fn aacquire(&self, blocking: bool) -> Result<bool> {
+        self.aacquire_n(1.0, blocking).await
+    }
+
+    fn acquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        if !blocking {
+            return match self.queue.try_join() {
+                Some(_turn) => self.inner.acquire_n(cost, false),
+                None => Ok(false),
+            };
+        }
+        let turn = self.queue.join();
+        self.queue.wait_blocking(turn.ticket);
+        self.inner.acquire_n(cost, true)
+    }
+
+    async fn aacquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        if !blocking {
+            return match self.queue.try_join() {
+                Some(_turn) => self.inner.aacquire_n(cost, false).await,
+                None => Ok(false),
+            };
+        }
+        let turn = self.queue.join();
+        self.queue.wait(turn.ticket).await;
+        self.inner.aacquire_n(cost, true).await
+    }
+
+    fn refund(&self, amount: f64) -> Result<()> {
+        self.inner.refund(amount)
+    }
+}
+
+/// Base trait for rate limiters keeping separate limits per key.
+///
+/// Keys identify who or what is limited, such as a tenant, an API key or a
+/// model name. The methods mirror [`BaseRateLimiter`], for a given key.
+#[async_trait]
+pub trait BaseKeyedRateLimiter: Send + Sync {
+    /// Attempt to acquire the necessary tokens for a key.
+    ///
+    /// This method blocks until the required tokens are available if `blocking`
+    /// is set to true, and returns immediately with the result of the attempt
+    /// otherwise.
+    fn acquire_key(&self, key: &str, blocking: bool) -> Result<bool>;
+
+    /// Attempt to acquire the necessary tokens for a key. Async version.
+    async fn aacquire_key(&self, key: &str, blocking: bool) -> Result<bool>;
+
+    /// Attempt to acquire `cost` units for a key.
+    fn acquire_key_n(&self, key: &str, cost: f64, blocking: bool) -> Result<bool> {
+        let _ = cost;
+        self.acquire_key(key, blocking)
+    }
+
+    /// Attempt to acquire `cost` units for a key. Async version.
+    async fn aacquire_key_n(&self, key: &str, cost: f64, blocking: bool) -> Result<bool> {
+        let _ = cost;
+        self.aacquire_key(key, blocking).await
+    }
+
+    /// Give back units that were acquired for a key but not used.
+    fn refund_key(&self, key: &str, amount: f64) -> Result<()> {
+        let _ = (key, amount);
+        Ok(())
+    }
+}
+
+/// Throttling metrics of a key of a [`KeyedRateLimiter`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRateLimitMetrics {
+    /// Number of successful acquisitions
+    pub acquired: u64,
+    /// Number of acquisitions that had to wait or failed because of the key's limit
+    pub throttled: u64,
+    /// Number of acquisitions that had to wait or failed because of the global limit
+    pub globally_throttled: u64,
+}
+
+impl_serializable!(
+    KeyRateLimitMetrics,
+    ["ferriclink", "rate_limiters", "key_rate_limit_metrics"]
+);
+
+/// Serializable configuration of a [`KeyedRateLimiter`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyedRateLimiterConfig {
+    /// Number of requests that each key can make per second
+    pub requests_per_second: f64,
+    /// Maximum number of tokens in the bucket of each key
+    pub max_bucket_size: f64,
+    /// Number of requests that all keys together can make per second
+    #[serde(default)]
+    pub global_requests_per_second: Option<f64>,
+    /// Maximum number of tokens in the global bucket
+    #[serde(default)]
+    pub global_max_bucket_size: Option<f64>,
+    /// How long a full bucket is kept after its last use
+    pub idle_timeout: Duration,
+    /// Check whether tokens are available every this many seconds
+    pub check_every_n_seconds: f64,
+}
+
+impl_serializable!(
+    KeyedRateLimiterConfig,
+    ["ferriclink", "rate_limiters", "keyed_rate_limiter_config"]
+);
+
+/// State of a key of a [`KeyedRateLimiter`]
+#[derive(Debug)]
+struct KeyState {
+    bucket: TokenBucket,
+    last_used: Instant,
+    metrics: KeyRateLimitMetrics,
+}
+
+/// State shared by the keys of a [`KeyedRateLimiter`]
+#[derive(Debug)]
+struct KeyedState {
+    keys: HashMap<String, KeyState>,
+    global: Option<TokenBucket>,
+    last_eviction: Instant,
+}
+
+/// Outcome of an attempt to consume tokens for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyedOutcome {
+    Acquired,
+    Throttled,
+    GloballyThrottled,
+}
+
+/// An in-memory rate limiter keeping an independent token bucket per key.
+///
+/// Every key gets its own bucket, created on first use and filled like the
+/// bucket of an [`InMemoryRateLimiter`]. An optional global bucket caps the
+/// requests of all keys together: tokens are only consumed when both the
+/// key's bucket and the global bucket have enough.
+///
+/// Buckets unused for longer than the idle timeout are evicted once they are
+/// full again, since a new bucket would then behave the same. The throttling
+/// metrics of a key are evicted with its bucket.
+///
+/// Use [`KeyedRateLimiter::for_key`] to get a [`BaseRateLimiter`] limited by
+/// one key, for example to pass to a chat model.
+///
+/// # Example
+///
+/// ```rust
+/// use ferriclink_core::rate_limiters::{BaseKeyedRateLimiter, KeyedRateLimiter};
+///
+/// # tokio_test::block_on(async {
+/// // 2 requests per second per tenant, 10 per second overall
+/// let rate_limiter = KeyedRateLimiter::new(2.0, 0.1, 2.0).with_global_limit(10.0, 10.0);
+///
+/// assert!(rate_limiter.aacquire_key("tenant-a", true).await.unwrap());
+/// assert!(rate_limiter.aacquire_key("tenant-b", true).await.unwrap());
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyedRateLimiter {
+    state: Arc<Mutex<KeyedState>>,
+    waiters: Arc<Waiters>,
+    config: KeyedRateLimiterConfig,
+}
+
+impl KeyedRateLimiter {
+    /// Create a new keyed rate limiter, without a global limit.
+    ///
+    /// The arguments are the same as for [`InMemoryRateLimiter::new`], and
+    /// apply to every key. Idle buckets are evicted after 5 minutes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_bucket_size` is less than 1.0, or if a rate or the
+    /// check interval is not greater than 0.0.
+    pub fn new(requests_per_second: f64, check_every_n_seconds: f64, max_bucket_size: f64) -> Self {
+        Self::from_config(KeyedRateLimiterConfig {
+            requests_per_second,
+            max_bucket_size,
+            global_requests_per_second: None,
+            global_max_bucket_size: None,
+            idle_timeout: Duration::from_secs(300),
+            check_every_n_seconds,
+        })
+    }
+
+    /// Cap the requests of all keys together.
+    pub fn with_global_limit(self, requests_per_second: f64, max_bucket_size: f64) -> Self {
+        Self::from_config(KeyedRateLimiterConfig {
+            global_requests_per_second: Some(requests_per_second),
+            global_max_bucket_size: Some(max_bucket_size),
+            ..self.config
+        })
+    }
+
+    /// Set how long a full bucket is kept after its last use.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.config.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Create from a serializable configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a bucket size is less than 1.0, or if a rate or the check
+    /// interval is not greater than 0.0.
+    pub fn from_config(config: KeyedRateLimiterConfig) -> Self {
+        assert!(
+            config.max_bucket_size >= 1.0,
+            "max_bucket_size must be at least 1.0"
+        );
+        assert!(
+            config.requests_per_second > 0.0,
+            "requests_per_second must be greater than 0.0"
+        );
+        assert!(
+            config.check_every_n_seconds > 0.0,
+            "check_every_n_seconds must be greater than 0.0"
+        );
+        let global = config.global_requests_per_second.map(|rate| {
+            let max_size = config.global_max_bucket_size.unwrap_or(1.0);
+            assert!(
+                rate > 0.0,
+                "global_requests_per_second must be greater than 0.0"
+            );
+            assert!(
+                max_size >= 1.0,
+                "global_max_bucket_size must be at least 1.0"
+            );
+            TokenBucket::new(rate, max_size, 1.0)
+        });
+
+        Self {
+            state: Arc::new(Mutex::new(KeyedState {
+                keys: HashMap::new(),
+                global,
+                last_eviction: Instant::now(),
+            })),
+            waiters: Arc::new(Waiters::default()),
+            config,
+        }
+    }
+
+    /// Convert to a serializable configuration
+    pub fn to_config(&self) -> KeyedRateLimiterConfig {
+        self.config.clone()
+    }
+
+    /// Get a rate limiter acquiring tokens for one key
+    pub fn for_key(&self, key: impl Into<String>) -> KeyRateLimiter {
+        KeyRateLimiter {
+            limiter: self.clone(),
+            key: key.into(),
+        }
+    }
+
+    /// Get the number of keys with a bucket
+    pub fn num_keys(&self) -> usize {
+        self.lock().keys.len()
+    }
+
+    /// Get the throttling metrics of a key, if it has a bucket
+    pub fn metrics(&self, key: &str) -> Option<KeyRateLimitMetrics> {
+        self.lock().keys.get(key).map(|state| state.metrics.clone())
+    }
+
+    /// Get the throttling metrics of all keys with a bucket
+    pub fn all_metrics(&self) -> HashMap<String, KeyRateLimitMetrics> {
+        self.lock()
+            .keys
+            .iter()
+            .map(|(key, state)| (key.clone(), state.metrics.clone()))
+            .collect()
+    }
+
+    /// Evict the buckets idle for longer than the idle timeout, returning how many were evicted.
+    pub fn evict_idle(&self) -> usize {
+        let mut state = self.lock();
+        self.evict_idle_locked(&mut state, Instant::now())
+    }
 
-        let now = Instant::now();
+    fn evict_idle_locked(&self, state: &mut KeyedState, now: Instant) -> usize {
+        let before = state.keys.len();
+        let idle_timeout = self.config.idle_timeout;
+        state.keys.retain(|_, key_state| {
+            if now.saturating_duration_since(key_state.last_used) < idle_timeout {
+                return true;
+            }
+            // Evicting a bucket that is not full would reset its limit
+            key_state.bucket.refill(now);
+            !key_state.bucket.has(key_state.bucket.max_size)
+        });
+        state.last_eviction = now;
+        before - state.keys.len()
+    }
 
-        // Initialize on first call to avoid a burst
-        if last.is_none() {
-            *last = Some(now);
+    /// Try to consume `cost` tokens for a key, and from the global bucket.
+    ///
+    /// The metrics only count a throttled acquisition when `record_throttle` is set.
+    fn consume(&self, key: &str, cost: f64, record_throttle: bool) -> KeyedOutcome {
+        let mut state = self.lock();
+        let now = Instant::now();
+        if now.saturating_duration_since(state.last_eviction) >= self.config.idle_timeout {
+            self.evict_idle_locked(&mut state, now);
         }
 
-        let elapsed = now.duration_since(last.unwrap()).as_secs_f64();
-
-        if elapsed * self.requests_per_second >= 1.0 {
-            *available_tokens += elapsed * self.requests_per_second;
-            *last = Some(now);
+        let KeyedState { keys, global, .. } = &mut *state;
+        let key_state = keys.entry(key.to_string()).or_insert_with(|| KeyState {
+            bucket: TokenBucket::new(
+                self.config.requests_per_second,
+                self.config.max_bucket_size,
+                1.0,
+            ),
+            last_used: now,
+            metrics: KeyRateLimitMetrics::default(),
+        });
+        key_state.last_used = now;
+        key_state.bucket.refill(now);
+        if let Some(global) = global.as_mut() {
+            global.refill(now);
         }
 
-        // Make sure that we don't exceed the bucket size.
-        // This is used to prevent bursts of requests.
-        *available_tokens = (*available_tokens).min(self.max_bucket_size);
-
-        // As long as we have at least one token, we can proceed.
-        if *available_tokens >= 1.0 {
-            *available_tokens -= 1.0;
-            Ok(true)
+        let outcome = if !key_state.bucket.has(cost) {
+            KeyedOutcome::Throttled
+        } else if global.as_ref().is_some_and(|global| !global.has(cost)) {
+            KeyedOutcome::GloballyThrottled
         } else {
-            Ok(false)
+            key_state.bucket.available -= cost;
+            if let Some(global) = global.as_mut() {
+                global.available -= cost;
+            }
+            KeyedOutcome::Acquired
+        };
+
+        match outcome {
+            KeyedOutcome::Acquired => key_state.metrics.acquired += 1,
+            KeyedOutcome::Throttled if record_throttle => key_state.metrics.throttled += 1,
+            KeyedOutcome::GloballyThrottled if record_throttle => {
+                key_state.metrics.globally_throttled += 1
+            }
+            _ => {}
         }
+        outcome
     }
 
-    /// Get the current number of available tokens
-    pub async fn available_tokens(&self) -> f64 {
-        *self.available_tokens.lock().await
+    fn check_key_cost(&self, cost: f64) -> Result<()> {
+        check_cost(cost, self.config.max_bucket_size)?;
+        if self.config.global_requests_per_second.is_some() {
+            check_cost(cost, self.config.global_max_bucket_size.unwrap_or(1.0))?;
+        }
+        Ok(())
     }
 
-    /// Get the maximum bucket size
-    pub fn max_bucket_size(&self) -> f64 {
-        self.max_bucket_size
+    fn lock(&self) -> std::sync::MutexGuard<'_, KeyedState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
     }
+}
 
-    /// Get the requests per second rate
-    pub fn requests_per_second(&self) -> f64 {
-        self.requests_per_second
+#[async_trait]
+impl BaseKeyedRateLimiter for KeyedRateLimiter {
+    fn acquire_key(&self, key: &str, blocking: bool) -> Result<bool> {
+        self.acquire_key_n(key, 1.0, blocking)
     }
 
-    /// Get the check interval in seconds
-    pub fn check_every_n_seconds(&self) -> f64 {
-        self.check_every_n_seconds
+    async fn aacquire_key(&self, key: &str, blocking: bool) -> Result<bool> {
+        self.aacquire_key_n(key, 1.0, blocking).await
     }
 
-    /// Convert to a serializable configuration
-    pub fn to_config(&self) -> InMemoryRateLimiterConfig {
-        InMemoryRateLimiterConfig {
-            requests_per_second: self.requests_per_second,
-            max_bucket_size: self.max_bucket_size,
-            check_every_n_seconds: self.check_every_n_seconds,
+    fn acquire_key_n(&self, key: &str, cost: f64, blocking: bool) -> Result<bool> {
+        self.check_key_cost(cost)?;
+        let mut first = true;
+        let mut try_acquire = || {
+            let acquired = self.consume(key, cost, first) == KeyedOutcome::Acquired;
+            first = false;
+            acquired
+        };
+        if !blocking {
+            return Ok(try_acquire());
         }
+        Ok(poll_blocking(
+            &self.waiters,
+            self.config.check_every_n_seconds,
+            try_acquire,
+        ))
     }
 
-    /// Create from a serializable configuration
-    pub fn from_config(config: InMemoryRateLimiterConfig) -> Self {
-        Self::new(
-            config.requests_per_second,
-            config.check_every_n_seconds,
-            config.max_bucket_size,
+    async fn aacquire_key_n(&self, key: &str, cost: f64, blocking: bool) -> Result<bool> {
+        self.check_key_cost(cost)?;
+        let mut first = true;
+        let mut try_acquire = || {
+            let acquired = self.consume(key, cost, first) == KeyedOutcome::Acquired;
+            first = false;
+            acquired
+        };
+        if !blocking {
+            return Ok(try_acquire());
+        }
+        Ok(poll_async(
+            &self.waiters,
+            self.config.check_every_n_seconds,
+            try_acquire,
         )
+        .await)
     }
-}
-
-impl_serializable!(
-    InMemoryRateLimiterConfig,
-    [
-        "ferriclink",
-        "rate_limiters",
-        "in_memory_rate_limiter_config"
-    ]
-);
 
-#[async_trait]
-impl BaseRateLimiter for InMemoryRateLimiter {
-    fn acquire(&self, blocking: bool) -> Result<bool> {
-        // For sync context, we need to use a blocking approach
-        if !blocking {
-            // Use tokio::runtime::Handle::try_current() to run async code in sync context
-            if let Ok(handle) = tokio::runtime::Handle::try_current() {
-                return handle.block_on(self.consume());
-            } else {
-                // If we're not in an async context, create a new runtime
-                let rt = tokio::runtime::Runtime::new().map_err(|e| {
-                    crate::errors::FerricLinkError::runtime(format!(
-                        "Failed to create runtime: {e}",
-                    ))
-                })?;
-                return rt.block_on(self.consume());
+    fn refund_key(&self, key: &str, amount: f64) -> Result<()> {
+        if !amount.is_finite() {
+            return Err(FerricLinkError::validation(format!(
+                "Rate limiter refund must be a finite number, got {amount}"
+            )));
+        }
+        {
+            let mut state = self.lock();
+            let now = Instant::now();
+            let KeyedState { keys, global, .. } = &mut *state;
+            // An evicted key's bucket was full, but the global share is still owed
+            if let Some(key_state) = keys.get_mut(key) {
+                key_state.bucket.refill(now);
+                key_state.bucket.refund(amount);
+            }
+            if let Some(global) = global.as_mut() {
+                global.refill(now);
+                global.refund(amount);
             }
         }
+        self.waiters.wake();
+        Ok(())
+    }
+}
 
-        // For blocking mode, we need to poll until we can acquire
-        loop {
-            let acquired = if let Ok(handle) = tokio::runtime::Handle::try_current() {
-                handle.block_on(self.consume())?
-            } else {
-                let rt = tokio::runtime::Runtime::new().map_err(|e| {
-                    crate::errors::FerricLinkError::runtime(format!(
-                        "Failed to create runtime: {e}",
-                    ))
-                })?;
-                rt.block_on(self.consume())?
-            };
+/// A rate limiter acquiring tokens for one key of a [`KeyedRateLimiter`]
+#[derive(Debug, Clone)]
+pub struct KeyRateLimiter {
+    limiter: KeyedRateLimiter,
+    key: String,
+}
 
-            if acquired {
-                return Ok(true);
-            }
+impl KeyRateLimiter {
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
 
-            // Sleep for the check interval
-            std::thread::sleep(Duration::from_secs_f64(self.check_every_n_seconds));
-        }
+#[async_trait]
+impl BaseRateLimiter for KeyRateLimiter {
+    fn acquire(&self, blocking: bool) -> Result<bool> {
+        self.limiter.acquire_key(&self.key, blocking)
     }
 
     async fn aacquire(&self, blocking: bool) -> Result<bool> {
-        if !blocking {
-            return self.consume().await;
-        }
+        self.limiter.aacquire_key(&self.key, blocking).await
+    }
 
-        loop {
-            if self.consume().await? {
-                return Ok(true);
-            }
+    fn acquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        self.limiter.acquire_key_n(&self.key, cost, blocking)
+    }
 
-            sleep(Duration::from_secs_f64(self.check_every_n_seconds)).await;
-        }
+    async fn aacquire_n(&self, cost: f64, blocking: bool) -> Result<bool> {
+        self.limiter.aacquire_key_n(&self.key, cost, blocking).await
+    }
+
+    fn refund(&self, amount: f64) -> Result<()> {
+        self.limiter.refund_key(&self.key, amount)
     }
 }
 
@@ -399,7 +2427,7 @@ impl AdvancedRateLimiter {
                     }
 
                     if retries >= self.config.max_retries {
-                        return Err(crate::errors::FerricLinkError::model_rate_limit(
+                        return Err(FerricLinkError::model_rate_limit(
                             "Max retries exceeded for rate limiter",
                         ));
                     }
@@ -457,6 +2485,7 @@ impl_serializable!(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::serializable::Serializable;
 
     #[tokio::test]
     async fn test_in_memory_rate_limiter_basic() {
@@ -563,6 +2592,473 @@ mod tests {
         assert!(tokens_after >= 0.0);
     }
 
+    #[tokio::test]
+    async fn test_in_memory_rate_limiter_cost() {
+        let rate_limiter = InMemoryRateLimiter::new(10.0, 0.01, 5.0);
+
+        // Only 1 token is available at first
+        assert!(!rate_limiter.aacquire_n(2.0, false).await.unwrap());
+        sleep(Duration::from_millis(200)).await;
+        assert!(rate_limiter.aacquire_n(2.0, false).await.unwrap());
+
+        rate_limiter.refund(2.0).unwrap();
+        assert!(rate_limiter.available_tokens().await >= 2.0);
+        assert!(rate_limiter.aacquire_n(6.0, true).await.is_err());
+    }
+
+    #[test]
+    fn test_in_memory_rate_limiter_sync_acquire() {
+        let rate_limiter = InMemoryRateLimiter::new(20.0, 0.01, 1.0);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(rate_limiter.acquire(true).unwrap());
+        }
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_in_memory_rate_limiter_mixed_sync_and_async() {
+        let rate_limiter = Arc::new(InMemoryRateLimiter::new(100.0, 0.005, 1.0));
+        let start = Instant::now();
+
+        let mut tasks = Vec::new();
+        for i in 0..6 {
+            let rate_limiter = rate_limiter.clone();
+            tasks.push(tokio::spawn(async move {
+                for _ in 0..5 {
+                    // Sync calls from tasks must neither panic nor stall the runtime
+                    if i % 2 == 0 {
+                        assert!(rate_limiter.acquire(true).unwrap());
+                    } else {
+                        assert!(rate_limiter.aacquire(true).await.unwrap());
+                    }
+                }
+            }));
+        }
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let rate_limiter = rate_limiter.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        assert!(rate_limiter.acquire(true).unwrap());
+                    }
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // 40 acquisitions at 100 per second, with a single token at first
+        assert!(start.elapsed() >= Duration::from_millis(350));
+        assert!(rate_limiter.available_tokens().await < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_sync_acquire_in_current_thread_runtime() {
+        let rate_limiters: Vec<Arc<dyn BaseRateLimiter>> = vec![
+            Arc::new(InMemoryRateLimiter::new(50.0, 0.005, 1.0)),
+            Arc::new(TokenRateLimiter::new(50.0, 6_000.0).with_check_every_n_seconds(0.005)),
+            Arc::new(GcraRateLimiter::new(50.0, 1.0)),
+            Arc::new(SlidingWindowRateLimiter::new(
+                1.0,
+                Duration::from_millis(20),
+            )),
+            Arc::new(FairRateLimiter::new(Arc::new(GcraRateLimiter::new(
+                50.0, 1.0,
+            )))),
+            Arc::new(KeyedRateLimiter::new(50.0, 0.005, 1.0).for_key("key")),
+        ];
+        for rate_limiter in rate_limiters {
+            assert!(rate_limiter.acquire(true).unwrap());
+            assert!(rate_limiter.acquire(true).unwrap());
+            assert!(rate_limiter.aacquire(true).await.unwrap());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_refund_wakes_threads_and_tasks() {
+        // 1 token per second, checked only every 10 seconds
+        let rate_limiter = Arc::new(
+            TokenRateLimiter::new(100.0, 60.0)
+                .with_max_request_burst(10.0)
+                .with_check_every_n_seconds(10.0),
+        );
+        assert!(rate_limiter.aacquire_n(60.0, false).await.unwrap());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread = {
+            let rate_limiter = rate_limiter.clone();
+            std::thread::spawn(move || {
+                assert!(rate_limiter.acquire_n(20.0, true).unwrap());
+                sender.send(()).unwrap();
+            })
+        };
+        let task = {
+            let rate_limiter = rate_limiter.clone();
+            tokio::spawn(async move { rate_limiter.aacquire_n(20.0, true).await })
+        };
+
+        sleep(Duration::from_millis(50)).await;
+        rate_limiter.refund(60.0).unwrap();
+
+        let acquired = tokio::time::timeout(Duration::from_secs(2), task).await;
+        assert!(acquired.unwrap().unwrap().unwrap());
+        receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_token_rate_limiter_requires_both_buckets() {
+        let rate_limiter = TokenRateLimiter::new(1.0, 6_000.0);
+
+        assert!(rate_limiter.aacquire_n(10.0, false).await.unwrap());
+        // No request is left, so no token is consumed
+        assert!(!rate_limiter.aacquire_n(10.0, false).await.unwrap());
+        assert!(rate_limiter.available_tokens() >= 5_990.0);
+        assert!(rate_limiter.available_requests() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_token_rate_limiter_waits_for_tokens() {
+        // 100 tokens per second, with a burst of 100 tokens
+        let rate_limiter = TokenRateLimiter::new(1_000.0, 6_000.0)
+            .with_max_request_burst(10.0)
+            .with_max_token_burst(100.0)
+            .with_check_every_n_seconds(0.01);
+
+        assert!(rate_limiter.aacquire_n(100.0, false).await.unwrap());
+        assert!(!rate_limiter.aacquire_n(50.0, false).await.unwrap());
+
+        let start = Instant::now();
+        assert!(rate_limiter.aacquire_n(50.0, true).await.unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        // Costs larger than the burst can never be acquired
+        assert!(rate_limiter.aacquire_n(101.0, true).await.is_err());
+        assert!(rate_limiter.acquire_n(-1.0, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_rate_limiter_refunds() {
+        let rate_limiter = TokenRateLimiter::new(100.0, 6_000.0)
+            .with_max_request_burst(10.0)
+            .with_max_token_burst(100.0);
+
+        assert!(rate_limiter.aacquire_n(80.0, false).await.unwrap());
+        rate_limiter.refund(30.0).unwrap();
+        assert!(rate_limiter.aacquire_n(40.0, true).await.unwrap());
+
+        // Refunds never overflow the bucket
+        rate_limiter.refund(1_000.0).unwrap();
+        assert!(rate_limiter.available_tokens() <= 100.0);
+
+        // Charging more than available leaves the limiter in debt
+        rate_limiter.refund(-150.0).unwrap();
+        assert!(rate_limiter.available_tokens() < 0.0);
+        assert!(!rate_limiter.aacquire_n(1.0, false).await.unwrap());
+        assert!(rate_limiter.refund(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_token_rate_limiter_config() {
+        let rate_limiter = TokenRateLimiter::new(2.0, 1_000.0).with_max_request_burst(4.0);
+        let json = rate_limiter.to_config().to_json().unwrap();
+        let config = TokenRateLimiterConfig::from_json(&json).unwrap();
+        assert_eq!(config.max_request_burst, 4.0);
+        assert_eq!(config.max_token_burst, 1_000.0);
+        assert_eq!(
+            TokenRateLimiter::from_config(config.clone()).to_config(),
+            config
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gcra_rate_limiter_waits_exactly() {
+        let rate_limiter = GcraRateLimiter::new(20.0, 1.0);
+
+        assert!(rate_limiter.aacquire(false).await.unwrap());
+        assert!(!rate_limiter.aacquire(false).await.unwrap());
+        let wait = rate_limiter.wait_time(1.0);
+        assert!(wait > Duration::from_millis(30) && wait <= Duration::from_millis(50));
+
+        let start = Instant::now();
+        assert!(rate_limiter.aacquire(true).await.unwrap());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(30) && elapsed < Duration::from_millis(200));
+        assert!(rate_limiter.aacquire_n(2.0, true).await.is_err());
+    }
+
+    #[test]
+    fn test_gcra_rate_limiter_burst() {
+        let rate_limiter = GcraRateLimiter::new(10.0, 3.0);
+        for _ in 0..3 {
+            assert!(rate_limiter.acquire(false).unwrap());
+        }
+        assert!(!rate_limiter.acquire(false).unwrap());
+
+        rate_limiter.refund(1.0).unwrap();
+        assert!(rate_limiter.acquire(false).unwrap());
+
+        // Huge refunds and charges are clamped instead of overflowing
+        rate_limiter.refund(-1e300).unwrap();
+        assert!(!rate_limiter.acquire(false).unwrap());
+        assert!(rate_limiter.wait_time(1.0) > Duration::from_secs(3600));
+        rate_limiter.refund(1e300).unwrap();
+        assert!(rate_limiter.acquire(false).unwrap());
+        assert!(rate_limiter.refund(f64::INFINITY).is_err());
+
+        let slow = GcraRateLimiter::new(1e-300, 1.0);
+        assert!(slow.acquire(false).unwrap());
+        assert!(!slow.acquire(false).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_gcra_rate_limiter_gives_back_cancelled_reservations() {
+        let rate_limiter = GcraRateLimiter::new(2.0, 1.0);
+        assert!(rate_limiter.aacquire(true).await.unwrap());
+
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(20), rate_limiter.aacquire(true)).await;
+        assert!(cancelled.is_err());
+
+        // Only the first request is still accounted for
+        assert!(rate_limiter.wait_time(1.0) <= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_sliding_window_rate_limiter() {
+        let rate_limiter = SlidingWindowRateLimiter::new(3.0, Duration::from_millis(200));
+
+        for _ in 0..3 {
+            assert!(rate_limiter.aacquire(false).await.unwrap());
+        }
+        assert!(!rate_limiter.aacquire(false).await.unwrap());
+        assert_eq!(rate_limiter.used(), 3.0);
+
+        let start = Instant::now();
+        assert!(rate_limiter.aacquire(true).await.unwrap());
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(150) && elapsed < Duration::from_millis(400));
+        assert_eq!(rate_limiter.used(), 1.0);
+
+        rate_limiter.refund(1.0).unwrap();
+        assert_eq!(rate_limiter.used(), 0.0);
+        assert!(rate_limiter.acquire_n(3.0, false).unwrap());
+        rate_limiter.refund(-1.0).unwrap();
+        assert_eq!(rate_limiter.used(), 4.0);
+        assert!(rate_limiter.acquire_n(4.0, true).is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_strategy_config() {
+        let strategies: Vec<RateLimitStrategy> = serde_json::from_str(
+            r#"[
+                {"strategy": "token_bucket", "requests_per_second": 1.0, "max_bucket_size": 1.0, "check_every_n_seconds": 0.1},
+                {"strategy": "gcra", "requests_per_second": 1.0, "burst": 1.0},
+                {"strategy": "sliding_window", "limit": 1.0, "window": {"secs": 1, "nanos": 0}}
+            ]"#,
+        )
+        .unwrap();
+        assert!(matches!(strategies[1], RateLimitStrategy::Gcra(_)));
+
+        for strategy in &strategies {
+            let rate_limiter = strategy.build();
+            assert!(rate_limiter.acquire(false).unwrap());
+            assert!(!rate_limiter.acquire(false).unwrap());
+
+            let json = strategy.to_json().unwrap();
+            assert_eq!(&RateLimitStrategy::from_json(&json).unwrap(), strategy);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_fair_rate_limiter_serves_in_arrival_order() {
+        let rate_limiter = Arc::new(FairRateLimiter::new(Arc::new(
+            SlidingWindowRateLimiter::new(1.0, Duration::from_millis(30)),
+        )));
+        assert!(rate_limiter.aacquire(true).await.unwrap());
+
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        let mut threads = Vec::new();
+        for i in 0..6 {
+            let limiter = rate_limiter.clone();
+            let served = order.clone();
+            if i % 3 == 2 {
+                threads.push(std::thread::spawn(move || {
+                    assert!(limiter.acquire(true).unwrap());
+                    served.lock().unwrap().push(i);
+                }));
+            } else {
+                tasks.push(tokio::spawn(async move {
+                    assert!(limiter.aacquire(true).await.unwrap());
+                    served.lock().unwrap().push(i);
+                }));
+            }
+            // Let the waiter join the queue before the next one, counting
+            // served waiters first so that none is counted twice
+            loop {
+                let served = order.lock().unwrap().len();
+                if served + rate_limiter.queue_len() > i {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+        assert!(!rate_limiter.aacquire(false).await.unwrap());
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), (0..6).collect::<Vec<_>>());
+        assert_eq!(rate_limiter.queue_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fair_rate_limiter_skips_cancelled_waiters() {
+        let rate_limiter = Arc::new(FairRateLimiter::new(Arc::new(
+            SlidingWindowRateLimiter::new(1.0, Duration::from_millis(100)),
+        )));
+        assert!(rate_limiter.aacquire(true).await.unwrap());
+
+        let first = {
+            let rate_limiter = rate_limiter.clone();
+            tokio::spawn(async move { rate_limiter.aacquire(true).await })
+        };
+        let second = {
+            let rate_limiter = rate_limiter.clone();
+            tokio::spawn(async move { rate_limiter.aacquire(true).await })
+        };
+        while rate_limiter.queue_len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        first.abort();
+
+        let acquired = tokio::time::timeout(Duration::from_secs(2), second).await;
+        assert!(acquired.unwrap().unwrap().unwrap());
+        assert_eq!(rate_limiter.queue_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_keyed_rate_limiter_keys_are_independent() {
+        let rate_limiter = KeyedRateLimiter::new(1.0, 0.01, 1.0);
+
+        assert!(rate_limiter.aacquire_key("a", false).await.unwrap());
+        assert!(!rate_limiter.aacquire_key("a", false).await.unwrap());
+        assert!(rate_limiter.aacquire_key("b", false).await.unwrap());
+
+        assert_eq!(rate_limiter.num_keys(), 2);
+        assert_eq!(
+            rate_limiter.metrics("a"),
+            Some(KeyRateLimitMetrics {
+                acquired: 1,
+                throttled: 1,
+                globally_throttled: 0,
+            })
+        );
+        assert_eq!(rate_limiter.all_metrics()["b"].acquired, 1);
+        assert!(rate_limiter.metrics("c").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_keyed_rate_limiter_global_limit() {
+        let rate_limiter = KeyedRateLimiter::new(100.0, 0.01, 5.0).with_global_limit(1.0, 1.0);
+
+        assert!(rate_limiter.aacquire_key("a", false).await.unwrap());
+        // "b" has tokens, but the global bucket is empty
+        assert!(!rate_limiter.aacquire_key("b", false).await.unwrap());
+        assert_eq!(rate_limiter.metrics("b").unwrap().globally_throttled, 1);
+
+        // Refunds go back to the key's bucket and to the global bucket
+        rate_limiter.refund_key("a", 1.0).unwrap();
+        assert!(rate_limiter.aacquire_key("b", false).await.unwrap());
+        assert!(rate_limiter.aacquire_key_n("a", 2.0, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keyed_rate_limiter_refund_after_eviction() {
+        let rate_limiter = KeyedRateLimiter::new(100.0, 0.01, 1.0)
+            .with_global_limit(0.1, 1.0)
+            .with_idle_timeout(Duration::from_millis(20));
+
+        assert!(rate_limiter.aacquire_key("a", false).await.unwrap());
+        sleep(Duration::from_millis(30)).await;
+        assert_eq!(rate_limiter.evict_idle(), 1);
+
+        // The global bucket gets its share back although "a" is gone
+        rate_limiter.refund_key("a", 1.0).unwrap();
+        assert!(rate_limiter.aacquire_key("b", false).await.unwrap());
+        rate_limiter.refund_key("never-used", 1.0).unwrap();
+        assert!(rate_limiter.aacquire_key("c", false).await.unwrap());
+        assert_eq!(rate_limiter.num_keys(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_keyed_rate_limiter_counts_waits_once() {
+        let rate_limiter = KeyedRateLimiter::new(20.0, 0.005, 1.0);
+        let tenant: Arc<dyn BaseRateLimiter> = Arc::new(rate_limiter.for_key("tenant"));
+
+        for _ in 0..3 {
+            assert!(tenant.aacquire(true).await.unwrap());
+        }
+        let metrics = rate_limiter.metrics("tenant").unwrap();
+        assert_eq!(metrics.acquired, 3);
+        assert_eq!(metrics.throttled, 2);
+
+        // The sync path shares the same buckets
+        assert!(tenant.acquire(true).unwrap());
+        assert_eq!(rate_limiter.metrics("tenant").unwrap().acquired, 4);
+    }
+
+    #[tokio::test]
+    async fn test_keyed_rate_limiter_evicts_idle_buckets() {
+        let rate_limiter =
+            KeyedRateLimiter::new(100.0, 0.01, 1.0).with_idle_timeout(Duration::from_millis(50));
+        let slow =
+            KeyedRateLimiter::new(0.1, 0.01, 1.0).with_idle_timeout(Duration::from_millis(50));
+
+        rate_limiter.aacquire_key("a", false).await.unwrap();
+        slow.aacquire_key("a", false).await.unwrap();
+        sleep(Duration::from_millis(60)).await;
+
+        assert_eq!(rate_limiter.evict_idle(), 1);
+        assert_eq!(rate_limiter.num_keys(), 0);
+
+        // A depleted bucket is kept, so that its limit still applies
+        assert_eq!(slow.evict_idle(), 0);
+        assert!(!slow.aacquire_key("a", false).await.unwrap());
+
+        // Idle buckets are also evicted while acquiring for other keys
+        rate_limiter.aacquire_key("b", false).await.unwrap();
+        sleep(Duration::from_millis(60)).await;
+        rate_limiter.aacquire_key("c", false).await.unwrap();
+        assert!(rate_limiter.metrics("b").is_none());
+        assert_eq!(rate_limiter.num_keys(), 1);
+    }
+
+    #[test]
+    fn test_keyed_rate_limiter_config() {
+        let rate_limiter = KeyedRateLimiter::new(2.0, 0.1, 4.0)
+            .with_global_limit(10.0, 20.0)
+            .with_idle_timeout(Duration::from_secs(30));
+        let json = rate_limiter.to_config().to_json().unwrap();
+        let config = KeyedRateLimiterConfig::from_json(&json).unwrap();
+        assert_eq!(config.global_max_bucket_size, Some(20.0));
+        assert_eq!(config.idle_timeout, Duration::from_secs(30));
+        assert_eq!(
+            KeyedRateLimiter::from_config(config.clone()).to_config(),
+            config
+        );
+    }
+
     #[tokio::test]
     async fn test_serialization() {
         let rate_limiter = InMemoryRateLimiter::new(2.0, 0.1, 5.0);
@@ -585,4 +3081,241 @@ mod tests {
             deserialized_rate_limiter.max_bucket_size()
         );
     }
+
+    /// A fake Redis server running the token bucket script natively
+    struct FakeRedis {
+        address: String,
+        commands: Arc<Mutex<Vec<String>>>,
+        /// Loaded scripts by digest; clearing it simulates `SCRIPT FLUSH`
+        scripts: Arc<Mutex<HashMap<String, String>>>,
+    }
+
+    impl FakeRedis {
+        fn start(password: Option<&str>) -> Self {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let commands = Arc::new(Mutex::new(Vec::new()));
+            let scripts = Arc::new(Mutex::new(HashMap::new()));
+            let buckets = Arc::new(Mutex::new(HashMap::<String, (f64, f64)>::new()));
+            let password = password.map(str::to_string);
+
+            let (recorded, loaded) = (commands.clone(), scripts.clone());
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = stream.unwrap();
+                    let (commands, scripts, buckets, password) = (
+                        recorded.clone(),
+                        loaded.clone(),
+                        buckets.clone(),
+                        password.clone(),
+                    );
+                    std::thread::spawn(move || {
+                        Self::serve(stream, &commands, &scripts, &buckets, password.as_deref())
+                    });
+                }
+            });
+            Self {
+                address,
+                commands,
+                scripts,
+            }
+        }
+
+        fn serve(
+            stream: std::net::TcpStream,
+            commands: &Mutex<Vec<String>>,
+            scripts: &Mutex<HashMap<String, String>>,
+            buckets: &Mutex<HashMap<String, (f64, f64)>>,
+            password: Option<&str>,
+        ) {
+            use std::io::Write;
+
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = std::io::BufReader::new(stream);
+            let mut authenticated = password.is_none();
+            while let Ok(RespValue::Array(Some(items))) = RespValue::read(&mut reader) {
+                let args: Vec<String> = items
+                    .into_iter()
+                    .map(|item| match item {
+                        RespValue::Bulk(Some(data)) => String::from_utf8(data).unwrap(),
+                        other => panic!("unexpected argument {other:?}"),
+                    })
+                    .collect();
+                commands.lock().unwrap().push(args[0].clone());
+
+                let reply = match args[0].as_str() {
+                    "AUTH" if Some(args[1].as_str()) == password => {
+                        authenticated = true;
+                        "+OK\r\n".to_string()
+                    }
+                    "AUTH" => "-WRONGPASS invalid password\r\n".to_string(),
+                    _ if !authenticated => "-NOAUTH Authentication required.\r\n".to_string(),
+                    "SELECT" => "+OK\r\n".to_string(),
+                    "SCRIPT" if args[1] == "LOAD" => {
+                        let sha = crate::utils::hash_key_parts(&[&args[2]]);
+                        scripts.lock().unwrap().insert(sha.clone(), args[2].clone());
+                        format!("${}\r\n{sha}\r\n", sha.len())
+                    }
+                    "EVALSHA"
+                        if scripts
+                            .lock()
+                            .unwrap()
+                            .get(&args[1])
+                            .is_some_and(|script| script == TOKEN_BUCKET_SCRIPT) =>
+                    {
+                        let number = |i: usize| args[i].parse::<f64>().unwrap();
+                        let (rate, max_size, initial, op, amount) =
+                            (number(4), number(5), number(6), args[7].as_str(), number(8));
+                        let now = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs_f64();
+
+                        let mut buckets = buckets.lock().unwrap();
+                        let (mut available, last) =
+                            buckets.get(&args[3]).copied().unwrap_or((initial, now));
+                        let mut ok = 1;
+                        if op != "available" {
+                            available = (available + (now - last).max(0.0) * rate).min(max_size);
+                            if op == "consume" {
+                                if available >= amount {
+                                    available -= amount;
+                                } else {
+                                    ok = 0;
+                                }
+                            } else {
+                                available = (available + amount).min(max_size);
+                            }
+                            buckets.insert(args[3].clone(), (available, now));
+                        }
+                        let available = available.to_string();
+                        format!("*2\r\n:{ok}\r\n${}\r\n{available}\r\n", available.len())
+                    }
+                    "EVALSHA" => "-NOSCRIPT No matching script. Please use EVAL.\r\n".to_string(),
+                    _ => "-ERR unknown command\r\n".to_string(),
+                };
+                if writer.write_all(reply.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_resp_encoding() {
+        assert_eq!(
+            RespValue::encode_command(&[b"GET", b"key"]),
+            b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n"
+        );
+        let mut reader: &[u8] = b"*3\r\n:1\r\n$-1\r\n+OK\r\n";
+        assert_eq!(
+            RespValue::read(&mut reader).unwrap(),
+            RespValue::Array(Some(vec![
+                RespValue::Integer(1),
+                RespValue::Bulk(None),
+                RespValue::Simple("OK".to_string()),
+            ]))
+        );
+        assert!(RespValue::read(&mut &b"?oops\r\n"[..]).is_err());
+        assert!(RespValue::read(&mut "\u{e9}t\u{e9}\r\n".as_bytes()).is_err());
+        assert!(RespValue::read(&mut &b"$9223372036854775807\r\n"[..]).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_redis_backend_shares_quota_across_limiters() {
+        let server = FakeRedis::start(None);
+        // Each worker has its own connection to the server
+        let worker = || {
+            InMemoryRateLimiter::new(20.0, 0.01, 1.0)
+                .with_backend(Arc::new(RedisRateLimitBackend::new(&server.address)))
+                .with_key("provider")
+        };
+        let (first, second) = (worker(), worker());
+
+        assert!(first.acquire(false).unwrap());
+        assert!(!second.aacquire(false).await.unwrap());
+        first.refund(1.0).unwrap();
+        assert!(second.aacquire(false).await.unwrap());
+        assert!(second.available_tokens().await < 1.0);
+
+        // Other keys have their own bucket
+        let other = worker().with_key("other");
+        assert!(other.aacquire(false).await.unwrap());
+
+        let start = Instant::now();
+        for _ in 0..2 {
+            assert!(first.acquire(true).unwrap());
+            assert!(second.aacquire(true).await.unwrap());
+        }
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_redis_backend_auth_and_errors() {
+        let server = FakeRedis::start(Some("secret"));
+        let params = TokenBucketParams {
+            rate_per_second: 1.0,
+            max_size: 2.0,
+            initial: 2.0,
+        };
+
+        let anonymous = RedisRateLimitBackend::new(&server.address);
+        let error = anonymous.try_consume("key", params, 1.0).unwrap_err();
+        assert!(error.to_string().contains("NOAUTH"));
+
+        let backend = RedisRateLimitBackend::new(&server.address)
+            .with_password("secret")
+            .with_database(2)
+            .with_key_prefix("test:");
+        assert!(backend.try_consume("key", params, 2.0).unwrap());
+        assert!(!backend.try_consume("key", params, 1.0).unwrap());
+        backend.refund("key", params, 1.5).unwrap();
+        assert!(backend.available("key", params).unwrap() >= 1.5);
+        let commands = server.commands.lock().unwrap().clone();
+        assert_eq!(
+            commands,
+            [
+                "SCRIPT", "AUTH", "SELECT", "SCRIPT", "EVALSHA", "EVALSHA", "EVALSHA", "EVALSHA"
+            ]
+        );
+
+        // The script is loaded again once the server forgot it
+        server.scripts.lock().unwrap().clear();
+        assert!(backend.try_consume("key", params, 1.0).unwrap());
+        assert!(backend.try_consume("key", params, 0.1).unwrap());
+        let commands = server.commands.lock().unwrap().clone();
+        assert_eq!(&commands[8..], ["EVALSHA", "SCRIPT", "EVALSHA", "EVALSHA"]);
+
+        // Blocking acquisitions fail instead of waiting for an unreachable server
+        let address = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+        let rate_limiter = InMemoryRateLimiter::new(1.0, 0.01, 1.0).with_backend(Arc::new(
+            RedisRateLimitBackend::new(address).with_timeout(Duration::from_millis(200)),
+        ));
+        assert!(rate_limiter.acquire(true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_backend_shared_and_config() {
+        let backend = Arc::new(InMemoryRateLimitBackend::new());
+        let first = InMemoryRateLimiter::new(2.0, 0.1, 5.0).with_backend(backend.clone());
+        let second = InMemoryRateLimiter::new(2.0, 0.1, 5.0).with_backend(backend);
+        assert!(first.aacquire(false).await.unwrap());
+        assert!(!second.aacquire(false).await.unwrap());
+        assert_eq!(second.key(), "default");
+
+        // The backend is not part of the configuration
+        let config = second.with_key("shared").to_config();
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            serde_json::json!({
+                "requests_per_second": 2.0,
+                "max_bucket_size": 5.0,
+                "check_every_n_seconds": 0.1,
+            })
+        );
+        assert_eq!(InMemoryRateLimiter::from_config(config).key(), "default");
+    }
 }
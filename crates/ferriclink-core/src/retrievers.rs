@@ -9,8 +9,9 @@ use std::collections::HashMap;
 
 use crate::documents::Document;
 use crate::errors::Result;
+use crate::graph::{Graph, Node};
 use crate::impl_serializable;
-use crate::runnables::{Runnable, RunnableConfig};
+use crate::runnables::{Runnable, RunnableConfig, run_with_callbacks, to_callback_value};
 use crate::vectorstores::VectorStore;
 
 /// A retriever result containing documents and metadata
@@ -204,7 +205,43 @@ where
         input: String,
         config: Option<RunnableConfig>,
     ) -> Result<RetrieverResult> {
-        self.retriever.get_relevant_documents(&input, config).await
+        run_with_callbacks(
+            config,
+            self.name(),
+            "retriever",
+            serde_json::Value::String(input.clone()),
+            to_callback_value,
+            |run_config| async move {
+                run_config
+                    .run_cancellable(
+                        self.retriever
+                            .get_relevant_documents(&input, Some(run_config.clone())),
+                    )
+                    .await
+            },
+        )
+        .await
+    }
+
+    fn name(&self) -> String {
+        crate::utils::short_type_name::<R>()
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        self.retriever.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<serde_json::Value> {
+        self.retriever.output_schema()
+    }
+
+    fn get_graph(&self) -> Graph {
+        Graph::from_node(
+            Node::new(self.name())
+                .with_component_type("retriever")
+                .with_input_schema(self.input_schema())
+                .with_output_schema(self.output_schema()),
+        )
     }
 }
 
@@ -311,6 +348,9 @@ impl BaseRetriever for MultiRetriever {
         let mut results = Vec::new();
 
         for retriever in &self.retrievers {
+            if let Some(config) = &config {
+                config.check_cancelled()?;
+            }
             let result = retriever
                 .get_relevant_documents(query, config.clone())
                 .await?;
@@ -8,10 +8,19 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::callbacks::{
+    CallbackHandler, CallbackManager, CallbackRunManager, EventStreamCallbackHandler, RunId,
+    RunInfo, StreamEvent, StreamEventFilter,
+};
 use crate::errors::Result;
+use crate::graph::{Graph, Node};
 use crate::impl_serializable;
-use crate::utils::{colors, print_colored_text};
+use crate::rate_limiters::BaseRateLimiter;
+use crate::utils::short_type_name;
+
+pub use tokio_util::sync::CancellationToken;
 
 /// Configuration for running a Runnable
 #[derive(Clone, Serialize, Deserialize, Default)]
@@ -30,7 +39,28 @@ pub struct RunnableConfig {
     pub verbose: bool,
     /// Callback handlers for this run
     #[serde(skip)]
-    pub callbacks: Vec<Arc<dyn CallbackHandler>>,
+    pub callbacks: CallbackManager,
+    /// Maximum number of concurrent sub-runs (unbounded if `None`)
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /// ID of this run, if assigned by the caller
+    #[serde(default)]
+    pub run_id: Option<RunId>,
+    /// ID of the parent run, if this run is nested inside another
+    #[serde(default)]
+    pub parent_run_id: Option<RunId>,
+    /// Point in time after which the run should be aborted
+    #[serde(skip)]
+    pub deadline: Option<tokio::time::Instant>,
+    /// Token that signals cooperative cancellation of the run
+    #[serde(skip)]
+    pub cancellation_token: Option<CancellationToken>,
+    /// Values for configurable fields and alternatives, keyed by field ID
+    #[serde(default)]
+    pub configurable: HashMap<String, serde_json::Value>,
+    /// Whether `configurable` was already checked by an enclosing runnable
+    #[serde(skip)]
+    pub configurable_validated: bool,
 }
 
 impl RunnableConfig {
@@ -63,106 +93,218 @@ impl RunnableConfig {
         self
     }
 
-    /// Add a callback handler
+    /// Add a callback handler that also receives events from nested runs
     pub fn with_callback(mut self, callback: Arc<dyn CallbackHandler>) -> Self {
-        self.callbacks.push(callback);
+        self.callbacks.add_handler(callback);
         self
     }
-}
 
-impl PartialEq for RunnableConfig {
-    fn eq(&self, other: &Self) -> bool {
-        self.tags == other.tags
-            && self.metadata == other.metadata
-            && self.debug == other.debug
-            && self.verbose == other.verbose
-        // Skip callbacks comparison
+    /// Add a callback handler that only receives events for this run
+    pub fn with_local_callback(mut self, callback: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add_local_handler(callback);
+        self
     }
-}
 
-impl_serializable!(RunnableConfig, ["ferriclink", "runnables", "config"]);
+    /// Set the value of a configurable field or alternative
+    pub fn with_configurable(mut self, id: impl Into<String>, value: serde_json::Value) -> Self {
+        self.configurable.insert(id.into(), value);
+        self
+    }
 
-/// Trait for callback handlers that can be used during runnable execution
-#[async_trait]
-pub trait CallbackHandler: Send + Sync {
-    /// Called when a runnable starts
-    async fn on_start(&self, run_id: &str, input: &serde_json::Value) -> Result<()> {
-        let _ = (run_id, input);
+    /// Check the configurable values against a config schema
+    ///
+    /// Every key must be declared by the schema and every value must match the
+    /// declared type. The first runnable to check the values marks them as
+    /// checked, so the runnables it calls do not check them again against a
+    /// schema covering only part of the chain. `schema` is only computed when
+    /// needed.
+    pub fn validate_configurable(
+        &mut self,
+        schema: impl FnOnce() -> Option<serde_json::Value>,
+    ) -> Result<()> {
+        if self.configurable_validated || self.configurable.is_empty() {
+            return Ok(());
+        }
+
+        let schema = schema().unwrap_or_default();
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, value) in &self.configurable {
+            let Some(property) = properties.and_then(|p| p.get(key)) else {
+                let mut known: Vec<_> = properties
+                    .map(|p| p.keys().map(String::as_str).collect())
+                    .unwrap_or_default();
+                known.sort_unstable();
+                return Err(crate::errors::FerricLinkError::configuration(format!(
+                    "Unknown configurable key '{key}', expected one of: [{}]",
+                    known.join(", ")
+                )));
+            };
+            check_configurable_value(key, property, value)?;
+        }
+        self.configurable_validated = true;
         Ok(())
     }
 
-    /// Called when a runnable completes successfully
-    async fn on_success(&self, run_id: &str, output: &serde_json::Value) -> Result<()> {
-        let _ = (run_id, output);
-        Ok(())
+    /// Set the maximum number of concurrent sub-runs
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency);
+        self
     }
 
-    /// Called when a runnable fails
-    async fn on_error(&self, run_id: &str, error: &crate::errors::FerricLinkError) -> Result<()> {
-        let _ = (run_id, error);
-        Ok(())
+    /// Set the ID of this run
+    pub fn with_run_id(mut self, run_id: RunId) -> Self {
+        self.run_id = Some(run_id);
+        self
     }
 
-    /// Called when a runnable produces streaming output
-    async fn on_stream(&self, run_id: &str, chunk: &serde_json::Value) -> Result<()> {
-        let _ = (run_id, chunk);
-        Ok(())
+    /// Abort the run once the given duration has elapsed
+    ///
+    /// An existing earlier deadline is kept. A timeout too long to represent
+    /// sets no deadline.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        match tokio::time::Instant::now().checked_add(timeout) {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self,
+        }
     }
-}
 
-/// A simple console callback handler for debugging
-pub struct ConsoleCallbackHandler {
-    /// The color to use for text output (matching LangChain's color scheme)
-    pub color: Option<String>,
-}
+    /// Abort the run at the given deadline
+    ///
+    /// An existing earlier deadline is kept.
+    pub fn with_deadline(mut self, deadline: tokio::time::Instant) -> Self {
+        self.deadline = Some(match self.deadline {
+            Some(existing) => existing.min(deadline),
+            None => deadline,
+        });
+        self
+    }
 
-impl ConsoleCallbackHandler {
-    /// Create a new console callback handler
-    pub fn new() -> Self {
-        Self { color: None }
+    /// Attach a cancellation token to the run
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
     }
 
-    /// Create a new console callback handler with color
-    pub fn new_with_color(color: impl Into<String>) -> Self {
-        Self {
-            color: Some(color.into()),
-        }
+    /// Get the time left before the deadline, if one is set
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()))
     }
-}
 
-impl Default for ConsoleCallbackHandler {
-    fn default() -> Self {
-        Self::new()
+    /// Check if the run has been cancelled or has passed its deadline
+    pub fn is_cancelled(&self) -> bool {
+        self.check_cancelled().is_err()
     }
-}
 
-#[async_trait]
-impl CallbackHandler for ConsoleCallbackHandler {
-    async fn on_start(&self, run_id: &str, input: &serde_json::Value) -> Result<()> {
-        let message = format!("Starting run {run_id} with input: {input}");
-        print_colored_text(&message, self.color.as_deref());
+    /// Return an error if the run has been cancelled or has passed its deadline
+    ///
+    /// Long-running implementations (models, tools, retrievers) should call
+    /// this between steps to stop work early.
+    pub fn check_cancelled(&self) -> Result<()> {
+        if let Some(token) = &self.cancellation_token {
+            if token.is_cancelled() {
+                return Err(crate::errors::FerricLinkError::cancelled(
+                    "Run was cancelled",
+                ));
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(crate::errors::FerricLinkError::timeout(
+                    "Run exceeded its deadline",
+                ));
+            }
+        }
         Ok(())
     }
 
-    async fn on_success(&self, run_id: &str, output: &serde_json::Value) -> Result<()> {
-        let message = format!("Run {run_id} completed with output: {output}");
-        print_colored_text(&message, self.color.as_deref());
-        Ok(())
+    /// Drive a future to completion unless the run is cancelled or times out first
+    pub async fn run_cancellable<T, F>(&self, future: F) -> Result<T>
+    where
+        F: std::future::Future<Output = Result<T>>,
+    {
+        self.check_cancelled()?;
+
+        let cancelled = async {
+            match &self.cancellation_token {
+                Some(token) => token.cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+        let expired = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            result = future => result,
+            _ = cancelled => Err(crate::errors::FerricLinkError::cancelled("Run was cancelled")),
+            _ = expired => Err(crate::errors::FerricLinkError::timeout("Run exceeded its deadline")),
+        }
     }
 
-    async fn on_error(&self, run_id: &str, error: &crate::errors::FerricLinkError) -> Result<()> {
-        let message = format!("Run {run_id} failed with error: {error}");
-        print_colored_text(&message, Some(colors::RED));
-        Ok(())
+    /// Create the configuration for a nested run
+    ///
+    /// The child inherits tags, metadata, inheritable callbacks and the
+    /// deadline, gets a fresh run ID and records this run's ID as its parent.
+    /// Its cancellation token is a child of this run's token, so cancelling the
+    /// parent cancels the child but not the other way around.
+    pub fn child(&self) -> Self {
+        let mut child = self.clone();
+        child.parent_run_id = self.run_id.clone();
+        child.run_id = Some(RunId::new());
+        child.callbacks = self.callbacks.get_child();
+        child.cancellation_token = self
+            .cancellation_token
+            .as_ref()
+            .map(CancellationToken::child_token);
+        child
     }
 
-    async fn on_stream(&self, run_id: &str, chunk: &serde_json::Value) -> Result<()> {
-        let message = format!("Run {run_id} streamed: {chunk}");
-        print_colored_text(&message, self.color.as_deref());
-        Ok(())
+    /// Start a run described by this configuration and notify the callbacks
+    ///
+    /// Returns the run manager used to report the outcome, and the
+    /// configuration of the started run, whose [`child`](Self::child) should be
+    /// passed to nested runs.
+    pub async fn start_run(
+        &self,
+        name: impl Into<String>,
+        component_type: impl Into<String>,
+        input: serde_json::Value,
+    ) -> Result<(CallbackRunManager, RunnableConfig)> {
+        let run_id = self.run_id.clone().unwrap_or_default();
+        let mut run_info = RunInfo::new(run_id.clone(), name, component_type, input);
+        run_info.tags = self.tags.clone();
+        run_info.metadata = self.metadata.clone();
+        run_info.parent_run_id = self.parent_run_id.clone();
+
+        let run_manager = self.callbacks.start_run(run_info).await?;
+        let mut run_config = self.clone();
+        run_config.run_id = Some(run_id);
+        // The rate limiter wait belongs to this run only, not to nested runs
+        run_config.metadata.remove(RATE_LIMIT_WAIT_METADATA_KEY);
+        Ok((run_manager, run_config))
+    }
+}
+
+impl PartialEq for RunnableConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.tags == other.tags
+            && self.metadata == other.metadata
+            && self.debug == other.debug
+            && self.verbose == other.verbose
+            && self.max_concurrency == other.max_concurrency
+            && self.run_id == other.run_id
+            && self.parent_run_id == other.parent_run_id
+            && self.configurable == other.configurable
+        // Skip callbacks comparison
     }
 }
 
+impl_serializable!(RunnableConfig, ["ferriclink", "runnables", "config"]);
+
 /// The core Runnable trait that all FerricLink components implement
 #[async_trait]
 pub trait Runnable<Input, Output>: Send + Sync + 'static
@@ -204,6 +346,28 @@ where
         Ok(Box::pin(stream))
     }
 
+    /// Stream structured events from this runnable and every nested component
+    ///
+    /// The runnable is invoked with an additional inheritable
+    /// [`EventStreamCallbackHandler`], so any component reporting its runs
+    /// through callbacks emits events. Events are yielded as they happen; if
+    /// the invocation fails, its error is yielded after the last event.
+    fn stream_events(
+        &self,
+        input: Input,
+        config: Option<RunnableConfig>,
+        filter: StreamEventFilter,
+    ) -> Pin<Box<dyn futures::Stream<Item = Result<StreamEvent>> + Send + '_>> {
+        let (handler, receiver) = EventStreamCallbackHandler::new(filter);
+        let config = config.unwrap_or_default().with_callback(Arc::new(handler));
+        event_stream(self.invoke(input, Some(config)), receiver)
+    }
+
+    /// Get the name of this runnable, as reported to callbacks
+    fn name(&self) -> String {
+        short_type_name::<Self>()
+    }
+
     /// Get the input schema for this runnable
     fn input_schema(&self) -> Option<serde_json::Value> {
         None
@@ -218,8 +382,293 @@ where
     fn config_schema(&self) -> Option<serde_json::Value> {
         None
     }
+
+    /// Get a graph describing the structure of this runnable
+    fn get_graph(&self) -> Graph {
+        Graph::from_node(
+            Node::new(self.name())
+                .with_input_schema(self.input_schema())
+                .with_output_schema(self.output_schema()),
+        )
+    }
+}
+
+/// Metadata key under which a run reports how long it waited for its rate
+/// limiter, in milliseconds
+pub const RATE_LIMIT_WAIT_METADATA_KEY: &str = "rate_limit_wait_ms";
+
+/// Wait for a rate limiter before starting a run
+///
+/// Returns the configuration to start the run with: its metadata records the
+/// time spent waiting under [`RATE_LIMIT_WAIT_METADATA_KEY`], which
+/// [`RunnableConfig::start_run`] reports on the started run but not on nested
+/// runs. Waiting stops early when the run is cancelled or times out. Without a
+/// rate limiter the configuration is returned unchanged.
+pub async fn acquire_rate_limiter(
+    rate_limiter: Option<&dyn BaseRateLimiter>,
+    config: Option<RunnableConfig>,
+) -> Result<Option<RunnableConfig>> {
+    let Some(rate_limiter) = rate_limiter else {
+        return Ok(config);
+    };
+
+    let config = config.unwrap_or_default();
+    let start = tokio::time::Instant::now();
+    let acquired = config.run_cancellable(rate_limiter.aacquire(true)).await?;
+    if !acquired {
+        return Err(crate::errors::FerricLinkError::model_rate_limit(
+            "Rate limiter refused the request",
+        ));
+    }
+    let waited_ms = start.elapsed().as_secs_f64() * 1000.0;
+    Ok(Some(config.with_metadata(
+        RATE_LIMIT_WAIT_METADATA_KEY,
+        serde_json::json!(waited_ms),
+    )))
+}
+
+/// Run `func` as a traced run, reporting its start and outcome to the callbacks
+///
+/// `func` receives the configuration of the started run; nested runnables
+/// should be invoked with its [`RunnableConfig::child`]. `output_value` converts
+/// the output to JSON for the callbacks. Timeouts and cancellations are
+/// reported through `on_run_cancel`, other errors through `on_run_error`.
+pub async fn run_with_callbacks<Output, F, Fut>(
+    config: Option<RunnableConfig>,
+    name: impl Into<String>,
+    component_type: impl Into<String>,
+    input: serde_json::Value,
+    output_value: impl FnOnce(&Output) -> serde_json::Value,
+    func: F,
+) -> Result<Output>
+where
+    F: FnOnce(RunnableConfig) -> Fut,
+    Fut: std::future::Future<Output = Result<Output>>,
+{
+    let config = config.unwrap_or_default();
+    let (run_manager, run_config) = config.start_run(name, component_type, input).await?;
+
+    match func(run_config).await {
+        Ok(output) => {
+            run_manager.on_success(output_value(&output)).await?;
+            Ok(output)
+        }
+        Err(error) => {
+            run_manager.on_error(&error).await?;
+            Err(error)
+        }
+    }
+}
+
+/// Wrap a stream so that every chunk is reported to the run's callbacks
+///
+/// The run completes with the last chunk when the stream ends, or fails with
+/// the first error it yields.
+pub fn stream_with_callbacks<Output>(
+    inner: Pin<Box<dyn futures::Stream<Item = Result<Output>> + Send>>,
+    run_manager: CallbackRunManager,
+    chunk_value: fn(&Output) -> serde_json::Value,
+) -> Pin<Box<dyn futures::Stream<Item = Result<Output>> + Send>>
+where
+    Output: Send + 'static,
+{
+    use futures::StreamExt;
+
+    let state = Some((inner, run_manager, serde_json::Value::Null));
+    let stream = futures::stream::unfold(state, move |state| async move {
+        let (mut inner, run_manager, last) = state?;
+        match inner.next().await {
+            Some(Ok(chunk)) => {
+                let value = chunk_value(&chunk);
+                match run_manager.on_stream(&value).await {
+                    Ok(()) => Some((Ok(chunk), Some((inner, run_manager, value)))),
+                    Err(error) => Some((Err(error), None)),
+                }
+            }
+            Some(Err(error)) => match run_manager.on_error(&error).await {
+                Ok(()) => Some((Err(error), None)),
+                Err(callback_error) => Some((Err(callback_error), None)),
+            },
+            None => match run_manager.on_success(last).await {
+                Ok(()) => None,
+                Err(error) => Some((Err(error), None)),
+            },
+        }
+    });
+    Box::pin(stream)
+}
+
+/// Merge the config schemas of several runnables into one
+///
+/// Returns `None` if none of the runnables is configurable.
+pub fn merge_config_schemas(
+    schemas: impl IntoIterator<Item = Option<serde_json::Value>>,
+) -> Option<serde_json::Value> {
+    let mut properties = serde_json::Map::new();
+    for schema in schemas.into_iter().flatten() {
+        if let Some(serde_json::Value::Object(props)) = schema.get("properties") {
+            properties.extend(props.clone());
+        }
+    }
+
+    if properties.is_empty() {
+        None
+    } else {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": properties,
+            "additionalProperties": false,
+        }))
+    }
+}
+
+/// Check a configurable value against its property schema
+fn check_configurable_value(
+    key: &str,
+    property: &serde_json::Value,
+    value: &serde_json::Value,
+) -> Result<()> {
+    if let Some(allowed) = property.get("enum").and_then(|e| e.as_array()) {
+        if !allowed.contains(value) {
+            return Err(crate::errors::FerricLinkError::configuration(format!(
+                "Invalid value {value} for configurable key '{key}', expected one of: {}",
+                serde_json::Value::Array(allowed.clone())
+            )));
+        }
+    }
+
+    let expected = property
+        .get("type")
+        .and_then(|t| t.as_str())
+        .and_then(ConfigurableFieldType::from_schema_type);
+    if let Some(expected) = expected {
+        if !expected.matches(value) {
+            return Err(crate::errors::FerricLinkError::configuration(format!(
+                "Invalid value {value} for configurable key '{key}', expected {}",
+                expected.as_schema_type()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Build the graph of branches running side by side between input and output nodes
+fn fan_out_graph<'a>(
+    name: &str,
+    branches: impl IntoIterator<Item = (Option<&'a str>, Graph)>,
+) -> Graph {
+    let mut graph = Graph::new();
+    let input = graph.add_node(Node::new(format!("{name}Input")));
+    let output = graph.add_node(Node::new(format!("{name}Output")));
+    for (label, branch) in branches {
+        let (first, last) = graph.extend(branch);
+        graph.connect(std::slice::from_ref(&input), &first, label, false);
+        graph.connect(&last, std::slice::from_ref(&output), None, false);
+    }
+    graph
+}
+
+/// Drive a run to completion while yielding the events it emits
+fn event_stream<'a, Output>(
+    run: Pin<Box<dyn std::future::Future<Output = Result<Output>> + Send + 'a>>,
+    receiver: tokio::sync::mpsc::UnboundedReceiver<StreamEvent>,
+) -> Pin<Box<dyn futures::Stream<Item = Result<StreamEvent>> + Send + 'a>>
+where
+    Output: Send + 'a,
+{
+    let state = (Some(run), receiver, None);
+    let stream = futures::stream::unfold(state, |(mut run, mut receiver, mut error)| async move {
+        loop {
+            match run.as_mut() {
+                Some(future) => {
+                    tokio::select! {
+                        biased;
+                        Some(event) = receiver.recv() => {
+                            return Some((Ok(event), (run, receiver, error)));
+                        }
+                        result = future => {
+                            run = None;
+                            error = result.err();
+                        }
+                    }
+                }
+                None => {
+                    // Callbacks send synchronously, so every event emitted by
+                    // the run is already queued once it has completed
+                    if let Ok(event) = receiver.try_recv() {
+                        return Some((Ok(event), (run, receiver, error)));
+                    }
+                    return error.map(|error| (Err(error), (None, receiver, None)));
+                }
+            }
+        }
+    });
+    Box::pin(stream)
+}
+
+/// Serialize a value for callbacks, falling back to `null`
+pub(crate) fn to_callback_value<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// Report a value to callbacks as `null`
+fn null_callback_value<T>(_: &T) -> serde_json::Value {
+    serde_json::Value::Null
+}
+
+/// Conversions of a run's input and output into the values reported to
+/// callbacks and stream events
+///
+/// Generic runnables do not require their values to implement [`Serialize`],
+/// so they report `null` by default. Runnables over serializable values can
+/// report them with [`CallbackValues::serialized`], usually through their
+/// `with_serialized_values` builder.
+pub struct CallbackValues<Input, Output> {
+    input: fn(&Input) -> serde_json::Value,
+    output: fn(&Output) -> serde_json::Value,
+}
+
+impl<Input, Output> CallbackValues<Input, Output> {
+    /// Report the serialized input and output
+    pub fn serialized() -> Self
+    where
+        Input: Serialize,
+        Output: Serialize,
+    {
+        Self {
+            input: to_callback_value::<Input>,
+            output: to_callback_value::<Output>,
+        }
+    }
+
+    /// Get the value reported for an input
+    pub fn input(&self, input: &Input) -> serde_json::Value {
+        (self.input)(input)
+    }
+
+    /// Get the value reported for an output or a streamed chunk
+    pub fn output(&self, output: &Output) -> serde_json::Value {
+        (self.output)(output)
+    }
+}
+
+impl<Input, Output> Default for CallbackValues<Input, Output> {
+    fn default() -> Self {
+        Self {
+            input: null_callback_value::<Input>,
+            output: null_callback_value::<Output>,
+        }
+    }
+}
+
+impl<Input, Output> Clone for CallbackValues<Input, Output> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
+impl<Input, Output> Copy for CallbackValues<Input, Output> {}
+
 /// A runnable that wraps a simple function
 pub struct RunnableLambda<F, Input, Output>
 where
@@ -228,6 +677,8 @@ where
     Output: Send + Sync + 'static,
 {
     func: F,
+    name: Option<String>,
+    values: CallbackValues<Input, Output>,
     _phantom: std::marker::PhantomData<(Input, Output)>,
 }
 
@@ -241,9 +692,27 @@ where
     pub fn new(func: F) -> Self {
         Self {
             func,
+            name: None,
+            values: CallbackValues::default(),
             _phantom: std::marker::PhantomData,
         }
     }
+
+    /// Set the name reported to callbacks
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Report the serialized input and output to callbacks instead of `null`
+    pub fn with_serialized_values(mut self) -> Self
+    where
+        Input: Serialize,
+        Output: Serialize,
+    {
+        self.values = CallbackValues::serialized();
+        self
+    }
 }
 
 #[async_trait]
@@ -253,8 +722,20 @@ where
     Input: Send + Sync + 'static,
     Output: Send + Sync + 'static,
 {
-    async fn invoke(&self, input: Input, _config: Option<RunnableConfig>) -> Result<Output> {
-        (self.func)(input)
+    async fn invoke(&self, input: Input, config: Option<RunnableConfig>) -> Result<Output> {
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            self.values.input(&input),
+            |output| self.values.output(output),
+            |_| async move { (self.func)(input) },
+        )
+        .await
+    }
+
+    fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(short_type_name::<Self>)
     }
 }
 
@@ -267,6 +748,8 @@ where
     Output: Send + Sync + 'static,
 {
     func: F,
+    name: Option<String>,
+    values: CallbackValues<Input, Output>,
     _phantom: std::marker::PhantomData<(Input, Output)>,
 }
 
@@ -281,9 +764,27 @@ where
     pub fn new(func: F) -> Self {
         Self {
             func,
+            name: None,
+            values: CallbackValues::default(),
             _phantom: std::marker::PhantomData,
         }
     }
+
+    /// Set the name reported to callbacks
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Report the serialized input and output to callbacks instead of `null`
+    pub fn with_serialized_values(mut self) -> Self
+    where
+        Input: Serialize,
+        Output: Serialize,
+    {
+        self.values = CallbackValues::serialized();
+        self
+    }
 }
 
 #[async_trait]
@@ -294,8 +795,20 @@ where
     Input: Send + Sync + 'static,
     Output: Send + Sync + 'static,
 {
-    async fn invoke(&self, input: Input, _config: Option<RunnableConfig>) -> Result<Output> {
-        (self.func)(input).await
+    async fn invoke(&self, input: Input, config: Option<RunnableConfig>) -> Result<Output> {
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            self.values.input(&input),
+            |output| self.values.output(output),
+            |_| (self.func)(input),
+        )
+        .await
+    }
+
+    fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(short_type_name::<Self>)
     }
 }
 
@@ -303,6 +816,7 @@ where
 pub struct RunnableSequence<Input, Intermediate, Output> {
     first: Arc<dyn Runnable<Input, Intermediate>>,
     second: Arc<dyn Runnable<Intermediate, Output>>,
+    values: CallbackValues<Input, Output>,
 }
 
 impl<Input, Intermediate, Output> RunnableSequence<Input, Intermediate, Output>
@@ -316,7 +830,21 @@ where
         first: Arc<dyn Runnable<Input, Intermediate>>,
         second: Arc<dyn Runnable<Intermediate, Output>>,
     ) -> Self {
-        Self { first, second }
+        Self {
+            first,
+            second,
+            values: CallbackValues::default(),
+        }
+    }
+
+    /// Report the serialized input and output to callbacks instead of `null`
+    pub fn with_serialized_values(mut self) -> Self
+    where
+        Input: Serialize,
+        Output: Serialize,
+    {
+        self.values = CallbackValues::serialized();
+        self
     }
 }
 
@@ -328,15 +856,76 @@ where
     Intermediate: Send + Sync + 'static,
     Output: Send + Sync + 'static,
 {
-    async fn invoke(&self, input: Input, config: Option<RunnableConfig>) -> Result<Output> {
-        let intermediate = self.first.invoke(input, config.clone()).await?;
-        self.second.invoke(intermediate, config).await
+    async fn invoke(&self, input: Input, mut config: Option<RunnableConfig>) -> Result<Output> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            self.values.input(&input),
+            |output| self.values.output(output),
+            |run_config| async move {
+                let intermediate = self.first.invoke(input, Some(run_config.child())).await?;
+                run_config.check_cancelled()?;
+                self.second
+                    .invoke(intermediate, Some(run_config.child()))
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn stream(
+        &self,
+        input: Input,
+        config: Option<RunnableConfig>,
+    ) -> Result<Pin<Box<dyn futures::Stream<Item = Result<Output>> + Send>>> {
+        let config = config.unwrap_or_default();
+        let (run_manager, run_config) = config
+            .start_run(self.name(), "chain", self.values.input(&input))
+            .await?;
+
+        let inner = async {
+            let intermediate = self.first.invoke(input, Some(run_config.child())).await?;
+            run_config.check_cancelled()?;
+            self.second
+                .stream(intermediate, Some(run_config.child()))
+                .await
+        }
+        .await;
+
+        match inner {
+            Ok(inner) => Ok(stream_with_callbacks(
+                inner,
+                run_manager,
+                self.values.output,
+            )),
+            Err(error) => {
+                run_manager.on_error(&error).await?;
+                Err(error)
+            }
+        }
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        merge_config_schemas([self.first.config_schema(), self.second.config_schema()])
+    }
+
+    fn get_graph(&self) -> Graph {
+        let mut graph = self.first.get_graph();
+        let last = graph.last_nodes();
+        let (first, _) = graph.extend(self.second.get_graph());
+        graph.connect(&last, &first, None, false);
+        graph
     }
 }
 
 /// A runnable that runs multiple runnables in parallel
 pub struct RunnableParallel<Input, Output> {
     runnables: Vec<Arc<dyn Runnable<Input, Output>>>,
+    values: CallbackValues<Input, Vec<Output>>,
 }
 
 impl<Input, Output> RunnableParallel<Input, Output>
@@ -346,7 +935,20 @@ where
 {
     /// Create a new runnable parallel
     pub fn new(runnables: Vec<Arc<dyn Runnable<Input, Output>>>) -> Self {
-        Self { runnables }
+        Self {
+            runnables,
+            values: CallbackValues::default(),
+        }
+    }
+
+    /// Report the serialized input and outputs to callbacks instead of `null`
+    pub fn with_serialized_values(mut self) -> Self
+    where
+        Input: Serialize,
+        Output: Serialize,
+    {
+        self.values = CallbackValues::serialized();
+        self
     }
 
     /// Add a runnable to the parallel execution
@@ -361,100 +963,1931 @@ where
     Input: Send + Sync + 'static + Clone,
     Output: Send + Sync + 'static,
 {
-    async fn invoke(&self, input: Input, config: Option<RunnableConfig>) -> Result<Vec<Output>> {
-        let mut handles = Vec::new();
-
-        for runnable in &self.runnables {
-            let runnable = runnable.clone();
-            let input = input.clone();
-            let config = config.clone();
-
-            let handle = tokio::spawn(async move { runnable.invoke(input, config).await });
-
-            handles.push(handle);
+    async fn invoke(
+        &self,
+        input: Input,
+        mut config: Option<RunnableConfig>,
+    ) -> Result<Vec<Output>> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
         }
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            self.values.input(&input),
+            |outputs| self.values.output(outputs),
+            |run_config| async move {
+                // Runnables run within this task, so a failing runnable, a
+                // timeout or a cancellation drops the others instead of
+                // leaving them running
+                let runs = self
+                    .runnables
+                    .iter()
+                    .map(|runnable| runnable.invoke(input.clone(), Some(run_config.child())));
+                futures::future::try_join_all(runs).await
+            },
+        )
+        .await
+    }
 
-        let mut results = Vec::new();
-        for handle in handles {
-            let result = handle.await.map_err(|e| {
-                crate::errors::FerricLinkError::runtime(format!("Task failed: {e}"))
-            })?;
-            results.push(result?);
-        }
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        merge_config_schemas(self.runnables.iter().map(|r| r.config_schema()))
+    }
 
-        Ok(results)
+    fn get_graph(&self) -> Graph {
+        fan_out_graph(
+            &self.name(),
+            self.runnables.iter().map(|r| (None, r.get_graph())),
+        )
     }
 }
 
-/// Helper function to create a runnable from a simple function
-pub fn runnable<F, Input, Output>(func: F) -> Arc<dyn Runnable<Input, Output>>
-where
-    F: Fn(Input) -> Result<Output> + Send + Sync + 'static,
-    Input: Send + Sync + 'static,
-    Output: Send + Sync + 'static,
-{
-    Arc::new(RunnableLambda::new(func))
+/// A runnable that runs named branches concurrently and collects their outputs
+///
+/// Each branch may produce a different output type; outputs are serialized to
+/// JSON and returned as an object keyed by branch name. Concurrency is bounded
+/// by [`RunnableConfig::max_concurrency`], and every branch runs with a child
+/// configuration whose parent is the map's run.
+pub struct RunnableMap<Input> {
+    branches: Vec<(String, Arc<dyn Runnable<Input, serde_json::Value>>)>,
 }
 
-/// Helper function to create an async runnable from a function
-pub fn runnable_async<F, Input, Output, Fut>(func: F) -> Arc<dyn Runnable<Input, Output>>
+impl<Input> RunnableMap<Input>
 where
-    F: Fn(Input) -> Fut + Send + Sync + 'static,
-    Fut: std::future::Future<Output = Result<Output>> + Send + 'static,
-    Input: Send + Sync + 'static,
-    Output: Send + Sync + 'static,
+    Input: Send + Sync + 'static + Clone,
 {
-    Arc::new(RunnableAsync::new(func))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[tokio::test]
-    async fn test_runnable_lambda() {
-        let runnable = RunnableLambda::new(|x: i32| Ok(x * 2));
-        let result = runnable.invoke_simple(5).await.unwrap();
-        assert_eq!(result, 10);
+    /// Create a new runnable map with no branches
+    pub fn new() -> Self {
+        Self {
+            branches: Vec::new(),
+        }
     }
 
-    #[tokio::test]
-    async fn test_runnable_async() {
-        let runnable = RunnableAsync::new(|x: i32| async move {
-            tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
-            Ok(x * 3)
-        });
-        let result = runnable.invoke_simple(4).await.unwrap();
-        assert_eq!(result, 12);
+    /// Add a named branch whose output is serialized to JSON
+    pub fn with_branch<Output>(
+        mut self,
+        key: impl Into<String>,
+        runnable: Arc<dyn Runnable<Input, Output>>,
+    ) -> Self
+    where
+        Output: Serialize + Send + Sync + 'static,
+    {
+        self.add_branch(key, runnable);
+        self
     }
 
-    #[tokio::test]
-    async fn test_runnable_sequence() {
-        let first = Arc::new(RunnableLambda::new(|x: i32| Ok(x + 1)));
-        let second = Arc::new(RunnableLambda::new(|x: i32| Ok(x * 2)));
-        let sequence = RunnableSequence::new(first, second);
+    /// Add a named branch whose output is serialized to JSON
+    ///
+    /// Adding a branch with an existing key replaces the previous branch.
+    pub fn add_branch<Output>(
+        &mut self,
+        key: impl Into<String>,
+        runnable: Arc<dyn Runnable<Input, Output>>,
+    ) where
+        Output: Serialize + Send + Sync + 'static,
+    {
+        let key = key.into();
+        self.branches.retain(|(existing, _)| *existing != key);
+        self.branches
+            .push((key, Arc::new(SerializedOutput { inner: runnable })));
+    }
 
-        let result = sequence.invoke_simple(5).await.unwrap();
-        assert_eq!(result, 12); // (5 + 1) * 2
+    /// Get the branch keys in insertion order
+    pub fn keys(&self) -> Vec<&str> {
+        self.branches.iter().map(|(key, _)| key.as_str()).collect()
     }
 
-    #[tokio::test]
-    async fn test_runnable_parallel() {
-        let runnable1 = Arc::new(RunnableLambda::new(|x: i32| Ok(x * 2)));
-        let runnable2 = Arc::new(RunnableLambda::new(|x: i32| Ok(x * 3)));
-        let parallel = RunnableParallel::new(vec![runnable1, runnable2]);
+    /// Get the number of branches
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
 
-        let results = parallel.invoke_simple(5).await.unwrap();
-        assert_eq!(results.len(), 2);
-        assert!(results.contains(&10)); // 5 * 2
-        assert!(results.contains(&15)); // 5 * 3
+    /// Check if the map has no branches
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
     }
 
-    #[tokio::test]
-    async fn test_runnable_batch() {
-        let runnable = RunnableLambda::new(|x: i32| Ok(x * 2));
-        let results = runnable.batch(vec![1, 2, 3], None).await.unwrap();
-        assert_eq!(results, vec![2, 4, 6]);
+    /// Invoke the map and deserialize the keyed outputs into a typed struct
+    pub async fn invoke_typed<T>(&self, input: Input, config: Option<RunnableConfig>) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let output = self.invoke(input, config).await?;
+        Ok(serde_json::from_value(output)?)
+    }
+}
+
+impl<Input> Default for RunnableMap<Input>
+where
+    Input: Send + Sync + 'static + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<Input> Runnable<Input, serde_json::Value> for RunnableMap<Input>
+where
+    Input: Send + Sync + 'static + Clone,
+{
+    async fn invoke(
+        &self,
+        input: Input,
+        mut config: Option<RunnableConfig>,
+    ) -> Result<serde_json::Value> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            serde_json::Value::Null,
+            serde_json::Value::clone,
+            |run_config| async move {
+                use futures::{StreamExt, TryStreamExt};
+
+                // Branches run within this task, so a failing branch, a timeout
+                // or a cancellation drops the other branches instead of
+                // leaving them running
+                let limit = run_config
+                    .max_concurrency
+                    .unwrap_or(self.branches.len())
+                    .max(1);
+                let mut runs = Vec::new();
+                for (index, (key, runnable)) in self.branches.iter().enumerate() {
+                    let input = input.clone();
+                    let child_config = run_config.child().with_tag(format!("map:key:{key}"));
+                    runs.push(async move {
+                        let output = runnable.invoke(input, Some(child_config)).await?;
+                        Ok::<_, crate::errors::FerricLinkError>((index, output))
+                    });
+                }
+                // Outputs are collected as they complete, so the first error
+                // is returned without waiting for earlier branches
+                let mut outputs: Vec<(usize, serde_json::Value)> = futures::stream::iter(runs)
+                    .buffer_unordered(limit)
+                    .try_collect()
+                    .await?;
+                outputs.sort_by_key(|(index, _)| *index);
+
+                let results: serde_json::Map<String, serde_json::Value> = outputs
+                    .into_iter()
+                    .map(|(index, output)| (self.branches[index].0.clone(), output))
+                    .collect();
+
+                Ok(serde_json::Value::Object(results))
+            },
+        )
+        .await
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        merge_config_schemas(self.branches.iter().map(|(_, r)| r.config_schema()))
+    }
+
+    fn get_graph(&self) -> Graph {
+        fan_out_graph(
+            &self.name(),
+            self.branches
+                .iter()
+                .map(|(key, r)| (Some(key.as_str()), r.get_graph())),
+        )
+    }
+}
+
+/// Adapter that erases a runnable's output type by serializing it to JSON
+struct SerializedOutput<Input, Output> {
+    inner: Arc<dyn Runnable<Input, Output>>,
+}
+
+#[async_trait]
+impl<Input, Output> Runnable<Input, serde_json::Value> for SerializedOutput<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Serialize + Send + Sync + 'static,
+{
+    async fn invoke(
+        &self,
+        input: Input,
+        config: Option<RunnableConfig>,
+    ) -> Result<serde_json::Value> {
+        let output = self.inner.invoke(input, config).await?;
+        Ok(serde_json::to_value(output)?)
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        self.inner.config_schema()
+    }
+
+    fn get_graph(&self) -> Graph {
+        self.inner.get_graph()
+    }
+}
+
+/// A condition/runnable pair used by [`RunnableBranch`]
+type Branch<Input, Output> = (
+    Arc<dyn Runnable<Input, bool>>,
+    Arc<dyn Runnable<Input, Output>>,
+);
+
+/// A runnable that routes its input to the first branch whose condition holds
+///
+/// Conditions are evaluated in order; if none of them returns `true`, the
+/// default runnable is invoked instead.
+pub struct RunnableBranch<Input, Output> {
+    branches: Vec<Branch<Input, Output>>,
+    default: Arc<dyn Runnable<Input, Output>>,
+}
+
+impl<Input, Output> RunnableBranch<Input, Output>
+where
+    Input: Send + Sync + 'static + Clone,
+    Output: Send + Sync + 'static,
+{
+    /// Create a new runnable branch with the given default runnable
+    pub fn new(default: Arc<dyn Runnable<Input, Output>>) -> Self {
+        Self {
+            branches: Vec::new(),
+            default,
+        }
+    }
+
+    /// Add a condition/runnable pair, checked after all previously added branches
+    pub fn with_branch(
+        mut self,
+        condition: Arc<dyn Runnable<Input, bool>>,
+        runnable: Arc<dyn Runnable<Input, Output>>,
+    ) -> Self {
+        self.branches.push((condition, runnable));
+        self
+    }
+
+    /// Add a condition/runnable pair to the branch
+    pub fn add_branch(
+        &mut self,
+        condition: Arc<dyn Runnable<Input, bool>>,
+        runnable: Arc<dyn Runnable<Input, Output>>,
+    ) {
+        self.branches.push((condition, runnable));
+    }
+
+    /// Get the number of conditional branches (excluding the default)
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// Check if the branch has no conditional branches
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+}
+
+#[async_trait]
+impl<Input, Output> Runnable<Input, Output> for RunnableBranch<Input, Output>
+where
+    Input: Send + Sync + 'static + Clone,
+    Output: Send + Sync + 'static,
+{
+    async fn invoke(&self, input: Input, mut config: Option<RunnableConfig>) -> Result<Output> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            serde_json::Value::Null,
+            |_| serde_json::Value::Null,
+            |run_config| async move {
+                for (condition, runnable) in &self.branches {
+                    if condition
+                        .invoke(input.clone(), Some(run_config.child()))
+                        .await?
+                    {
+                        return runnable.invoke(input, Some(run_config.child())).await;
+                    }
+                }
+                self.default.invoke(input, Some(run_config.child())).await
+            },
+        )
+        .await
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        merge_config_schemas(
+            self.branches
+                .iter()
+                .flat_map(|(condition, runnable)| {
+                    [condition.config_schema(), runnable.config_schema()]
+                })
+                .chain([self.default.config_schema()]),
+        )
+    }
+
+    fn get_graph(&self) -> Graph {
+        let name = self.name();
+        let mut graph = Graph::new();
+        let start = graph.add_node(Node::new(name.clone()).with_input_schema(self.input_schema()));
+        let end = graph.add_node(Node::new(format!("{name}Output")));
+
+        let branches = self
+            .branches
+            .iter()
+            .map(|(condition, runnable)| (condition.name(), runnable))
+            .chain([("default".to_string(), &self.default)]);
+        for (label, runnable) in branches {
+            let (first, last) = graph.extend(runnable.get_graph());
+            graph.connect(std::slice::from_ref(&start), &first, Some(&label), true);
+            graph.connect(&last, std::slice::from_ref(&end), None, false);
+        }
+        graph
+    }
+}
+
+/// A runnable that passes its input through unchanged
+///
+/// Combine with [`RunnablePassthrough::assign`] to extend a JSON object with
+/// computed keys while keeping the original ones.
+pub struct RunnablePassthrough<T> {
+    values: CallbackValues<T, T>,
+}
+
+impl<T> RunnablePassthrough<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Create a new passthrough runnable
+    pub fn new() -> Self {
+        Self {
+            values: CallbackValues::default(),
+        }
+    }
+
+    /// Report the serialized value to callbacks instead of `null`
+    pub fn with_serialized_values(mut self) -> Self
+    where
+        T: Serialize,
+    {
+        self.values = CallbackValues::serialized();
+        self
+    }
+}
+
+impl RunnablePassthrough<serde_json::Value> {
+    /// Create a [`RunnableAssign`] that adds computed keys to a JSON object input
+    pub fn assign() -> RunnableAssign {
+        RunnableAssign::new()
+    }
+}
+
+impl<T> Default for RunnablePassthrough<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<T> Runnable<T, T> for RunnablePassthrough<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn invoke(&self, input: T, config: Option<RunnableConfig>) -> Result<T> {
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            self.values.input(&input),
+            |output| self.values.output(output),
+            |_| async move { Ok(input) },
+        )
+        .await
+    }
+}
+
+/// A runnable that adds computed keys to a JSON object
+///
+/// Every mapper receives the full input object and its output is stored under
+/// its key. Mappers run concurrently and may overwrite existing keys.
+pub struct RunnableAssign {
+    mappers: Vec<(
+        String,
+        Arc<dyn Runnable<serde_json::Value, serde_json::Value>>,
+    )>,
+}
+
+impl RunnableAssign {
+    /// Create a new assign runnable with no mappers
+    pub fn new() -> Self {
+        Self {
+            mappers: Vec::new(),
+        }
+    }
+
+    /// Add a key computed by the given runnable
+    pub fn with(
+        mut self,
+        key: impl Into<String>,
+        runnable: Arc<dyn Runnable<serde_json::Value, serde_json::Value>>,
+    ) -> Self {
+        self.add(key, runnable);
+        self
+    }
+
+    /// Add a key computed by the given runnable
+    pub fn add(
+        &mut self,
+        key: impl Into<String>,
+        runnable: Arc<dyn Runnable<serde_json::Value, serde_json::Value>>,
+    ) {
+        let key = key.into();
+        self.mappers.retain(|(existing, _)| *existing != key);
+        self.mappers.push((key, runnable));
+    }
+
+    /// Get the keys that will be assigned
+    pub fn keys(&self) -> Vec<&str> {
+        self.mappers.iter().map(|(key, _)| key.as_str()).collect()
+    }
+}
+
+impl Default for RunnableAssign {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Runnable<serde_json::Value, serde_json::Value> for RunnableAssign {
+    async fn invoke(
+        &self,
+        input: serde_json::Value,
+        mut config: Option<RunnableConfig>,
+    ) -> Result<serde_json::Value> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            input.clone(),
+            serde_json::Value::clone,
+            |run_config| async move {
+                let serde_json::Value::Object(mut map) = input else {
+                    return Err(crate::errors::FerricLinkError::validation(format!(
+                        "RunnableAssign expects a JSON object input, got: {input}"
+                    )));
+                };
+
+                let input = serde_json::Value::Object(map.clone());
+                let outputs =
+                    futures::future::try_join_all(self.mappers.iter().map(|(_, runnable)| {
+                        runnable.invoke(input.clone(), Some(run_config.child()))
+                    }))
+                    .await?;
+
+                for ((key, _), value) in self.mappers.iter().zip(outputs) {
+                    map.insert(key.clone(), value);
+                }
+
+                Ok(serde_json::Value::Object(map))
+            },
+        )
+        .await
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        merge_config_schemas(self.mappers.iter().map(|(_, r)| r.config_schema()))
+    }
+
+    fn get_graph(&self) -> Graph {
+        let name = self.name();
+        let mut graph = fan_out_graph(
+            &name,
+            self.mappers
+                .iter()
+                .map(|(key, r)| (Some(key.as_str()), r.get_graph())),
+        );
+        graph.add_edge(
+            crate::graph::Edge::new(format!("{name}Input"), format!("{name}Output"))
+                .with_label("passthrough"),
+        );
+        graph
+    }
+}
+
+/// A runnable that selects keys from a JSON object
+///
+/// With a single key the value itself is returned (`null` if missing); with
+/// several keys a new object containing only the present keys is returned.
+pub struct RunnablePick {
+    keys: Vec<String>,
+    single: bool,
+}
+
+impl RunnablePick {
+    /// Create a pick runnable that returns the value of a single key
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            keys: vec![key.into()],
+            single: true,
+        }
+    }
+
+    /// Create a pick runnable that returns an object with the given keys
+    pub fn new_many<I, S>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+            single: false,
+        }
+    }
+
+    /// Get the keys picked by this runnable
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
+
+#[async_trait]
+impl Runnable<serde_json::Value, serde_json::Value> for RunnablePick {
+    async fn invoke(
+        &self,
+        input: serde_json::Value,
+        config: Option<RunnableConfig>,
+    ) -> Result<serde_json::Value> {
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            input.clone(),
+            serde_json::Value::clone,
+            |_| async move {
+                let serde_json::Value::Object(mut map) = input else {
+                    return Err(crate::errors::FerricLinkError::validation(format!(
+                        "RunnablePick expects a JSON object input, got: {input}"
+                    )));
+                };
+
+                if self.single {
+                    return Ok(map.remove(&self.keys[0]).unwrap_or(serde_json::Value::Null));
+                }
+
+                let picked = self
+                    .keys
+                    .iter()
+                    .filter_map(|key| map.remove(key).map(|value| (key.clone(), value)))
+                    .collect();
+                Ok(serde_json::Value::Object(picked))
+            },
+        )
+        .await
+    }
+}
+
+/// A runnable that aborts its inner runnable after a timeout
+///
+/// The inner runnable receives a child configuration carrying the deadline and
+/// a cancellation token that is cancelled when the timeout fires, so
+/// cooperative implementations can stop their own work. Callbacks are notified
+/// through [`CallbackHandler::on_run_cancel`].
+pub struct RunnableTimeout<Input, Output> {
+    inner: Arc<dyn Runnable<Input, Output>>,
+    timeout: Duration,
+    values: CallbackValues<Input, Output>,
+}
+
+impl<Input, Output> RunnableTimeout<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    /// Create a new runnable timeout
+    pub fn new(inner: Arc<dyn Runnable<Input, Output>>, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            values: CallbackValues::default(),
+        }
+    }
+
+    /// Report the serialized input and output to callbacks instead of `null`
+    pub fn with_serialized_values(mut self) -> Self
+    where
+        Input: Serialize,
+        Output: Serialize,
+    {
+        self.values = CallbackValues::serialized();
+        self
+    }
+
+    /// Get the timeout applied to the inner runnable
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+#[async_trait]
+impl<Input, Output> Runnable<Input, Output> for RunnableTimeout<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    async fn invoke(&self, input: Input, mut config: Option<RunnableConfig>) -> Result<Output> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            self.values.input(&input),
+            |output| self.values.output(output),
+            |run_config| async move {
+                let token = run_config
+                    .cancellation_token
+                    .as_ref()
+                    .map(CancellationToken::child_token)
+                    .unwrap_or_default();
+                let run_config = run_config
+                    .with_timeout(self.timeout)
+                    .with_cancellation_token(token.clone());
+
+                let result = run_config
+                    .run_cancellable(self.inner.invoke(input, Some(run_config.child())))
+                    .await;
+                if result.is_err() {
+                    token.cancel();
+                }
+                result
+            },
+        )
+        .await
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        self.inner.config_schema()
+    }
+
+    fn get_graph(&self) -> Graph {
+        self.inner.get_graph()
+    }
+}
+
+/// Helper function to wrap a runnable with a timeout
+pub fn with_timeout<Input, Output>(
+    runnable: Arc<dyn Runnable<Input, Output>>,
+    timeout: Duration,
+) -> Arc<dyn Runnable<Input, Output>>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    Arc::new(RunnableTimeout::new(runnable, timeout))
+}
+
+/// A runnable that waits for a rate limiter before every call of its inner
+/// runnable
+///
+/// Each element of a batch and each stream acquires separately, so a single
+/// limiter shared between runnables and models protects a common provider
+/// quota. The wait is reported under [`RATE_LIMIT_WAIT_METADATA_KEY`] in the
+/// metadata of the run started for the call.
+pub struct RunnableRateLimited<Input, Output> {
+    inner: Arc<dyn Runnable<Input, Output>>,
+    rate_limiter: Arc<dyn BaseRateLimiter>,
+    values: CallbackValues<Input, Output>,
+}
+
+impl<Input, Output> RunnableRateLimited<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    /// Create a new rate limited runnable
+    pub fn new(
+        inner: Arc<dyn Runnable<Input, Output>>,
+        rate_limiter: Arc<dyn BaseRateLimiter>,
+    ) -> Self {
+        Self {
+            inner,
+            rate_limiter,
+            values: CallbackValues::default(),
+        }
+    }
+
+    /// Report the serialized input and output to callbacks instead of `null`
+    pub fn with_serialized_values(mut self) -> Self
+    where
+        Input: Serialize,
+        Output: Serialize,
+    {
+        self.values = CallbackValues::serialized();
+        self
+    }
+
+    /// Get the rate limiter acquired before each call
+    pub fn rate_limiter(&self) -> &Arc<dyn BaseRateLimiter> {
+        &self.rate_limiter
+    }
+}
+
+#[async_trait]
+impl<Input, Output> Runnable<Input, Output> for RunnableRateLimited<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    async fn invoke(&self, input: Input, mut config: Option<RunnableConfig>) -> Result<Output> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        let config = acquire_rate_limiter(Some(self.rate_limiter.as_ref()), config).await?;
+        run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            self.values.input(&input),
+            |output| self.values.output(output),
+            |run_config| async move { self.inner.invoke(input, Some(run_config.child())).await },
+        )
+        .await
+    }
+
+    async fn stream(
+        &self,
+        input: Input,
+        config: Option<RunnableConfig>,
+    ) -> Result<Pin<Box<dyn futures::Stream<Item = Result<Output>> + Send>>> {
+        let config = acquire_rate_limiter(Some(self.rate_limiter.as_ref()), config)
+            .await?
+            .unwrap_or_default();
+        let (run_manager, run_config) = config
+            .start_run(self.name(), "chain", self.values.input(&input))
+            .await?;
+
+        match self.inner.stream(input, Some(run_config.child())).await {
+            Ok(inner) => Ok(stream_with_callbacks(
+                inner,
+                run_manager,
+                self.values.output,
+            )),
+            Err(error) => {
+                run_manager.on_error(&error).await?;
+                Err(error)
+            }
+        }
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        self.inner.config_schema()
+    }
+
+    fn get_graph(&self) -> Graph {
+        self.inner.get_graph()
+    }
+}
+
+/// Helper function to wrap a runnable with a rate limiter
+pub fn with_rate_limiter<Input, Output>(
+    runnable: Arc<dyn Runnable<Input, Output>>,
+    rate_limiter: Arc<dyn BaseRateLimiter>,
+) -> Arc<dyn Runnable<Input, Output>>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    Arc::new(RunnableRateLimited::new(runnable, rate_limiter))
+}
+
+/// The JSON type of a configurable field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigurableFieldType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl ConfigurableFieldType {
+    /// Infer the type of a value, or `None` for `null`
+    pub fn infer(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::Bool(_) => Some(Self::Boolean),
+            serde_json::Value::Number(n) if n.is_f64() => Some(Self::Number),
+            serde_json::Value::Number(_) => Some(Self::Integer),
+            serde_json::Value::String(_) => Some(Self::String),
+            serde_json::Value::Array(_) => Some(Self::Array),
+            serde_json::Value::Object(_) => Some(Self::Object),
+        }
+    }
+
+    /// Check whether a value is of this type
+    pub fn matches(&self, value: &serde_json::Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Number => value.is_number(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Boolean => value.is_boolean(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+        }
+    }
+
+    /// Get the JSON Schema name of this type
+    pub fn as_schema_type(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Integer => "integer",
+            Self::Boolean => "boolean",
+            Self::Array => "array",
+            Self::Object => "object",
+        }
+    }
+
+    /// Parse a JSON Schema type name
+    pub fn from_schema_type(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(Self::String),
+            "number" => Some(Self::Number),
+            "integer" => Some(Self::Integer),
+            "boolean" => Some(Self::Boolean),
+            "array" => Some(Self::Array),
+            "object" => Some(Self::Object),
+            _ => None,
+        }
+    }
+}
+
+/// A named knob of a runnable, populated from [`RunnableConfig::configurable`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigurableField {
+    /// Key of the field in `RunnableConfig::configurable`
+    pub id: String,
+    /// Human-readable name of the field
+    pub name: Option<String>,
+    /// Description of the field
+    pub description: Option<String>,
+    /// Value used when the field is not configured
+    pub default: serde_json::Value,
+    /// Expected type of the value, inferred from the default if not set
+    pub field_type: Option<ConfigurableFieldType>,
+}
+
+impl ConfigurableField {
+    /// Create a new configurable field
+    pub fn new(id: impl Into<String>, default: serde_json::Value) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+            description: None,
+            field_type: ConfigurableFieldType::infer(&default),
+            default,
+        }
+    }
+
+    /// Set the human-readable name of the field
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the description of the field
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the expected type of the value
+    pub fn with_type(mut self, field_type: ConfigurableFieldType) -> Self {
+        self.field_type = Some(field_type);
+        self
+    }
+
+    /// Get the JSON Schema of the field
+    pub fn schema(&self) -> serde_json::Value {
+        let mut schema = serde_json::Map::new();
+        if let Some(name) = &self.name {
+            schema.insert("title".to_string(), name.clone().into());
+        }
+        if let Some(description) = &self.description {
+            schema.insert("description".to_string(), description.clone().into());
+        }
+        if let Some(field_type) = self.field_type {
+            schema.insert("type".to_string(), field_type.as_schema_type().into());
+        }
+        schema.insert("default".to_string(), self.default.clone());
+        serde_json::Value::Object(schema)
+    }
+}
+
+impl_serializable!(
+    ConfigurableField,
+    ["ferriclink", "runnables", "configurable_field"]
+);
+
+/// Factory building a runnable from the values of its configurable fields
+pub type RunnableFactory<Input, Output> = Arc<
+    dyn Fn(&HashMap<String, serde_json::Value>) -> Result<Arc<dyn Runnable<Input, Output>>>
+        + Send
+        + Sync,
+>;
+
+/// Remove the given keys from the configurable values of a configuration
+fn strip_configurable<'a>(
+    config: Option<RunnableConfig>,
+    keys: impl IntoIterator<Item = &'a str>,
+) -> Option<RunnableConfig> {
+    config.map(|mut config| {
+        for key in keys {
+            config.configurable.remove(key);
+        }
+        config
+    })
+}
+
+/// A runnable whose fields can be changed per request
+///
+/// The runnable is rebuilt by the factory whenever one of its fields is set in
+/// [`RunnableConfig::configurable`]; otherwise a default instance is reused.
+pub struct RunnableConfigurableFields<Input, Output> {
+    fields: Vec<ConfigurableField>,
+    factory: RunnableFactory<Input, Output>,
+    default: Arc<dyn Runnable<Input, Output>>,
+}
+
+impl<Input, Output> RunnableConfigurableFields<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    /// Create a new configurable runnable, building its default instance
+    pub fn new<F>(fields: Vec<ConfigurableField>, factory: F) -> Result<Self>
+    where
+        F: Fn(&HashMap<String, serde_json::Value>) -> Result<Arc<dyn Runnable<Input, Output>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let defaults = fields
+            .iter()
+            .map(|field| (field.id.clone(), field.default.clone()))
+            .collect();
+        let default = factory(&defaults)?;
+        Ok(Self {
+            fields,
+            factory: Arc::new(factory),
+            default,
+        })
+    }
+
+    /// Get the configurable fields
+    pub fn fields(&self) -> &[ConfigurableField] {
+        &self.fields
+    }
+
+    /// Get the runnable to use for a configuration
+    pub fn resolve(
+        &self,
+        config: Option<&RunnableConfig>,
+    ) -> Result<Arc<dyn Runnable<Input, Output>>> {
+        let configurable = config.map(|config| &config.configurable);
+        let is_configured = |field: &ConfigurableField| {
+            configurable.is_some_and(|values| values.contains_key(&field.id))
+        };
+        if !self.fields.iter().any(is_configured) {
+            return Ok(self.default.clone());
+        }
+
+        let mut values = HashMap::new();
+        for field in &self.fields {
+            let value = match configurable.and_then(|values| values.get(&field.id)) {
+                Some(value) => {
+                    check_configurable_value(&field.id, &field.schema(), value)?;
+                    value.clone()
+                }
+                None => field.default.clone(),
+            };
+            values.insert(field.id.clone(), value);
+        }
+        (self.factory)(&values)
+    }
+
+    fn own_schema(&self) -> serde_json::Value {
+        let properties: serde_json::Map<_, _> = self
+            .fields
+            .iter()
+            .map(|field| (field.id.clone(), field.schema()))
+            .collect();
+        serde_json::json!({ "properties": properties })
+    }
+}
+
+#[async_trait]
+impl<Input, Output> Runnable<Input, Output> for RunnableConfigurableFields<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    async fn invoke(&self, input: Input, mut config: Option<RunnableConfig>) -> Result<Output> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        let runnable = self.resolve(config.as_ref())?;
+        let config = strip_configurable(config, self.fields.iter().map(|f| f.id.as_str()));
+        runnable.invoke(input, config).await
+    }
+
+    async fn stream(
+        &self,
+        input: Input,
+        mut config: Option<RunnableConfig>,
+    ) -> Result<Pin<Box<dyn futures::Stream<Item = Result<Output>> + Send>>> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        let runnable = self.resolve(config.as_ref())?;
+        let config = strip_configurable(config, self.fields.iter().map(|f| f.id.as_str()));
+        runnable.stream(input, config).await
+    }
+
+    fn name(&self) -> String {
+        self.default.name()
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        self.default.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<serde_json::Value> {
+        self.default.output_schema()
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        merge_config_schemas([Some(self.own_schema()), self.default.config_schema()])
+    }
+
+    fn get_graph(&self) -> Graph {
+        self.default.get_graph()
+    }
+}
+
+/// A runnable that switches between alternatives per request
+///
+/// The alternative is selected by the value of its ID in
+/// [`RunnableConfig::configurable`], falling back to the default alternative.
+pub struct RunnableConfigurableAlternatives<Input, Output> {
+    id: String,
+    description: Option<String>,
+    default_key: String,
+    alternatives: Vec<(String, Arc<dyn Runnable<Input, Output>>)>,
+}
+
+impl<Input, Output> RunnableConfigurableAlternatives<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    /// Create a new set of alternatives with a default alternative
+    pub fn new(
+        id: impl Into<String>,
+        default_key: impl Into<String>,
+        default: Arc<dyn Runnable<Input, Output>>,
+    ) -> Self {
+        let default_key = default_key.into();
+        Self {
+            id: id.into(),
+            description: None,
+            alternatives: vec![(default_key.clone(), default)],
+            default_key,
+        }
+    }
+
+    /// Add an alternative, replacing any alternative with the same key
+    pub fn with_alternative(
+        mut self,
+        key: impl Into<String>,
+        runnable: Arc<dyn Runnable<Input, Output>>,
+    ) -> Self {
+        self.add_alternative(key, runnable);
+        self
+    }
+
+    /// Add an alternative, replacing any alternative with the same key
+    pub fn add_alternative(
+        &mut self,
+        key: impl Into<String>,
+        runnable: Arc<dyn Runnable<Input, Output>>,
+    ) {
+        let key = key.into();
+        match self.alternatives.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = runnable,
+            None => self.alternatives.push((key, runnable)),
+        }
+    }
+
+    /// Set the description of the selector
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Get the ID of the selector in `RunnableConfig::configurable`
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the keys of the alternatives
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.alternatives.iter().map(|(key, _)| key.as_str())
+    }
+
+    /// Get the alternative to use for a configuration
+    pub fn resolve(
+        &self,
+        config: Option<&RunnableConfig>,
+    ) -> Result<Arc<dyn Runnable<Input, Output>>> {
+        let selected = config.and_then(|config| config.configurable.get(&self.id));
+        let key = match selected {
+            Some(value) => {
+                check_configurable_value(&self.id, &self.own_property(), value)?;
+                value.as_str().unwrap_or_default()
+            }
+            None => self.default_key.as_str(),
+        };
+        self.alternatives
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, runnable)| runnable.clone())
+            .ok_or_else(|| {
+                crate::errors::FerricLinkError::configuration(format!(
+                    "Unknown alternative '{key}' for configurable key '{}'",
+                    self.id
+                ))
+            })
+    }
+
+    fn own_property(&self) -> serde_json::Value {
+        let mut property = serde_json::json!({
+            "type": "string",
+            "enum": self.keys().collect::<Vec<_>>(),
+            "default": self.default_key,
+        });
+        if let Some(description) = &self.description {
+            property["description"] = description.clone().into();
+        }
+        property
+    }
+}
+
+#[async_trait]
+impl<Input, Output> Runnable<Input, Output> for RunnableConfigurableAlternatives<Input, Output>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    async fn invoke(&self, input: Input, mut config: Option<RunnableConfig>) -> Result<Output> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        let runnable = self.resolve(config.as_ref())?;
+        let config = strip_configurable(config, [self.id.as_str()]);
+        runnable.invoke(input, config).await
+    }
+
+    async fn stream(
+        &self,
+        input: Input,
+        mut config: Option<RunnableConfig>,
+    ) -> Result<Pin<Box<dyn futures::Stream<Item = Result<Output>> + Send>>> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        let runnable = self.resolve(config.as_ref())?;
+        let config = strip_configurable(config, [self.id.as_str()]);
+        runnable.stream(input, config).await
+    }
+
+    fn name(&self) -> String {
+        self.resolve(None)
+            .map(|runnable| runnable.name())
+            .unwrap_or_else(|_| short_type_name::<Self>())
+    }
+
+    fn get_graph(&self) -> Graph {
+        self.resolve(None)
+            .map(|runnable| runnable.get_graph())
+            .unwrap_or_default()
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        let own = serde_json::json!({
+            "properties": { self.id.clone(): self.own_property() },
+        });
+        merge_config_schemas(
+            std::iter::once(Some(own))
+                .chain(self.alternatives.iter().map(|(_, r)| r.config_schema())),
+        )
+    }
+}
+
+/// Helper function to create a runnable with configurable fields
+pub fn configurable_fields<Input, Output, F>(
+    fields: Vec<ConfigurableField>,
+    factory: F,
+) -> Result<Arc<dyn Runnable<Input, Output>>>
+where
+    F: Fn(&HashMap<String, serde_json::Value>) -> Result<Arc<dyn Runnable<Input, Output>>>
+        + Send
+        + Sync
+        + 'static,
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    Ok(Arc::new(RunnableConfigurableFields::new(fields, factory)?))
+}
+
+/// Helper function to create a runnable with configurable alternatives
+pub fn configurable_alternatives<Input, Output>(
+    id: impl Into<String>,
+    default_key: impl Into<String>,
+    default: Arc<dyn Runnable<Input, Output>>,
+    alternatives: impl IntoIterator<Item = (String, Arc<dyn Runnable<Input, Output>>)>,
+) -> Arc<dyn Runnable<Input, Output>>
+where
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    let mut runnable = RunnableConfigurableAlternatives::new(id, default_key, default);
+    for (key, alternative) in alternatives {
+        runnable.add_alternative(key, alternative);
+    }
+    Arc::new(runnable)
+}
+
+/// Helper function to create a runnable from a simple function
+pub fn runnable<F, Input, Output>(func: F) -> Arc<dyn Runnable<Input, Output>>
+where
+    F: Fn(Input) -> Result<Output> + Send + Sync + 'static,
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    Arc::new(RunnableLambda::new(func))
+}
+
+/// Helper function to create an async runnable from a function
+pub fn runnable_async<F, Input, Output, Fut>(func: F) -> Arc<dyn Runnable<Input, Output>>
+where
+    F: Fn(Input) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Result<Output>> + Send + 'static,
+    Input: Send + Sync + 'static,
+    Output: Send + Sync + 'static,
+{
+    Arc::new(RunnableAsync::new(func))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::FerricLinkError;
+
+    #[tokio::test]
+    async fn test_runnable_lambda() {
+        let runnable = RunnableLambda::new(|x: i32| Ok(x * 2));
+        let result = runnable.invoke_simple(5).await.unwrap();
+        assert_eq!(result, 10);
+    }
+
+    #[tokio::test]
+    async fn test_runnable_async() {
+        let runnable = RunnableAsync::new(|x: i32| async move {
+            tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
+            Ok(x * 3)
+        });
+        let result = runnable.invoke_simple(4).await.unwrap();
+        assert_eq!(result, 12);
+    }
+
+    #[tokio::test]
+    async fn test_runnable_sequence() {
+        let first = Arc::new(RunnableLambda::new(|x: i32| Ok(x + 1)));
+        let second = Arc::new(RunnableLambda::new(|x: i32| Ok(x * 2)));
+        let sequence = RunnableSequence::new(first, second);
+
+        let result = sequence.invoke_simple(5).await.unwrap();
+        assert_eq!(result, 12); // (5 + 1) * 2
+    }
+
+    #[tokio::test]
+    async fn test_runnable_parallel() {
+        let runnable1 = Arc::new(RunnableLambda::new(|x: i32| Ok(x * 2)));
+        let runnable2 = Arc::new(RunnableLambda::new(|x: i32| Ok(x * 3)));
+        let parallel = RunnableParallel::new(vec![runnable1, runnable2]);
+
+        let results = parallel.invoke_simple(5).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.contains(&10)); // 5 * 2
+        assert!(results.contains(&15)); // 5 * 3
+    }
+
+    #[tokio::test]
+    async fn test_runnable_map() {
+        #[derive(Deserialize)]
+        struct Combined {
+            doubled: i32,
+            label: String,
+            parts: Vec<i32>,
+        }
+
+        let map = RunnableMap::new()
+            .with_branch("doubled", runnable(|x: i32| Ok(x * 2)))
+            .with_branch("label", runnable(|x: i32| Ok(format!("value {x}"))))
+            .with_branch("parts", runnable(|x: i32| Ok(vec![x, x + 1])));
+
+        assert_eq!(map.keys(), vec!["doubled", "label", "parts"]);
+
+        let result = map.invoke_simple(5).await.unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({"doubled": 10, "label": "value 5", "parts": [5, 6]})
+        );
+
+        let typed: Combined = map.invoke_typed(1, None).await.unwrap();
+        assert_eq!(typed.doubled, 2);
+        assert_eq!(typed.label, "value 1");
+        assert_eq!(typed.parts, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_runnable_map_max_concurrency_and_run_ids() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Probe {
+            active: Arc<AtomicUsize>,
+            peak: Arc<AtomicUsize>,
+        }
+
+        #[async_trait]
+        impl Runnable<i32, Option<String>> for Probe {
+            async fn invoke(
+                &self,
+                _input: i32,
+                config: Option<RunnableConfig>,
+            ) -> Result<Option<String>> {
+                let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                self.active.fetch_sub(1, Ordering::SeqCst);
+                Ok(config.and_then(|c| c.parent_run_id).map(|id| id.id))
+            }
+        }
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let mut map = RunnableMap::new();
+        for key in ["a", "b", "c", "d"] {
+            map.add_branch(
+                key,
+                Arc::new(Probe {
+                    active: active.clone(),
+                    peak: peak.clone(),
+                }),
+            );
+        }
+
+        let run_id = RunId::new_with_id("parent");
+        let config = RunnableConfig::new()
+            .with_max_concurrency(2)
+            .with_run_id(run_id);
+        let result = map.invoke(0, Some(config)).await.unwrap();
+
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        for key in ["a", "b", "c", "d"] {
+            assert_eq!(result[key], serde_json::json!("parent"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runnable_map_failure_stops_other_branches() {
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let finished_clone = finished.clone();
+        let map = RunnableMap::new()
+            .with_branch(
+                "slow",
+                Arc::new(RunnableAsync::new(move |x: i32| {
+                    let finished = finished_clone.clone();
+                    async move {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        finished.store(true, std::sync::atomic::Ordering::SeqCst);
+                        Ok(x)
+                    }
+                })),
+            )
+            .with_branch(
+                "failing",
+                runnable(|_: i32| -> Result<i32> { Err(FerricLinkError::runtime("boom")) }),
+            );
+
+        let error = map.invoke_simple(1).await.unwrap_err();
+        assert!(error.to_string().contains("boom"));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!finished.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_runnable_parallel_failure_stops_other_runnables() {
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let finished_clone = finished.clone();
+        let parallel = RunnableParallel::new(vec![
+            Arc::new(RunnableAsync::new(move |x: i32| {
+                let finished = finished_clone.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    finished.store(true, std::sync::atomic::Ordering::SeqCst);
+                    Ok(x)
+                }
+            })),
+            runnable(|_: i32| -> Result<i32> { Err(FerricLinkError::runtime("boom")) }),
+        ]);
+
+        let error = parallel.invoke_simple(1).await.unwrap_err();
+        assert!(error.to_string().contains("boom"));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!finished.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_runnable_branch() {
+        let branch = RunnableBranch::new(runnable(|x: i32| Ok(format!("other {x}"))))
+            .with_branch(
+                runnable(|x: i32| Ok(x < 0)),
+                runnable(|x: i32| Ok(format!("negative {x}"))),
+            )
+            .with_branch(
+                runnable(|x: i32| Ok(x % 2 == 0)),
+                runnable(|x: i32| Ok(format!("even {x}"))),
+            );
+
+        assert_eq!(branch.len(), 2);
+        assert_eq!(branch.invoke_simple(-2).await.unwrap(), "negative -2");
+        assert_eq!(branch.invoke_simple(4).await.unwrap(), "even 4");
+        assert_eq!(branch.invoke_simple(3).await.unwrap(), "other 3");
+    }
+
+    #[tokio::test]
+    async fn test_runnable_passthrough_assign() {
+        let passthrough = RunnablePassthrough::new();
+        assert_eq!(passthrough.invoke_simple(7).await.unwrap(), 7);
+
+        let assign = RunnablePassthrough::assign()
+            .with(
+                "upper",
+                runnable(|v: serde_json::Value| {
+                    Ok(serde_json::json!(
+                        v["question"].as_str().unwrap_or_default().to_uppercase()
+                    ))
+                }),
+            )
+            .with("context", runnable(|_| Ok(serde_json::json!(["doc"]))));
+
+        let result = assign
+            .invoke_simple(serde_json::json!({"question": "why?"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({"question": "why?", "upper": "WHY?", "context": ["doc"]})
+        );
+
+        assert!(assign.invoke_simple(serde_json::json!(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_runnable_pick() {
+        let input = serde_json::json!({"a": 1, "b": 2, "c": 3});
+
+        let single = RunnablePick::new("b");
+        assert_eq!(
+            single.invoke_simple(input.clone()).await.unwrap(),
+            serde_json::json!(2)
+        );
+
+        let many = RunnablePick::new_many(["a", "c", "missing"]);
+        assert_eq!(
+            many.invoke_simple(input).await.unwrap(),
+            serde_json::json!({"a": 1, "c": 3})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configurable_fields() {
+        let scaled = configurable_fields(
+            vec![
+                ConfigurableField::new("temperature", serde_json::json!(0.5))
+                    .with_name("Temperature")
+                    .with_description("Sampling temperature"),
+            ],
+            |values| {
+                let temperature = values["temperature"].as_f64().unwrap_or_default();
+                Ok(runnable(move |x: f64| Ok(x * temperature)))
+            },
+        )
+        .unwrap();
+        let chain = Arc::new(RunnableSequence::new(
+            scaled,
+            runnable(|x: f64| Ok(x + 1.0)),
+        ));
+
+        assert_eq!(chain.invoke_simple(2.0).await.unwrap(), 2.0);
+
+        let config = RunnableConfig::new().with_configurable("temperature", serde_json::json!(2.0));
+        assert_eq!(chain.invoke(2.0, Some(config)).await.unwrap(), 5.0);
+
+        let schema = chain.config_schema().unwrap();
+        assert_eq!(schema["properties"]["temperature"]["type"], "number");
+        assert_eq!(schema["properties"]["temperature"]["default"], 0.5);
+        assert_eq!(schema["properties"]["temperature"]["title"], "Temperature");
+        assert_eq!(schema["additionalProperties"], false);
+    }
+
+    #[tokio::test]
+    async fn test_configurable_alternatives() {
+        let greeter = configurable_alternatives(
+            "language",
+            "english",
+            runnable(|name: String| Ok(format!("Hello, {name}"))),
+            [(
+                "french".to_string(),
+                runnable(|name: String| Ok(format!("Bonjour, {name}"))),
+            )],
+        );
+
+        assert_eq!(
+            greeter.invoke_simple("Ada".to_string()).await.unwrap(),
+            "Hello, Ada"
+        );
+        let config =
+            RunnableConfig::new().with_configurable("language", serde_json::json!("french"));
+        assert_eq!(
+            greeter
+                .invoke("Ada".to_string(), Some(config))
+                .await
+                .unwrap(),
+            "Bonjour, Ada"
+        );
+
+        let schema = greeter.config_schema().unwrap();
+        assert_eq!(
+            schema["properties"]["language"]["enum"],
+            serde_json::json!(["english", "french"])
+        );
+
+        let config =
+            RunnableConfig::new().with_configurable("language", serde_json::json!("german"));
+        let error = greeter
+            .invoke("Ada".to_string(), Some(config))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("german"));
+    }
+
+    #[tokio::test]
+    async fn test_configurable_validation_errors() {
+        let top_k = configurable_fields(
+            vec![ConfigurableField::new("k", serde_json::json!(4))],
+            |values| {
+                let k = values["k"].as_u64().unwrap_or_default() as usize;
+                Ok(runnable(move |items: Vec<i32>| {
+                    Ok(items.into_iter().take(k).collect())
+                }))
+            },
+        )
+        .unwrap();
+        let chain: RunnableSequence<Vec<i32>, Vec<i32>, usize> =
+            RunnableSequence::new(top_k, runnable(|items: Vec<i32>| Ok(items.len())));
+
+        let config = RunnableConfig::new().with_configurable("k", serde_json::json!(2));
+        assert_eq!(chain.invoke(vec![1, 2, 3], Some(config)).await.unwrap(), 2);
+
+        let config = RunnableConfig::new().with_configurable("top_p", serde_json::json!(0.9));
+        let error = chain.invoke(vec![1, 2, 3], Some(config)).await.unwrap_err();
+        assert!(matches!(error, FerricLinkError::Configuration(_)));
+        assert!(error.to_string().contains("top_p"));
+
+        let config = RunnableConfig::new().with_configurable("k", serde_json::json!("two"));
+        let error = chain.invoke(vec![1, 2, 3], Some(config)).await.unwrap_err();
+        assert!(matches!(error, FerricLinkError::Configuration(_)));
+        assert!(error.to_string().contains("integer"));
+
+        // Nested runs not checked by an enclosing runnable are checked too
+        let nested = RunnableConfig::new()
+            .with_run_id(RunId::new())
+            .child()
+            .with_configurable("top_p", serde_json::json!(0.9));
+        let error = chain.invoke(vec![1, 2, 3], Some(nested)).await.unwrap_err();
+        assert!(error.to_string().contains("top_p"));
+    }
+
+    #[tokio::test]
+    async fn test_configurable_alternatives_accept_sibling_keys() {
+        let formal = configurable_fields(
+            vec![ConfigurableField::new("formal", serde_json::json!(false))],
+            |values| {
+                let formal = values["formal"].as_bool().unwrap_or_default();
+                Ok(runnable(move |name: String| {
+                    Ok(if formal {
+                        format!("Bonjour, {name}")
+                    } else {
+                        format!("Salut, {name}")
+                    })
+                }))
+            },
+        )
+        .unwrap();
+        let english: Arc<dyn Runnable<String, String>> = Arc::new(RunnableSequence::new(
+            runnable(|name: String| Ok(name)),
+            runnable(|name: String| Ok(format!("Hello, {name}"))),
+        ));
+        let greeter = configurable_alternatives(
+            "language",
+            "english",
+            english,
+            [("french".to_string(), formal)],
+        );
+
+        // A key of the french alternative is valid when english is selected
+        let config = RunnableConfig::new().with_configurable("formal", serde_json::json!(true));
+        assert_eq!(
+            greeter
+                .invoke("Ada".to_string(), Some(config.clone()))
+                .await
+                .unwrap(),
+            "Hello, Ada"
+        );
+        let config = config.with_configurable("language", serde_json::json!("french"));
+        assert_eq!(
+            greeter
+                .invoke("Ada".to_string(), Some(config))
+                .await
+                .unwrap(),
+            "Bonjour, Ada"
+        );
+
+        let config = RunnableConfig::new().with_configurable("formal", serde_json::json!("yes"));
+        let error = greeter
+            .invoke("Ada".to_string(), Some(config))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("boolean"));
+    }
+
+    #[tokio::test]
+    async fn test_runnable_get_graph() {
+        let named = |name: &str| -> Arc<dyn Runnable<i32, i32>> {
+            Arc::new(RunnableLambda::new(|x: i32| Ok(x)).with_name(name))
+        };
+        let branch = RunnableBranch::new(named("large")).with_branch(
+            Arc::new(RunnableLambda::new(|x: i32| Ok(x < 10)).with_name("is_small")),
+            named("small"),
+        );
+        let chain = RunnableSequence::new(
+            named("parse"),
+            Arc::new(RunnableSequence::new(
+                Arc::new(branch),
+                Arc::new(RunnableParallel::new(vec![
+                    named("double"),
+                    named("square"),
+                ])),
+            )),
+        );
+
+        let graph = chain.get_graph();
+        let names: Vec<_> = graph.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "parse",
+                "RunnableBranch",
+                "RunnableBranchOutput",
+                "small",
+                "large",
+                "RunnableParallelInput",
+                "RunnableParallelOutput",
+                "double",
+                "square",
+            ]
+        );
+        assert_eq!(graph.first_node().unwrap().name, "parse");
+        assert_eq!(graph.last_node().unwrap().name, "RunnableParallelOutput");
+
+        let conditional: Vec<_> = graph
+            .edges
+            .iter()
+            .filter(|e| e.conditional)
+            .map(|e| (e.target.as_str(), e.label.as_deref()))
+            .collect();
+        assert_eq!(
+            conditional,
+            vec![("small", Some("is_small")), ("large", Some("default"))]
+        );
+
+        let mermaid = graph.draw_mermaid();
+        assert!(mermaid.contains("n0 --> n1;"));
+        assert!(mermaid.contains("n1 -. \"is_small\" .-> n3;"));
+        assert!(
+            graph
+                .draw_dot()
+                .contains("\"parse\" -> \"RunnableBranch\";")
+        );
+        assert!(graph.draw_ascii().contains("| RunnableParallelOutput |"));
+    }
+
+    #[tokio::test]
+    async fn test_runnable_timeout() {
+        struct CancelRecorder {
+            cancelled: Arc<std::sync::atomic::AtomicBool>,
+        }
+
+        #[async_trait]
+        impl CallbackHandler for CancelRecorder {
+            async fn on_run_cancel(&self, run_info: &RunInfo) -> Result<()> {
+                assert!(run_info.is_failed());
+                self.cancelled
+                    .store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let observed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let observed_clone = observed.clone();
+        let slow = Arc::new(RunnableAsync::new(move |x: i32| {
+            let observed = observed_clone.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                observed.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(x)
+            }
+        }));
+
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let config = RunnableConfig::new().with_callback(Arc::new(CancelRecorder {
+            cancelled: cancelled.clone(),
+        }));
+
+        let timed = with_timeout(slow.clone(), Duration::from_millis(10));
+        let error = timed.invoke(1, Some(config)).await.unwrap_err();
+        assert!(error.is_timeout());
+        assert!(cancelled.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(!observed.load(std::sync::atomic::Ordering::SeqCst));
+
+        let generous = RunnableTimeout::new(slow.clone(), Duration::from_secs(5));
+        assert_eq!(generous.invoke_simple(2).await.unwrap(), 2);
+        let unbounded = with_timeout(slow, Duration::MAX);
+        assert_eq!(unbounded.invoke_simple(3).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_runnable_rate_limited() {
+        type Waits = Arc<std::sync::Mutex<Vec<(String, Option<f64>)>>>;
+
+        struct WaitRecorder {
+            waits: Waits,
+        }
+
+        #[async_trait]
+        impl CallbackHandler for WaitRecorder {
+            async fn on_run_start(&self, run_info: &RunInfo) -> Result<()> {
+                let wait = run_info
+                    .metadata
+                    .get(RATE_LIMIT_WAIT_METADATA_KEY)
+                    .and_then(|value| value.as_f64());
+                self.waits
+                    .lock()
+                    .unwrap()
+                    .push((run_info.name.clone(), wait));
+                Ok(())
+            }
+        }
+
+        let limiter: Arc<dyn BaseRateLimiter> =
+            Arc::new(crate::rate_limiters::GcraRateLimiter::new(20.0, 1.0));
+        let sequence: Arc<dyn Runnable<i32, i32>> = Arc::new(RunnableSequence::new(
+            runnable(|x: i32| Ok(x + 1)),
+            runnable(|x: i32| Ok(x * 2)),
+        ));
+        let limited = with_rate_limiter(sequence, limiter.clone());
+
+        let waits = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let config = RunnableConfig::new().with_callback(Arc::new(WaitRecorder {
+            waits: waits.clone(),
+        }));
+
+        let start = std::time::Instant::now();
+        let results = limited
+            .batch(vec![1, 2, 3, 4], Some(config.clone()))
+            .await
+            .unwrap();
+        assert_eq!(results, vec![4, 6, 8, 10]);
+        assert!(start.elapsed() >= Duration::from_millis(140));
+
+        // Streams acquire too, and report the wait on their run
+        use futures::StreamExt;
+        let chunks: Vec<i32> = limited
+            .stream(5, Some(config))
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks, vec![12]);
+
+        let waits = waits.lock().unwrap().clone();
+        let limited_runs: Vec<f64> = waits
+            .iter()
+            .filter(|(name, _)| name == "RunnableRateLimited")
+            .map(|(_, wait)| wait.expect("wait reported on the rate limited run"))
+            .collect();
+        assert_eq!(limited_runs.len(), 5);
+        assert!(limited_runs.iter().skip(1).all(|wait| *wait > 20.0));
+        assert!(
+            waits
+                .iter()
+                .filter(|(name, _)| name != "RunnableRateLimited")
+                .all(|(_, wait)| wait.is_none())
+        );
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let error = limited
+            .invoke(
+                1,
+                Some(RunnableConfig::new().with_cancellation_token(token)),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_runnable_config_cancellation() {
+        let token = CancellationToken::new();
+        let config = RunnableConfig::new().with_cancellation_token(token.clone());
+        assert!(config.check_cancelled().is_ok());
+
+        let child = config.child();
+        token.cancel();
+        assert!(config.check_cancelled().unwrap_err().is_cancelled());
+        assert!(child.is_cancelled());
+
+        let pending = config
+            .run_cancellable(std::future::pending::<Result<()>>())
+            .await;
+        assert!(pending.unwrap_err().is_cancelled());
+
+        let expired = RunnableConfig::new().with_timeout(Duration::ZERO);
+        assert!(expired.check_cancelled().unwrap_err().is_timeout());
+        assert_eq!(expired.remaining_time(), Some(Duration::ZERO));
+
+        // Timeouts too long to represent set no deadline
+        let unbounded = RunnableConfig::new().with_timeout(Duration::MAX);
+        assert_eq!(unbounded.remaining_time(), None);
+        assert!(!unbounded.is_cancelled());
+        let bounded = RunnableConfig::new()
+            .with_timeout(Duration::from_secs(60))
+            .with_timeout(Duration::MAX);
+        assert!(bounded.remaining_time().unwrap() <= Duration::from_secs(60));
+
+        let sequence =
+            RunnableSequence::new(runnable(|x: i32| Ok(x + 1)), runnable(|x: i32| Ok(x * 2)));
+        let error = sequence
+            .invoke(
+                1,
+                Some(RunnableConfig::new().with_cancellation_token(token)),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_runnable_batch() {
+        let runnable = RunnableLambda::new(|x: i32| Ok(x * 2));
+        let results = runnable.batch(vec![1, 2, 3], None).await.unwrap();
+        assert_eq!(results, vec![2, 4, 6]);
     }
 
     #[tokio::test]
@@ -473,18 +2906,213 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_console_callback_handler() {
-        let handler = ConsoleCallbackHandler::new();
-        let run_id = "test-run";
-        let input = serde_json::Value::String("test input".to_string());
-        let output = serde_json::Value::String("test output".to_string());
-        let error = crate::errors::FerricLinkError::generic("test error");
-
-        // These should not panic
-        handler.on_start(run_id, &input).await.unwrap();
-        handler.on_success(run_id, &output).await.unwrap();
-        handler.on_error(run_id, &error).await.unwrap();
-        handler.on_stream(run_id, &output).await.unwrap();
+    async fn test_callbacks_parent_child_linkage() {
+        use crate::callbacks::MemoryCallbackHandler;
+
+        let handler = Arc::new(MemoryCallbackHandler::new());
+        let local = Arc::new(MemoryCallbackHandler::new());
+        let sequence = RunnableSequence::new(
+            Arc::new(RunnableLambda::new(|x: i32| Ok(x + 1)).with_name("add_one")),
+            Arc::new(RunnableLambda::new(|x: i32| Ok(x * 2)).with_name("double")),
+        );
+
+        let config = RunnableConfig::new()
+            .with_tag("linkage")
+            .with_callback(handler.clone())
+            .with_local_callback(local.clone());
+        assert_eq!(sequence.invoke(5, Some(config)).await.unwrap(), 12);
+
+        let runs = handler.get_runs().await;
+        assert_eq!(runs.len(), 3);
+        assert!(runs.iter().all(|run| run.is_successful()));
+        assert!(
+            runs.iter()
+                .all(|run| run.tags.contains(&"linkage".to_string()))
+        );
+
+        let parent = &runs[0];
+        assert_eq!(parent.name, "RunnableSequence");
+        assert_eq!(parent.component_type, "chain");
+        assert!(parent.parent_run_id.is_none());
+        for (child, name) in runs[1..].iter().zip(["add_one", "double"]) {
+            assert_eq!(child.name, name);
+            assert_eq!(child.parent_run_id.as_ref(), Some(&parent.run_id));
+        }
+
+        // Local handlers only see the run they were attached to
+        let local_runs = local.get_runs().await;
+        assert_eq!(local_runs.len(), 1);
+        assert_eq!(local_runs[0].run_id, parent.run_id);
+    }
+
+    #[tokio::test]
+    async fn test_callbacks_on_error_and_stream() {
+        use crate::callbacks::MemoryCallbackHandler;
+        use futures::StreamExt;
+
+        struct StreamRecorder {
+            chunks: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+        }
+
+        #[async_trait]
+        impl CallbackHandler for StreamRecorder {
+            async fn on_run_stream(
+                &self,
+                _run_info: &RunInfo,
+                chunk: &serde_json::Value,
+            ) -> Result<()> {
+                self.chunks.lock().unwrap().push(chunk.clone());
+                Ok(())
+            }
+        }
+
+        let handler = Arc::new(MemoryCallbackHandler::new());
+        let failing = RunnableLambda::new(|_: i32| -> Result<i32> {
+            Err(crate::errors::FerricLinkError::generic("boom"))
+        });
+        let config = RunnableConfig::new().with_callback(handler.clone());
+        assert!(failing.invoke(1, Some(config)).await.is_err());
+        let failed = handler.get_failed_runs().await;
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].error.as_deref().unwrap().contains("boom"));
+
+        let chunks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pick = Arc::new(RunnablePick::new("a"));
+        let sequence =
+            RunnableSequence::new(runnable(|x: i32| Ok(serde_json::json!({"a": x}))), pick);
+        let config = RunnableConfig::new().with_callback(Arc::new(StreamRecorder {
+            chunks: chunks.clone(),
+        }));
+        let outputs: Vec<_> = sequence
+            .stream(3, Some(config))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(chunks.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_events() {
+        use crate::callbacks::StreamEventKind;
+        use futures::StreamExt;
+
+        let assign = RunnablePassthrough::assign().with(
+            "doubled",
+            runnable(|v: serde_json::Value| Ok(v["x"].clone())),
+        );
+        let sequence = RunnableSequence::new(Arc::new(assign), Arc::new(RunnablePick::new("x")));
+
+        let events: Vec<_> = sequence
+            .stream_events(serde_json::json!({"x": 1}), None, StreamEventFilter::new())
+            .collect()
+            .await;
+        let events: Vec<_> = events.into_iter().map(|e| e.unwrap()).collect();
+
+        let names: Vec<_> = events
+            .iter()
+            .map(|e| format!("{}:{}", e.event, e.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "on_chain_start:RunnableSequence",
+                "on_chain_start:RunnableAssign",
+                "on_chain_start:RunnableLambda",
+                "on_chain_end:RunnableLambda",
+                "on_chain_end:RunnableAssign",
+                "on_chain_start:RunnablePick",
+                "on_chain_end:RunnablePick",
+                "on_chain_end:RunnableSequence",
+            ]
+        );
+
+        let root = events[0].run_id.clone();
+        assert_eq!(
+            events[2].parent_ids,
+            vec![root.clone(), events[1].run_id.clone()]
+        );
+        assert_eq!(events[6].data.output, Some(serde_json::json!(1)));
+
+        let filtered: Vec<_> = sequence
+            .stream_events(
+                serde_json::json!({"x": 1}),
+                None,
+                StreamEventFilter::new().include_name("RunnablePick"),
+            )
+            .collect()
+            .await;
+        assert_eq!(filtered.len(), 2);
+        assert!(
+            filtered
+                .iter()
+                .all(|e| e.as_ref().unwrap().name == "RunnablePick")
+        );
+
+        let failing = RunnablePick::new("x");
+        let events: Vec<_> = failing
+            .stream_events(serde_json::json!(1), None, StreamEventFilter::new())
+            .collect()
+            .await;
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].as_ref().unwrap().kind, StreamEventKind::Error);
+        assert!(events[2].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_serialized_values() {
+        use crate::callbacks::StreamEventKind;
+        use futures::StreamExt;
+
+        // The input of start events and the output of end events
+        let values = |events: Vec<Result<StreamEvent>>| {
+            events
+                .into_iter()
+                .map(|e| {
+                    let e = e.unwrap();
+                    match e.kind {
+                        StreamEventKind::Start => e.data.input,
+                        _ => e.data.output,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Generic values are reported as null by default
+        let lambda = RunnableLambda::new(|x: i32| Ok(x + 1));
+        let events = lambda
+            .stream_events(1, None, StreamEventFilter::new())
+            .collect()
+            .await;
+        assert_eq!(
+            values(events),
+            vec![Some(serde_json::Value::Null), Some(serde_json::Value::Null),]
+        );
+
+        let sequence = RunnableSequence::new(
+            Arc::new(RunnableLambda::new(|x: i32| Ok(x + 1)).with_serialized_values()),
+            Arc::new(RunnablePassthrough::new().with_serialized_values()),
+        )
+        .with_serialized_values();
+        let parallel = RunnableParallel::new(vec![Arc::new(sequence)]).with_serialized_values();
+        let events = parallel
+            .stream_events(1, None, StreamEventFilter::new())
+            .collect()
+            .await;
+        assert_eq!(
+            values(events),
+            vec![
+                Some(serde_json::json!(1)),
+                Some(serde_json::json!(1)),
+                Some(serde_json::json!(1)),
+                Some(serde_json::json!(2)),
+                Some(serde_json::json!(2)),
+                Some(serde_json::json!(2)),
+                Some(serde_json::json!(2)),
+                Some(serde_json::json!([2])),
+            ]
+        );
     }
 
     #[tokio::test]
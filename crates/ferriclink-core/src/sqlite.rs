@@ -0,0 +1,1182 @@
+//! SQLite persistence for FerricLink Core
+//!
+//! This module is available with the `sqlite` feature. It provides stores that
+//! survive restarts, all sharing a single database file:
+//!
+//! - [`SqliteChatMessageHistory`]: chat message history per session.
+//! - [`SqliteCache`]: an LLM cache with optional TTL and size limit.
+//! - [`SqliteRecordStore`]: a record of the keys written to a vector store,
+//!   with the time they were last updated.
+//!
+//! Connections come from a [`SqlitePool`]. Async methods run on Tokio's
+//! blocking thread pool, so they never block the async runtime. Each store
+//! creates and upgrades its tables with versioned [`Migration`]s.
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::caches::{BaseCache, CacheStats, CachedGenerations};
+use crate::chat_history::{BaseChatMessageHistory, replace_prefix};
+use crate::errors::{FerricLinkError, Result};
+use crate::messages::AnyMessage;
+
+/// Default maximum number of pooled connections
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 4;
+
+/// Name of the table recording applied migrations
+const MIGRATIONS_TABLE: &str = "ferriclink_migrations";
+
+/// A versioned schema change of a store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    /// Version reached once the migration is applied, starting at 1
+    pub version: u32,
+    /// Short description of the change
+    pub description: &'static str,
+    /// SQL statements to execute
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// Create a new migration.
+    pub const fn new(version: u32, description: &'static str, sql: &'static str) -> Self {
+        Self {
+            version,
+            description,
+            sql,
+        }
+    }
+}
+
+fn pool_error(e: r2d2::Error) -> FerricLinkError {
+    FerricLinkError::runtime(format!("SQLite pool error: {e}"))
+}
+
+fn now_micros() -> i64 {
+    Utc::now().timestamp_micros()
+}
+
+fn from_micros(micros: i64) -> DateTime<Utc> {
+    Utc.timestamp_micros(micros).single().unwrap_or_default()
+}
+
+/// A pool of connections to a SQLite database file
+///
+/// Cloning the pool shares its connections.
+#[derive(Debug, Clone)]
+pub struct SqlitePool {
+    pool: r2d2::Pool<SqliteConnectionManager>,
+}
+
+impl SqlitePool {
+    /// Open a database file, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_max_connections(path, DEFAULT_MAX_CONNECTIONS)
+    }
+
+    /// Open a database file with a maximum number of pooled connections.
+    ///
+    /// The database uses write-ahead logging, so readers do not block the
+    /// writer, and waits up to 5 seconds for locks held by other connections.
+    pub fn with_max_connections(path: impl AsRef<Path>, max_connections: u32) -> Result<Self> {
+        if max_connections == 0 {
+            return Err(FerricLinkError::validation(
+                "max_connections must be greater than 0",
+            ));
+        }
+
+        let manager = SqliteConnectionManager::file(path).with_init(|conn| {
+            conn.busy_timeout(Duration::from_secs(5))?;
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            conn.pragma_update(None, "synchronous", "NORMAL")
+        });
+        let pool = r2d2::Pool::builder()
+            .max_size(max_connections)
+            .build(manager)
+            .map_err(pool_error)?;
+        Ok(Self { pool })
+    }
+
+    /// Run a function with a pooled connection, blocking the current thread.
+    pub fn run_blocking<T>(&self, func: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        let mut conn = self.pool.get().map_err(pool_error)?;
+        func(&mut conn)
+    }
+
+    /// Run a function with a pooled connection on the blocking thread pool.
+    pub async fn run<T, F>(&self, func: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || pool.run_blocking(func))
+            .await
+            .map_err(|e| FerricLinkError::runtime(format!("SQLite task failed: {e}")))?
+    }
+
+    /// Apply the migrations of a component that have not been applied yet.
+    ///
+    /// Applied versions are recorded per component, and every migration runs
+    /// in its own transaction.
+    pub fn migrate(&self, component: &str, migrations: &[Migration]) -> Result<()> {
+        self.run_blocking(|conn| {
+            conn.execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {MIGRATIONS_TABLE} (
+                    component TEXT NOT NULL,
+                    version INTEGER NOT NULL,
+                    description TEXT NOT NULL,
+                    applied_at INTEGER NOT NULL,
+                    PRIMARY KEY (component, version)
+                )"
+            ))?;
+
+            let mut migrations = migrations.to_vec();
+            migrations.sort_by_key(|m| m.version);
+            for migration in migrations {
+                let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+                let applied: bool = tx.query_row(
+                    &format!(
+                        "SELECT EXISTS(SELECT 1 FROM {MIGRATIONS_TABLE} WHERE component = ?1 AND version = ?2)"
+                    ),
+                    params![component, migration.version],
+                    |row| row.get(0),
+                )?;
+                if !applied {
+                    tx.execute_batch(migration.sql)?;
+                    tx.execute(
+                        &format!(
+                            "INSERT INTO {MIGRATIONS_TABLE} (component, version, description, applied_at) VALUES (?1, ?2, ?3, ?4)"
+                        ),
+                        params![
+                            component,
+                            migration.version,
+                            migration.description,
+                            now_micros()
+                        ],
+                    )?;
+                }
+                tx.commit()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Get the latest applied migration version of a component, 0 if none.
+    pub fn schema_version(&self, component: &str) -> Result<u32> {
+        self.run_blocking(|conn| {
+            let table_exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+                [MIGRATIONS_TABLE],
+                |row| row.get(0),
+            )?;
+            if !table_exists {
+                return Ok(0);
+            }
+            let version: Option<u32> = conn.query_row(
+                &format!("SELECT MAX(version) FROM {MIGRATIONS_TABLE} WHERE component = ?1"),
+                [component],
+                |row| row.get(0),
+            )?;
+            Ok(version.unwrap_or(0))
+        })
+    }
+}
+
+/// Migrations of [`SqliteChatMessageHistory`]
+pub const CHAT_HISTORY_MIGRATIONS: &[Migration] = &[Migration::new(
+    1,
+    "create message_store",
+    "CREATE TABLE IF NOT EXISTS message_store (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        session_id TEXT NOT NULL,
+        message TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS message_store_session_idx ON message_store (session_id, id);",
+)];
+
+/// Chat message history stored in a SQLite database.
+#[derive(Debug, Clone)]
+pub struct SqliteChatMessageHistory {
+    pool: SqlitePool,
+    session_id: String,
+}
+
+impl SqliteChatMessageHistory {
+    /// Create a new history for a session, creating its table if needed.
+    pub fn new(pool: SqlitePool, session_id: impl Into<String>) -> Result<Self> {
+        pool.migrate("chat_history", CHAT_HISTORY_MIGRATIONS)?;
+        Ok(Self {
+            pool,
+            session_id: session_id.into(),
+        })
+    }
+
+    /// Open a database file and create a history for a session.
+    pub fn open(path: impl AsRef<Path>, session_id: impl Into<String>) -> Result<Self> {
+        Self::new(SqlitePool::open(path)?, session_id)
+    }
+
+    /// Get the session ID.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    fn select(conn: &Connection, session_id: &str) -> Result<Vec<AnyMessage>> {
+        let mut stmt =
+            conn.prepare("SELECT message FROM message_store WHERE session_id = ?1 ORDER BY id")?;
+        let rows = stmt.query_map([session_id], |row| row.get::<_, String>(0))?;
+        let mut messages = Vec::new();
+        for row in rows {
+            messages.push(serde_json::from_str(&row?)?);
+        }
+        Ok(messages)
+    }
+
+    fn insert_rows(conn: &Connection, session_id: &str, messages: &[AnyMessage]) -> Result<()> {
+        let mut stmt = conn.prepare(
+            "INSERT INTO message_store (session_id, message, created_at) VALUES (?1, ?2, ?3)",
+        )?;
+        for message in messages {
+            stmt.execute(params![
+                session_id,
+                serde_json::to_string(message)?,
+                now_micros()
+            ])?;
+        }
+        Ok(())
+    }
+
+    fn insert(conn: &mut Connection, session_id: &str, messages: &[AnyMessage]) -> Result<()> {
+        let tx = conn.transaction()?;
+        Self::insert_rows(&tx, session_id, messages)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete(conn: &mut Connection, session_id: &str) -> Result<()> {
+        conn.execute(
+            "DELETE FROM message_store WHERE session_id = ?1",
+            [session_id],
+        )?;
+        Ok(())
+    }
+
+    fn replace(conn: &mut Connection, session_id: &str, messages: &[AnyMessage]) -> Result<()> {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM message_store WHERE session_id = ?1",
+            [session_id],
+        )?;
+        Self::insert_rows(&tx, session_id, messages)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn replace_if(
+        conn: &mut Connection,
+        session_id: &str,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        // Take the write lock up front, so no message can be added between the
+        // check and the replacement
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let current = Self::select(&tx, session_id)?;
+        let Some(messages) = replace_prefix(&current, expected_prefix, replacement) else {
+            return Ok(false);
+        };
+        tx.execute(
+            "DELETE FROM message_store WHERE session_id = ?1",
+            [session_id],
+        )?;
+        Self::insert_rows(&tx, session_id, &messages)?;
+        tx.commit()?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl BaseChatMessageHistory for SqliteChatMessageHistory {
+    fn messages(&self) -> Result<Vec<AnyMessage>> {
+        self.pool
+            .run_blocking(|conn| Self::select(conn, &self.session_id))
+    }
+
+    fn add_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.pool
+            .run_blocking(|conn| Self::insert(conn, &self.session_id, &messages))
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.pool
+            .run_blocking(|conn| Self::delete(conn, &self.session_id))
+    }
+
+    async fn amessages(&self) -> Result<Vec<AnyMessage>> {
+        let session_id = self.session_id.clone();
+        self.pool
+            .run(move |conn| Self::select(conn, &session_id))
+            .await
+    }
+
+    async fn aadd_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        let session_id = self.session_id.clone();
+        self.pool
+            .run(move |conn| Self::insert(conn, &session_id, &messages))
+            .await
+    }
+
+    async fn aclear(&self) -> Result<()> {
+        let session_id = self.session_id.clone();
+        self.pool
+            .run(move |conn| Self::delete(conn, &session_id))
+            .await
+    }
+
+    fn replace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        self.pool
+            .run_blocking(|conn| Self::replace(conn, &self.session_id, &messages))
+    }
+
+    async fn areplace_messages(&self, messages: Vec<AnyMessage>) -> Result<()> {
+        let session_id = self.session_id.clone();
+        self.pool
+            .run(move |conn| Self::replace(conn, &session_id, &messages))
+            .await
+    }
+
+    fn replace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        self.pool.run_blocking(|conn| {
+            Self::replace_if(conn, &self.session_id, expected_prefix, replacement)
+        })
+    }
+
+    async fn areplace_messages_if(
+        &self,
+        expected_prefix: &[AnyMessage],
+        replacement: Vec<AnyMessage>,
+    ) -> Result<bool> {
+        let session_id = self.session_id.clone();
+        let expected_prefix = expected_prefix.to_vec();
+        self.pool
+            .run(move |conn| Self::replace_if(conn, &session_id, &expected_prefix, replacement))
+            .await
+    }
+}
+
+/// Migrations of [`SqliteCache`]
+pub const CACHE_MIGRATIONS: &[Migration] = &[
+    Migration::new(
+        1,
+        "create llm_cache",
+        "CREATE TABLE IF NOT EXISTS llm_cache (
+            prompt TEXT NOT NULL,
+            llm_string TEXT NOT NULL,
+            value TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (prompt, llm_string)
+        );",
+    ),
+    Migration::new(
+        2,
+        "add expiry and access tracking to llm_cache",
+        "ALTER TABLE llm_cache ADD COLUMN expires_at INTEGER;
+        ALTER TABLE llm_cache ADD COLUMN last_accessed INTEGER NOT NULL DEFAULT 0;
+        CREATE INDEX IF NOT EXISTS llm_cache_access_idx ON llm_cache (last_accessed);",
+    ),
+];
+
+/// Cache that stores LLM generations in a SQLite database.
+///
+/// Entries are keyed by the prompt and the LLM string. Entries can expire
+/// after a TTL, and the least recently used entries are evicted when the
+/// cache exceeds its maximum size.
+#[derive(Debug, Clone)]
+pub struct SqliteCache {
+    pool: SqlitePool,
+    ttl: Option<Duration>,
+    max_size: Option<usize>,
+    stats: Arc<Mutex<CacheStats>>,
+}
+
+impl SqliteCache {
+    /// Create a new cache, creating its table if needed.
+    pub fn new(pool: SqlitePool) -> Result<Self> {
+        pool.migrate("llm_cache", CACHE_MIGRATIONS)?;
+        Ok(Self {
+            pool,
+            ttl: None,
+            max_size: None,
+            stats: Arc::new(Mutex::new(CacheStats::default())),
+        })
+    }
+
+    /// Open a database file and create a cache.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(SqlitePool::open(path)?)
+    }
+
+    /// Set the time-to-live of new entries.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Set the maximum number of entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_size` is 0.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        assert!(max_size > 0, "max_size must be greater than 0");
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Get the time-to-live of new entries.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Get the maximum number of entries.
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Get cache statistics.
+    pub async fn stats(&self) -> Result<CacheStats> {
+        let current_size = self.size().await?;
+        Ok(CacheStats {
+            current_size,
+            ..self.lock_stats().clone()
+        })
+    }
+
+    /// Get the number of stored entries, including expired ones not yet removed.
+    pub async fn size(&self) -> Result<usize> {
+        self.pool.run(|conn| Self::count(conn)).await
+    }
+
+    /// Remove all expired entries, returning how many were removed.
+    pub async fn purge_expired(&self) -> Result<usize> {
+        self.pool
+            .run(|conn| {
+                Ok(conn.execute(
+                    "DELETE FROM llm_cache WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+                    [now_micros()],
+                )?)
+            })
+            .await
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, CacheStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn count(conn: &Connection) -> Result<usize> {
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM llm_cache", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn select(
+        conn: &mut Connection,
+        prompt: &str,
+        llm_string: &str,
+    ) -> Result<Option<CachedGenerations>> {
+        let now = now_micros();
+        let row: Option<(String, Option<i64>)> = conn
+            .query_row(
+                "SELECT value, expires_at FROM llm_cache WHERE prompt = ?1 AND llm_string = ?2",
+                params![prompt, llm_string],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        match row {
+            Some((_, Some(expires_at))) if expires_at <= now => {
+                conn.execute(
+                    "DELETE FROM llm_cache WHERE prompt = ?1 AND llm_string = ?2",
+                    params![prompt, llm_string],
+                )?;
+                Ok(None)
+            }
+            Some((value, _)) => {
+                conn.execute(
+                    "UPDATE llm_cache SET last_accessed = ?3 WHERE prompt = ?1 AND llm_string = ?2",
+                    params![prompt, llm_string, now],
+                )?;
+                Ok(Some(serde_json::from_str(&value)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn upsert(
+        conn: &mut Connection,
+        prompt: &str,
+        llm_string: &str,
+        value: &CachedGenerations,
+        ttl: Option<Duration>,
+        max_size: Option<usize>,
+    ) -> Result<(usize, usize)> {
+        let now = now_micros();
+        // A TTL too long to represent never expires
+        let expires_at = ttl
+            .and_then(|ttl| i64::try_from(ttl.as_micros()).ok())
+            .map(|ttl| now.saturating_add(ttl));
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO llm_cache (prompt, llm_string, value, created_at, expires_at, last_accessed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?4)
+             ON CONFLICT (prompt, llm_string) DO UPDATE SET
+                value = excluded.value,
+                created_at = excluded.created_at,
+                expires_at = excluded.expires_at,
+                last_accessed = excluded.last_accessed",
+            params![prompt, llm_string, serde_json::to_string(value)?, now, expires_at],
+        )?;
+
+        let mut evicted = 0;
+        if let Some(max_size) = max_size {
+            // Expired entries go first, then the least recently used ones
+            tx.execute(
+                "DELETE FROM llm_cache WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+                [now],
+            )?;
+            evicted = tx.execute(
+                "DELETE FROM llm_cache WHERE rowid IN (
+                    SELECT rowid FROM llm_cache ORDER BY last_accessed ASC, rowid ASC
+                    LIMIT MAX((SELECT COUNT(*) FROM llm_cache) - ?1, 0)
+                )",
+                [max_size as i64],
+            )?;
+        }
+
+        let size = Self::count(&tx)?;
+        tx.commit()?;
+        Ok((size, evicted))
+    }
+
+    fn record_lookup(&self, result: &Option<CachedGenerations>) {
+        let mut stats = self.lock_stats();
+        if result.is_some() {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+    }
+
+    fn record_update(&self, (size, evicted): (usize, usize)) {
+        let mut stats = self.lock_stats();
+        stats.updates += 1;
+        stats.evictions += evicted as u64;
+        stats.max_size_reached = stats.max_size_reached.max(size);
+    }
+
+    fn record_clear(&self) {
+        self.lock_stats().clears += 1;
+    }
+}
+
+#[async_trait]
+impl BaseCache for SqliteCache {
+    fn lookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
+        let result = self
+            .pool
+            .run_blocking(|conn| Self::select(conn, prompt, llm_string))?;
+        self.record_lookup(&result);
+        Ok(result)
+    }
+
+    fn update(&self, prompt: &str, llm_string: &str, return_val: CachedGenerations) -> Result<()> {
+        let counts = self.pool.run_blocking(|conn| {
+            Self::upsert(
+                conn,
+                prompt,
+                llm_string,
+                &return_val,
+                self.ttl,
+                self.max_size,
+            )
+        })?;
+        self.record_update(counts);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.pool.run_blocking(|conn| {
+            conn.execute("DELETE FROM llm_cache", [])?;
+            Ok(())
+        })?;
+        self.record_clear();
+        Ok(())
+    }
+
+    async fn alookup(&self, prompt: &str, llm_string: &str) -> Result<Option<CachedGenerations>> {
+        let (prompt, llm_string) = (prompt.to_string(), llm_string.to_string());
+        let result = self
+            .pool
+            .run(move |conn| Self::select(conn, &prompt, &llm_string))
+            .await?;
+        self.record_lookup(&result);
+        Ok(result)
+    }
+
+    async fn aupdate(
+        &self,
+        prompt: &str,
+        llm_string: &str,
+        return_val: CachedGenerations,
+    ) -> Result<()> {
+        let (prompt, llm_string) = (prompt.to_string(), llm_string.to_string());
+        let (ttl, max_size) = (self.ttl, self.max_size);
+        let counts = self
+            .pool
+            .run(move |conn| Self::upsert(conn, &prompt, &llm_string, &return_val, ttl, max_size))
+            .await?;
+        self.record_update(counts);
+        Ok(())
+    }
+
+    async fn aclear(&self) -> Result<()> {
+        self.pool
+            .run(|conn| {
+                conn.execute("DELETE FROM llm_cache", [])?;
+                Ok(())
+            })
+            .await?;
+        self.record_clear();
+        Ok(())
+    }
+}
+
+/// Migrations of [`SqliteRecordStore`]
+pub const RECORD_STORE_MIGRATIONS: &[Migration] = &[Migration::new(
+    1,
+    "create upsertion_record",
+    "CREATE TABLE IF NOT EXISTS upsertion_record (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        group_id TEXT,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (namespace, key)
+    );
+    CREATE INDEX IF NOT EXISTS upsertion_record_updated_idx ON upsertion_record (namespace, updated_at);
+    CREATE INDEX IF NOT EXISTS upsertion_record_group_idx ON upsertion_record (namespace, group_id);",
+)];
+
+/// A record of a key written to a vector store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// The key of the written document
+    pub key: String,
+    /// The group of the key, usually the source document
+    pub group_id: Option<String>,
+    /// When the key was last written
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Filter for [`SqliteRecordStore::list_keys`]
+#[derive(Debug, Clone, Default)]
+pub struct RecordFilter {
+    /// Only keys updated strictly before this time
+    pub before: Option<DateTime<Utc>>,
+    /// Only keys updated strictly after this time
+    pub after: Option<DateTime<Utc>>,
+    /// Only keys in one of these groups
+    pub group_ids: Option<Vec<String>>,
+    /// Maximum number of keys to return
+    pub limit: Option<usize>,
+}
+
+impl RecordFilter {
+    /// Create a new filter matching all keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match keys updated strictly before a time.
+    pub fn with_before(mut self, before: DateTime<Utc>) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    /// Only match keys updated strictly after a time.
+    pub fn with_after(mut self, after: DateTime<Utc>) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Only match keys in one of these groups.
+    pub fn with_group_ids(
+        mut self,
+        group_ids: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.group_ids = Some(group_ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Return at most this many keys.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// Store recording which keys were written to a vector store, and when.
+///
+/// Records are scoped to a namespace, so several indexes can share a
+/// database. Comparing update times lets an indexing job find and delete
+/// documents that were not written again during the latest run.
+#[derive(Debug, Clone)]
+pub struct SqliteRecordStore {
+    pool: SqlitePool,
+    namespace: String,
+}
+
+impl SqliteRecordStore {
+    /// Create a new record store for a namespace, creating its table if needed.
+    pub fn new(pool: SqlitePool, namespace: impl Into<String>) -> Result<Self> {
+        pool.migrate("record_store", RECORD_STORE_MIGRATIONS)?;
+        Ok(Self {
+            pool,
+            namespace: namespace.into(),
+        })
+    }
+
+    /// Open a database file and create a record store for a namespace.
+    pub fn open(path: impl AsRef<Path>, namespace: impl Into<String>) -> Result<Self> {
+        Self::new(SqlitePool::open(path)?, namespace)
+    }
+
+    /// Get the namespace.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Get the current time of the store, as used for the update times.
+    pub fn get_time(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    /// Write or refresh records for keys.
+    ///
+    /// `group_ids` must be empty or have one entry per key. When
+    /// `time_at_least` is given and the clock is behind it, an error is
+    /// returned instead of writing records with a time in the past.
+    pub async fn update(
+        &self,
+        keys: &[String],
+        group_ids: &[Option<String>],
+        time_at_least: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        if !group_ids.is_empty() && group_ids.len() != keys.len() {
+            return Err(FerricLinkError::validation(format!(
+                "Got {} group IDs for {} keys",
+                group_ids.len(),
+                keys.len()
+            )));
+        }
+
+        let updated_at = now_micros();
+        if let Some(time_at_least) = time_at_least {
+            if updated_at < time_at_least.timestamp_micros() {
+                return Err(FerricLinkError::runtime(format!(
+                    "Time sync issue: current time {} is before {time_at_least}",
+                    from_micros(updated_at)
+                )));
+            }
+        }
+
+        let namespace = self.namespace.clone();
+        let records: Vec<(String, Option<String>)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.clone(), group_ids.get(i).cloned().flatten()))
+            .collect();
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO upsertion_record (namespace, key, group_id, updated_at)
+                         VALUES (?1, ?2, ?3, ?4)
+                         ON CONFLICT (namespace, key) DO UPDATE SET
+                            group_id = excluded.group_id,
+                            updated_at = excluded.updated_at",
+                    )?;
+                    for (key, group_id) in &records {
+                        stmt.execute(params![namespace, key, group_id, updated_at])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    /// Check which keys have a record, in the order of `keys`.
+    pub async fn exists(&self, keys: &[String]) -> Result<Vec<bool>> {
+        let namespace = self.namespace.clone();
+        let keys = keys.to_vec();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT EXISTS(SELECT 1 FROM upsertion_record WHERE namespace = ?1 AND key = ?2)",
+                )?;
+                keys.iter()
+                    .map(|key| Ok(stmt.query_row(params![namespace, key], |row| row.get(0))?))
+                    .collect()
+            })
+            .await
+    }
+
+    /// Get the records of keys, skipping keys without a record.
+    pub async fn get(&self, keys: &[String]) -> Result<Vec<Record>> {
+        let namespace = self.namespace.clone();
+        let keys = keys.to_vec();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT key, group_id, updated_at FROM upsertion_record WHERE namespace = ?1 AND key = ?2",
+                )?;
+                let mut records = Vec::new();
+                for key in &keys {
+                    if let Some(record) = stmt
+                        .query_row(params![namespace, key], Self::read_record)
+                        .optional()?
+                    {
+                        records.push(record);
+                    }
+                }
+                Ok(records)
+            })
+            .await
+    }
+
+    /// List the keys matching a filter, oldest update first.
+    pub async fn list_keys(&self, filter: RecordFilter) -> Result<Vec<String>> {
+        let namespace = self.namespace.clone();
+        self.pool
+            .run(move |conn| {
+                let mut sql = String::from("SELECT key FROM upsertion_record WHERE namespace = ?");
+                let mut values: Vec<rusqlite::types::Value> = vec![namespace.into()];
+                if let Some(before) = filter.before {
+                    sql.push_str(" AND updated_at < ?");
+                    values.push(before.timestamp_micros().into());
+                }
+                if let Some(after) = filter.after {
+                    sql.push_str(" AND updated_at > ?");
+                    values.push(after.timestamp_micros().into());
+                }
+                if let Some(group_ids) = filter.group_ids {
+                    let placeholders = vec!["?"; group_ids.len()].join(", ");
+                    sql.push_str(&format!(" AND group_id IN ({placeholders})"));
+                    values.extend(group_ids.into_iter().map(Into::into));
+                }
+                sql.push_str(" ORDER BY updated_at, key");
+                if let Some(limit) = filter.limit {
+                    sql.push_str(" LIMIT ?");
+                    values.push((limit as i64).into());
+                }
+
+                let mut stmt = conn.prepare(&sql)?;
+                let rows = stmt.query_map(rusqlite::params_from_iter(values), |row| row.get(0))?;
+                Ok(rows.collect::<rusqlite::Result<Vec<String>>>()?)
+            })
+            .await
+    }
+
+    /// Delete the records of keys.
+    pub async fn delete_keys(&self, keys: &[String]) -> Result<()> {
+        let namespace = self.namespace.clone();
+        let keys = keys.to_vec();
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "DELETE FROM upsertion_record WHERE namespace = ?1 AND key = ?2",
+                    )?;
+                    for key in &keys {
+                        stmt.execute(params![namespace, key])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    fn read_record(row: &rusqlite::Row<'_>) -> rusqlite::Result<Record> {
+        Ok(Record {
+            key: row.get(0)?,
+            group_id: row.get(1)?,
+            updated_at: from_micros(row.get(2)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language_models::Generation;
+    use crate::messages::BaseMessage;
+
+    fn generations(text: &str) -> CachedGenerations {
+        vec![Generation::new(text)]
+    }
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn test_migrations() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = SqlitePool::open(dir.path().join("db.sqlite")).unwrap();
+        assert_eq!(pool.schema_version("llm_cache").unwrap(), 0);
+
+        pool.migrate("llm_cache", &CACHE_MIGRATIONS[..1]).unwrap();
+        assert_eq!(pool.schema_version("llm_cache").unwrap(), 1);
+
+        // Upgrading applies only the missing migrations, and is idempotent
+        pool.migrate("llm_cache", CACHE_MIGRATIONS).unwrap();
+        pool.migrate("llm_cache", CACHE_MIGRATIONS).unwrap();
+        assert_eq!(pool.schema_version("llm_cache").unwrap(), 2);
+        assert_eq!(pool.schema_version("chat_history").unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_chat_history_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.sqlite");
+
+        let history = SqliteChatMessageHistory::open(&path, "s1").unwrap();
+        history.add_user_message("Hello").unwrap();
+        history
+            .aadd_messages(vec![AnyMessage::ai("Hi!")])
+            .await
+            .unwrap();
+        let other = SqliteChatMessageHistory::new(history.pool.clone(), "s2").unwrap();
+        other.add_user_message("Elsewhere").unwrap();
+        drop(history);
+
+        // A new pool sees the stored messages
+        let reopened = SqliteChatMessageHistory::open(&path, "s1").unwrap();
+        let texts: Vec<String> = reopened
+            .amessages()
+            .await
+            .unwrap()
+            .iter()
+            .map(|m| m.text())
+            .collect();
+        assert_eq!(texts, vec!["Hello", "Hi!"]);
+
+        reopened
+            .areplace_messages(vec![AnyMessage::system("Summary")])
+            .await
+            .unwrap();
+        assert_eq!(reopened.messages().unwrap().len(), 1);
+        assert_eq!(other.messages().unwrap().len(), 1);
+
+        // The oldest messages are only replaced while they are unchanged
+        let read = reopened.messages().unwrap();
+        reopened.add_user_message("Late").unwrap();
+        assert!(
+            reopened
+                .areplace_messages_if(&read, vec![AnyMessage::system("New")])
+                .await
+                .unwrap()
+        );
+        let texts: Vec<String> = reopened
+            .messages()
+            .unwrap()
+            .iter()
+            .map(|m| m.text())
+            .collect();
+        assert_eq!(texts, vec!["New", "Late"]);
+        assert!(
+            !reopened
+                .replace_messages_if(&read, vec![AnyMessage::system("Stale")])
+                .unwrap()
+        );
+        assert_eq!(other.messages().unwrap().len(), 1);
+
+        reopened.aclear().await.unwrap();
+        assert!(reopened.messages().unwrap().is_empty());
+        assert_eq!(other.messages().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.sqlite");
+
+        let cache = SqliteCache::open(&path).unwrap();
+        assert!(cache.alookup("prompt", "llm").await.unwrap().is_none());
+        cache
+            .aupdate("prompt", "llm", generations("answer"))
+            .await
+            .unwrap();
+        cache
+            .update("prompt", "other-llm", generations("other"))
+            .unwrap();
+
+        let reopened = SqliteCache::open(&path).unwrap();
+        assert_eq!(
+            reopened.lookup("prompt", "llm").unwrap(),
+            Some(generations("answer"))
+        );
+        assert_eq!(
+            reopened.alookup("prompt", "other-llm").await.unwrap(),
+            Some(generations("other"))
+        );
+
+        let stats = cache.stats().await.unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.updates), (0, 1, 2));
+        assert_eq!(stats.current_size, 2);
+
+        cache.aclear().await.unwrap();
+        assert_eq!(reopened.size().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cache_ttl_and_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = SqlitePool::open(dir.path().join("cache.sqlite")).unwrap();
+
+        let cache = SqliteCache::new(pool.clone())
+            .unwrap()
+            .with_ttl(Duration::from_millis(50));
+        cache.aupdate("a", "llm", generations("a")).await.unwrap();
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(cache.alookup("a", "llm").await.unwrap().is_none());
+        cache.aupdate("b", "llm", generations("b")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(cache.purge_expired().await.unwrap(), 1);
+
+        let cache = SqliteCache::new(pool.clone())
+            .unwrap()
+            .with_ttl(Duration::MAX);
+        cache.aupdate("a", "llm", generations("a")).await.unwrap();
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        assert_eq!(cache.purge_expired().await.unwrap(), 0);
+        cache.aclear().await.unwrap();
+
+        let cache = SqliteCache::new(pool).unwrap().with_max_size(2);
+        cache.aupdate("a", "llm", generations("a")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        cache.aupdate("b", "llm", generations("b")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        // Reading "a" makes "b" the least recently used entry
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        cache.aupdate("c", "llm", generations("c")).await.unwrap();
+
+        assert_eq!(cache.size().await.unwrap(), 2);
+        assert!(cache.alookup("a", "llm").await.unwrap().is_some());
+        assert!(cache.alookup("b", "llm").await.unwrap().is_none());
+        assert!(cache.alookup("c", "llm").await.unwrap().is_some());
+        assert_eq!(cache.stats().await.unwrap().evictions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_record_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = SqlitePool::open(dir.path().join("records.sqlite")).unwrap();
+        let store = SqliteRecordStore::new(pool.clone(), "docs").unwrap();
+        let other = SqliteRecordStore::new(pool, "other").unwrap();
+
+        store
+            .update(
+                &keys(&["a", "b"]),
+                &[Some("source-1".to_string()), Some("source-2".to_string())],
+                None,
+            )
+            .await
+            .unwrap();
+        other.update(&keys(&["a"]), &[], None).await.unwrap();
+
+        let checkpoint = store.get_time();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        store
+            .update(
+                &keys(&["c"]),
+                &[Some("source-1".to_string())],
+                Some(checkpoint),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.exists(&keys(&["a", "x", "c"])).await.unwrap(),
+            vec![true, false, true]
+        );
+        assert_eq!(
+            store
+                .list_keys(RecordFilter::new().with_before(checkpoint))
+                .await
+                .unwrap(),
+            keys(&["a", "b"])
+        );
+        assert_eq!(
+            store
+                .list_keys(RecordFilter::new().with_after(checkpoint))
+                .await
+                .unwrap(),
+            keys(&["c"])
+        );
+        assert_eq!(
+            store
+                .list_keys(
+                    RecordFilter::new()
+                        .with_group_ids(["source-1"])
+                        .with_limit(1)
+                )
+                .await
+                .unwrap(),
+            keys(&["a"])
+        );
+
+        let records = store.get(&keys(&["c", "x"])).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].group_id.as_deref(), Some("source-1"));
+
+        store.delete_keys(&keys(&["a", "b"])).await.unwrap();
+        assert_eq!(
+            store.list_keys(RecordFilter::new()).await.unwrap(),
+            keys(&["c"])
+        );
+        assert_eq!(
+            other.list_keys(RecordFilter::new()).await.unwrap(),
+            keys(&["a"])
+        );
+
+        assert!(
+            store
+                .update(&keys(&["a"]), &[None, None], None)
+                .await
+                .is_err()
+        );
+        let future = Utc::now() + chrono::Duration::hours(1);
+        assert!(
+            store
+                .update(&keys(&["a"]), &[], Some(future))
+                .await
+                .is_err()
+        );
+    }
+}
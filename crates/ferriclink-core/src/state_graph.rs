@@ -0,0 +1,1165 @@
+//! Stateful graph execution for FerricLink Core
+//!
+//! This module provides [`StateGraph`], a LangGraph-like engine for building
+//! agent workflows out of runnables. Nodes read a typed shared state and
+//! return partial updates, which are merged into the state with per-key
+//! reducers. Execution proceeds in steps, can loop, can pause before or after
+//! given nodes, and can be checkpointed to resume later.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::errors::{FerricLinkError, Result};
+use crate::graph::{Edge, Graph, Node};
+use crate::impl_serializable;
+use crate::runnables::{Runnable, RunnableConfig, merge_config_schemas, run_with_callbacks};
+
+/// Name of the virtual node where execution starts
+pub const START: &str = "__start__";
+
+/// Name of the virtual node where execution ends
+pub const END: &str = "__end__";
+
+/// Key of the thread ID in [`RunnableConfig::configurable`]
+pub const THREAD_ID_KEY: &str = "thread_id";
+
+/// Default maximum number of steps of a graph run
+pub const DEFAULT_RECURSION_LIMIT: usize = 25;
+
+/// Function merging an update into the current value of a state key
+pub type Reducer =
+    Arc<dyn Fn(serde_json::Value, serde_json::Value) -> Result<serde_json::Value> + Send + Sync>;
+
+/// Reducer replacing the current value with the update
+pub fn replace_reducer(
+    _current: serde_json::Value,
+    update: serde_json::Value,
+) -> Result<serde_json::Value> {
+    Ok(update)
+}
+
+/// Reducer appending the update to the current array
+///
+/// Array updates are concatenated, other updates are pushed as one element.
+pub fn append_reducer(
+    current: serde_json::Value,
+    update: serde_json::Value,
+) -> Result<serde_json::Value> {
+    let mut items = match current {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Null => Vec::new(),
+        other => {
+            return Err(FerricLinkError::validation(format!(
+                "Cannot append to non-array state value {other}"
+            )));
+        }
+    };
+    match update {
+        serde_json::Value::Array(update) => items.extend(update),
+        update => items.push(update),
+    }
+    Ok(serde_json::Value::Array(items))
+}
+
+/// A conditional edge routing to the next node based on the state
+struct ConditionalEdge<S> {
+    source: String,
+    router: Arc<dyn Runnable<S, String>>,
+    path_map: HashMap<String, String>,
+}
+
+impl<S> ConditionalEdge<S> {
+    /// Get the node a router output leads to
+    fn target(&self, key: &str) -> Result<String> {
+        if self.path_map.is_empty() {
+            return Ok(key.to_string());
+        }
+        self.path_map.get(key).cloned().ok_or_else(|| {
+            FerricLinkError::runtime(format!(
+                "Router of node '{}' returned unknown route '{key}'",
+                self.source
+            ))
+        })
+    }
+}
+
+/// Builder for a graph of runnables operating on a shared state
+///
+/// Nodes receive the current state and return a JSON object with the keys to
+/// update. Updates are merged with the reducer registered for each key, or
+/// replace the previous value otherwise.
+pub struct StateGraph<S> {
+    nodes: Vec<(String, Arc<dyn Runnable<S, serde_json::Value>>)>,
+    edges: Vec<(String, String)>,
+    conditional_edges: Vec<ConditionalEdge<S>>,
+    reducers: HashMap<String, Reducer>,
+}
+
+impl<S> StateGraph<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    /// Create a new empty state graph
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            conditional_edges: Vec::new(),
+            reducers: HashMap::new(),
+        }
+    }
+
+    /// Add a node
+    pub fn with_node(
+        mut self,
+        name: impl Into<String>,
+        runnable: Arc<dyn Runnable<S, serde_json::Value>>,
+    ) -> Self {
+        self.add_node(name, runnable);
+        self
+    }
+
+    /// Add a node
+    pub fn add_node(
+        &mut self,
+        name: impl Into<String>,
+        runnable: Arc<dyn Runnable<S, serde_json::Value>>,
+    ) {
+        self.nodes.push((name.into(), runnable));
+    }
+
+    /// Add an edge, which may start at [`START`] or end at [`END`]
+    pub fn with_edge(mut self, source: impl Into<String>, target: impl Into<String>) -> Self {
+        self.add_edge(source, target);
+        self
+    }
+
+    /// Add an edge, which may start at [`START`] or end at [`END`]
+    pub fn add_edge(&mut self, source: impl Into<String>, target: impl Into<String>) {
+        self.edges.push((source.into(), target.into()));
+    }
+
+    /// Add conditional edges leaving a node
+    ///
+    /// After the source node runs, the router picks a route from the updated
+    /// state. The route is looked up in `path_map`, or used as the target node
+    /// name if the map is empty.
+    pub fn with_conditional_edges<I, K, T>(
+        mut self,
+        source: impl Into<String>,
+        router: Arc<dyn Runnable<S, String>>,
+        path_map: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (K, T)>,
+        K: Into<String>,
+        T: Into<String>,
+    {
+        self.add_conditional_edges(source, router, path_map);
+        self
+    }
+
+    /// Add conditional edges leaving a node
+    pub fn add_conditional_edges<I, K, T>(
+        &mut self,
+        source: impl Into<String>,
+        router: Arc<dyn Runnable<S, String>>,
+        path_map: I,
+    ) where
+        I: IntoIterator<Item = (K, T)>,
+        K: Into<String>,
+        T: Into<String>,
+    {
+        self.conditional_edges.push(ConditionalEdge {
+            source: source.into(),
+            router,
+            path_map: path_map
+                .into_iter()
+                .map(|(key, target)| (key.into(), target.into()))
+                .collect(),
+        });
+    }
+
+    /// Set the reducer merging updates of a state key
+    pub fn with_reducer<F>(mut self, key: impl Into<String>, reducer: F) -> Self
+    where
+        F: Fn(serde_json::Value, serde_json::Value) -> Result<serde_json::Value>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.reducers.insert(key.into(), Arc::new(reducer));
+        self
+    }
+
+    /// Validate the graph and compile it into a runnable
+    pub fn compile(self) -> Result<CompiledStateGraph<S>> {
+        let mut names = HashSet::new();
+        for (name, _) in &self.nodes {
+            if name == START || name == END {
+                return Err(FerricLinkError::validation(format!(
+                    "Node name '{name}' is reserved"
+                )));
+            }
+            if !names.insert(name.as_str()) {
+                return Err(FerricLinkError::validation(format!(
+                    "Duplicate node '{name}'"
+                )));
+            }
+        }
+
+        let check_source = |source: &str| {
+            if source == START || names.contains(source) {
+                Ok(())
+            } else {
+                Err(FerricLinkError::validation(format!(
+                    "Edge starts at unknown node '{source}'"
+                )))
+            }
+        };
+        let check_target = |target: &str| {
+            if target == END || names.contains(target) {
+                Ok(())
+            } else {
+                Err(FerricLinkError::validation(format!(
+                    "Edge ends at unknown node '{target}'"
+                )))
+            }
+        };
+        for (source, target) in &self.edges {
+            check_source(source)?;
+            check_target(target)?;
+        }
+        for edge in &self.conditional_edges {
+            check_source(&edge.source)?;
+            for target in edge.path_map.values() {
+                check_target(target)?;
+            }
+        }
+
+        let has_entry = self.edges.iter().any(|(source, _)| source == START)
+            || self.conditional_edges.iter().any(|e| e.source == START);
+        if !has_entry {
+            return Err(FerricLinkError::validation(
+                "Graph has no entry point, add an edge from START",
+            ));
+        }
+
+        Ok(CompiledStateGraph {
+            graph: Arc::new(self),
+            checkpointer: None,
+            interrupt_before: HashSet::new(),
+            interrupt_after: HashSet::new(),
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+        })
+    }
+
+    fn node(&self, name: &str) -> Option<&Arc<dyn Runnable<S, serde_json::Value>>> {
+        self.nodes.iter().find(|(n, _)| n == name).map(|(_, r)| r)
+    }
+}
+
+impl<S> Default for StateGraph<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A snapshot of a graph run, saved after every step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// ID of the thread the run belongs to
+    pub thread_id: String,
+    /// Number of steps completed
+    pub step: usize,
+    /// State after the step
+    pub state: serde_json::Value,
+    /// Nodes to run next; empty once the run has finished
+    pub next: Vec<String>,
+    /// Whether the run paused before running `next`, rather than after the step
+    #[serde(default)]
+    pub paused_before: bool,
+    /// When the checkpoint was created
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Checkpoint {
+    /// Create a new checkpoint
+    pub fn new(
+        thread_id: impl Into<String>,
+        step: usize,
+        state: serde_json::Value,
+        next: Vec<String>,
+    ) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            step,
+            state,
+            next,
+            paused_before: false,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Record whether the run paused before running `next`
+    pub fn with_paused_before(mut self, paused_before: bool) -> Self {
+        self.paused_before = paused_before;
+        self
+    }
+
+    /// Deserialize the saved state
+    pub fn state<S: DeserializeOwned>(&self) -> Result<S> {
+        Ok(serde_json::from_value(self.state.clone())?)
+    }
+
+    /// Check if the run is paused before reaching the end
+    pub fn is_interrupted(&self) -> bool {
+        !self.next.is_empty()
+    }
+}
+
+impl_serializable!(Checkpoint, ["ferriclink", "state_graph", "checkpoint"]);
+
+/// Storage for the checkpoints of graph runs
+#[async_trait]
+pub trait Checkpointer: Send + Sync {
+    /// Save a checkpoint
+    async fn put(&self, checkpoint: Checkpoint) -> Result<()>;
+
+    /// Get the latest checkpoint of a thread
+    async fn get(&self, thread_id: &str) -> Result<Option<Checkpoint>>;
+
+    /// List all checkpoints of a thread, oldest first
+    async fn list(&self, thread_id: &str) -> Result<Vec<Checkpoint>>;
+
+    /// Delete all checkpoints of a thread
+    async fn delete(&self, thread_id: &str) -> Result<()>;
+}
+
+/// A checkpointer keeping checkpoints in memory
+#[derive(Default)]
+pub struct InMemoryCheckpointer {
+    threads: RwLock<HashMap<String, Vec<Checkpoint>>>,
+}
+
+impl InMemoryCheckpointer {
+    /// Create a new in-memory checkpointer
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Checkpointer for InMemoryCheckpointer {
+    async fn put(&self, checkpoint: Checkpoint) -> Result<()> {
+        let mut threads = self.threads.write().await;
+        threads
+            .entry(checkpoint.thread_id.clone())
+            .or_default()
+            .push(checkpoint);
+        Ok(())
+    }
+
+    async fn get(&self, thread_id: &str) -> Result<Option<Checkpoint>> {
+        let threads = self.threads.read().await;
+        Ok(threads.get(thread_id).and_then(|c| c.last().cloned()))
+    }
+
+    async fn list(&self, thread_id: &str) -> Result<Vec<Checkpoint>> {
+        let threads = self.threads.read().await;
+        Ok(threads.get(thread_id).cloned().unwrap_or_default())
+    }
+
+    async fn delete(&self, thread_id: &str) -> Result<()> {
+        self.threads.write().await.remove(thread_id);
+        Ok(())
+    }
+}
+
+/// A checkpointer appending checkpoints to one JSON lines file per thread
+pub struct FileCheckpointer {
+    directory: PathBuf,
+}
+
+impl FileCheckpointer {
+    /// Create a new file checkpointer storing threads in a directory
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Get the directory the checkpoints are stored in
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn path(&self, thread_id: &str) -> Result<PathBuf> {
+        if !crate::utils::is_safe_file_stem(thread_id) {
+            return Err(FerricLinkError::validation(format!(
+                "Invalid thread ID '{thread_id}', only ASCII letters, digits, '-' and '_' are allowed"
+            )));
+        }
+        Ok(self.directory.join(format!("{thread_id}.jsonl")))
+    }
+}
+
+#[async_trait]
+impl Checkpointer for FileCheckpointer {
+    async fn put(&self, checkpoint: Checkpoint) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let path = self.path(&checkpoint.thread_id)?;
+        let mut line = serde_json::to_string(&checkpoint)?;
+        line.push('\n');
+
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn get(&self, thread_id: &str) -> Result<Option<Checkpoint>> {
+        Ok(self.list(thread_id).await?.pop())
+    }
+
+    async fn list(&self, thread_id: &str) -> Result<Vec<Checkpoint>> {
+        let path = self.path(thread_id)?;
+        let content = match tokio::fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    async fn delete(&self, thread_id: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(thread_id)?).await {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A compiled state graph, runnable on an initial state
+///
+/// When a checkpointer is attached, runs are identified by the `thread_id`
+/// configurable value and the state is saved after every step, so interrupted
+/// runs can be inspected, updated and resumed.
+pub struct CompiledStateGraph<S> {
+    graph: Arc<StateGraph<S>>,
+    checkpointer: Option<Arc<dyn Checkpointer>>,
+    interrupt_before: HashSet<String>,
+    interrupt_after: HashSet<String>,
+    recursion_limit: usize,
+}
+
+impl<S> CompiledStateGraph<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    /// Set the checkpointer saving the state after every step
+    pub fn with_checkpointer(mut self, checkpointer: Arc<dyn Checkpointer>) -> Self {
+        self.checkpointer = Some(checkpointer);
+        self
+    }
+
+    /// Pause runs before any of the given nodes
+    pub fn with_interrupt_before<I, N>(mut self, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        self.interrupt_before
+            .extend(nodes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Pause runs after any of the given nodes
+    pub fn with_interrupt_after<I, N>(mut self, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        self.interrupt_after
+            .extend(nodes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set the maximum number of steps of a run
+    pub fn with_recursion_limit(mut self, recursion_limit: usize) -> Self {
+        self.recursion_limit = recursion_limit;
+        self
+    }
+
+    /// Get the latest checkpoint of a thread
+    pub async fn get_state(&self, thread_id: &str) -> Result<Option<Checkpoint>> {
+        self.checkpointer()?.get(thread_id).await
+    }
+
+    /// Merge an update into the saved state of a thread, as if a node returned it
+    pub async fn update_state(
+        &self,
+        thread_id: &str,
+        update: serde_json::Value,
+    ) -> Result<Checkpoint> {
+        let checkpointer = self.checkpointer()?;
+        let checkpoint = checkpointer.get(thread_id).await?.ok_or_else(|| {
+            FerricLinkError::validation(format!("No checkpoint for thread '{thread_id}'"))
+        })?;
+
+        let state = self.apply_update(checkpoint.state, update)?;
+        let checkpoint = Checkpoint::new(thread_id, checkpoint.step, state, checkpoint.next)
+            .with_paused_before(checkpoint.paused_before);
+        checkpointer.put(checkpoint.clone()).await?;
+        Ok(checkpoint)
+    }
+
+    /// Resume the interrupted run of the thread set in the configuration
+    pub async fn resume(&self, mut config: RunnableConfig) -> Result<S> {
+        config.validate_configurable(|| self.config_schema())?;
+        let thread_id = thread_id(&config).ok_or_else(|| {
+            FerricLinkError::configuration("Resuming a graph run requires a thread_id")
+        })?;
+        let checkpoint = self.get_state(&thread_id).await?.ok_or_else(|| {
+            FerricLinkError::validation(format!("No checkpoint for thread '{thread_id}'"))
+        })?;
+        // The checkpoint may have been saved by another version of the graph
+        if let Some(unknown) = checkpoint
+            .next
+            .iter()
+            .find(|name| self.graph.node(name).is_none())
+        {
+            return Err(FerricLinkError::validation(format!(
+                "Checkpoint of thread '{thread_id}' continues with unknown node '{unknown}'"
+            )));
+        }
+
+        let output = run_with_callbacks(
+            Some(config),
+            self.name(),
+            "chain",
+            checkpoint.state.clone(),
+            |state: &serde_json::Value| state.clone(),
+            |run_config| {
+                let Checkpoint {
+                    state,
+                    next,
+                    step,
+                    paused_before,
+                    ..
+                } = checkpoint;
+                self.run(state, next, step, Some(paused_before), run_config)
+            },
+        )
+        .await?;
+        Ok(serde_json::from_value(output)?)
+    }
+
+    fn checkpointer(&self) -> Result<&Arc<dyn Checkpointer>> {
+        self.checkpointer
+            .as_ref()
+            .ok_or_else(|| FerricLinkError::configuration("Graph has no checkpointer"))
+    }
+
+    /// Merge a node update into the state with the registered reducers
+    fn apply_update(
+        &self,
+        state: serde_json::Value,
+        update: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let serde_json::Value::Object(mut state) = state else {
+            return Err(FerricLinkError::validation(
+                "Graph state must serialize to a JSON object",
+            ));
+        };
+        let update = match update {
+            serde_json::Value::Object(update) => update,
+            serde_json::Value::Null => return Ok(serde_json::Value::Object(state)),
+            other => {
+                return Err(FerricLinkError::validation(format!(
+                    "State update must be a JSON object, got {other}"
+                )));
+            }
+        };
+
+        for (key, value) in update {
+            let value = match self.graph.reducers.get(&key) {
+                Some(reducer) => {
+                    let current = state.remove(&key).unwrap_or_default();
+                    reducer(current, value)?
+                }
+                None => value,
+            };
+            state.insert(key, value);
+        }
+
+        // Round-trip through the typed state to reject invalid updates
+        let state: S = serde_json::from_value(serde_json::Value::Object(state))
+            .map_err(|e| FerricLinkError::validation(format!("Invalid state update: {e}")))?;
+        Ok(serde_json::to_value(state)?)
+    }
+
+    /// Get the nodes following the nodes of a step
+    async fn next_nodes(
+        &self,
+        ran: &[String],
+        state: &S,
+        config: &RunnableConfig,
+    ) -> Result<Vec<String>> {
+        let mut next = Vec::new();
+        let mut push = |target: String| {
+            if target != END && !next.contains(&target) {
+                next.push(target);
+            }
+        };
+
+        for source in ran {
+            for (from, to) in &self.graph.edges {
+                if from == source {
+                    push(to.clone());
+                }
+            }
+            for edge in &self.graph.conditional_edges {
+                if &edge.source == source {
+                    let route = edge
+                        .router
+                        .invoke(state.clone(), Some(config.child()))
+                        .await?;
+                    let target = edge.target(&route)?;
+                    if target != END && self.graph.node(&target).is_none() {
+                        return Err(FerricLinkError::runtime(format!(
+                            "Router of node '{source}' returned unknown node '{target}'"
+                        )));
+                    }
+                    push(target);
+                }
+            }
+        }
+        Ok(next)
+    }
+
+    /// Run steps until the graph ends or is interrupted, returning the final state
+    ///
+    /// `resumed` is `None` for a new run, and whether the checkpoint paused
+    /// before `next` for a resumed one.
+    async fn run(
+        &self,
+        mut state: serde_json::Value,
+        mut next: Vec<String>,
+        mut step: usize,
+        resumed: Option<bool>,
+        config: RunnableConfig,
+    ) -> Result<serde_json::Value> {
+        let thread_id = thread_id(&config);
+        let checkpointer = match (&self.checkpointer, &thread_id) {
+            (Some(checkpointer), Some(thread_id)) => Some((checkpointer, thread_id.as_str())),
+            _ => None,
+        };
+        let interrupts = !self.interrupt_before.is_empty() || !self.interrupt_after.is_empty();
+        if interrupts && checkpointer.is_none() {
+            return Err(FerricLinkError::configuration(
+                "Interrupts require a checkpointer and a thread_id",
+            ));
+        }
+
+        let pauses_before =
+            |next: &[String]| next.iter().any(|n| self.interrupt_before.contains(n));
+
+        // Whether the latest checkpoint records a pause before `next`
+        let mut checkpoint_paused_before = match resumed {
+            Some(paused_before) => paused_before,
+            None => {
+                // Save the input so runs interrupted before their first step can resume
+                let paused_before = pauses_before(&next);
+                if let Some((checkpointer, thread_id)) = checkpointer {
+                    let checkpoint = Checkpoint::new(thread_id, step, state.clone(), next.clone())
+                        .with_paused_before(paused_before);
+                    checkpointer.put(checkpoint).await?;
+                }
+                paused_before
+            }
+        };
+
+        // Nodes a resumed run was paused before are run without pausing again
+        let mut skip_interrupt_before = resumed == Some(true);
+        while !next.is_empty() {
+            config.check_cancelled()?;
+
+            if !skip_interrupt_before && pauses_before(&next) {
+                // A run resumed after a step now pauses before the next one
+                if let (Some((checkpointer, thread_id)), false) =
+                    (checkpointer, checkpoint_paused_before)
+                {
+                    let checkpoint = Checkpoint::new(thread_id, step, state.clone(), next.clone())
+                        .with_paused_before(true);
+                    checkpointer.put(checkpoint).await?;
+                }
+                break;
+            }
+            skip_interrupt_before = false;
+
+            if step >= self.recursion_limit {
+                return Err(FerricLinkError::runtime(format!(
+                    "Recursion limit of {} steps reached without hitting END",
+                    self.recursion_limit
+                )));
+            }
+
+            let typed: S = serde_json::from_value(state.clone())?;
+            let runs = next.iter().map(|name| {
+                let runnable = self
+                    .graph
+                    .node(name)
+                    .expect("next nodes are validated when routed or resumed");
+                let node_config = config.child().with_tag(format!("graph:node:{name}"));
+                runnable.invoke(typed.clone(), Some(node_config))
+            });
+            let updates = futures::future::try_join_all(runs).await?;
+            for update in updates {
+                state = self.apply_update(state, update)?;
+            }
+            step += 1;
+
+            let ran = std::mem::take(&mut next);
+            let typed: S = serde_json::from_value(state.clone())?;
+            next = self.next_nodes(&ran, &typed, &config).await?;
+
+            let paused_after = ran.iter().any(|n| self.interrupt_after.contains(n));
+            checkpoint_paused_before = !paused_after && pauses_before(&next);
+            if let Some((checkpointer, thread_id)) = checkpointer {
+                let checkpoint = Checkpoint::new(thread_id, step, state.clone(), next.clone())
+                    .with_paused_before(checkpoint_paused_before);
+                checkpointer.put(checkpoint).await?;
+            }
+
+            if paused_after {
+                break;
+            }
+        }
+        Ok(state)
+    }
+}
+
+/// Get the thread ID set in a configuration
+fn thread_id(config: &RunnableConfig) -> Option<String> {
+    config
+        .configurable
+        .get(THREAD_ID_KEY)
+        .and_then(|id| id.as_str())
+        .map(str::to_string)
+}
+
+#[async_trait]
+impl<S> Runnable<S, S> for CompiledStateGraph<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    async fn invoke(&self, input: S, mut config: Option<RunnableConfig>) -> Result<S> {
+        if let Some(config) = &mut config {
+            config.validate_configurable(|| self.config_schema())?;
+        }
+        let state = serde_json::to_value(&input)?;
+        let output = run_with_callbacks(
+            config,
+            self.name(),
+            "chain",
+            state.clone(),
+            |state: &serde_json::Value| state.clone(),
+            |run_config| async move {
+                let typed: S = serde_json::from_value(state.clone())?;
+                let next = self
+                    .next_nodes(&[START.to_string()], &typed, &run_config)
+                    .await?;
+                self.run(state, next, 0, None, run_config).await
+            },
+        )
+        .await?;
+        Ok(serde_json::from_value(output)?)
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        let thread = self.checkpointer.as_ref().map(|_| {
+            serde_json::json!({
+                "properties": {
+                    THREAD_ID_KEY: {
+                        "type": "string",
+                        "description": "ID of the thread whose state is checkpointed",
+                    },
+                },
+            })
+        });
+        merge_config_schemas(
+            std::iter::once(thread)
+                .chain(self.graph.nodes.iter().map(|(_, r)| r.config_schema()))
+                .chain(
+                    self.graph
+                        .conditional_edges
+                        .iter()
+                        .map(|e| e.router.config_schema()),
+                ),
+        )
+    }
+
+    fn get_graph(&self) -> Graph {
+        let mut graph = Graph::new();
+        graph.add_node(Node::new(START));
+        for (name, runnable) in &self.graph.nodes {
+            graph.add_node(
+                Node::new(name.clone())
+                    .with_input_schema(runnable.input_schema())
+                    .with_output_schema(runnable.output_schema()),
+            );
+        }
+        graph.add_node(Node::new(END));
+
+        for (source, target) in &self.graph.edges {
+            graph.add_edge(Edge::new(source.clone(), target.clone()));
+        }
+        for edge in &self.graph.conditional_edges {
+            if edge.path_map.is_empty() {
+                // Any node may be routed to
+                for (name, _) in &self.graph.nodes {
+                    graph.add_edge(
+                        Edge::new(edge.source.clone(), name.clone()).with_conditional(true),
+                    );
+                }
+                graph.add_edge(Edge::new(edge.source.clone(), END).with_conditional(true));
+            } else {
+                let mut routes: Vec<_> = edge.path_map.iter().collect();
+                routes.sort();
+                for (key, target) in routes {
+                    graph.add_edge(
+                        Edge::new(edge.source.clone(), target.clone())
+                            .with_label(key.clone())
+                            .with_conditional(true),
+                    );
+                }
+            }
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runnables::runnable;
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    struct AgentState {
+        messages: Vec<String>,
+        count: i32,
+    }
+
+    fn counter_graph() -> StateGraph<AgentState> {
+        StateGraph::new()
+            .with_reducer("messages", append_reducer)
+            .with_node(
+                "agent",
+                runnable(|state: AgentState| {
+                    Ok(serde_json::json!({
+                        "messages": format!("agent {}", state.count),
+                        "count": state.count + 1,
+                    }))
+                }),
+            )
+            .with_edge(START, "agent")
+            .with_conditional_edges(
+                "agent",
+                runnable(|state: AgentState| {
+                    Ok(if state.count < 3 { "again" } else { "done" }.to_string())
+                }),
+                [("again", "agent"), ("done", END)],
+            )
+    }
+
+    fn tool_graph() -> StateGraph<AgentState> {
+        StateGraph::new()
+            .with_reducer("messages", append_reducer)
+            .with_node(
+                "agent",
+                runnable(|_: AgentState| Ok(serde_json::json!({"messages": ["call tool"]}))),
+            )
+            .with_node(
+                "tools",
+                runnable(|state: AgentState| {
+                    Ok(serde_json::json!({"messages": [format!("tool ran after {} messages", state.messages.len())]}))
+                }),
+            )
+            .with_edge(START, "agent")
+            .with_edge("agent", "tools")
+            .with_edge("tools", END)
+    }
+
+    fn thread(id: &str) -> RunnableConfig {
+        RunnableConfig::new().with_configurable(THREAD_ID_KEY, serde_json::json!(id))
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_cycle_with_reducers() {
+        let graph = counter_graph().compile().unwrap();
+
+        let state = graph.invoke_simple(AgentState::default()).await.unwrap();
+        assert_eq!(state.count, 3);
+        assert_eq!(state.messages, vec!["agent 0", "agent 1", "agent 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_parallel_nodes() {
+        let graph = StateGraph::new()
+            .with_reducer("messages", append_reducer)
+            .with_node(
+                "a",
+                runnable(|_: AgentState| Ok(serde_json::json!({"messages": ["a"]}))),
+            )
+            .with_node(
+                "b",
+                runnable(|_: AgentState| Ok(serde_json::json!({"messages": ["b"], "count": 1}))),
+            )
+            .with_edge(START, "a")
+            .with_edge(START, "b")
+            .with_edge("a", END)
+            .with_edge("b", END)
+            .compile()
+            .unwrap();
+
+        let state = graph.invoke_simple(AgentState::default()).await.unwrap();
+        assert_eq!(state.messages, vec!["a", "b"]);
+        assert_eq!(state.count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_recursion_limit() {
+        let graph = StateGraph::new()
+            .with_node(
+                "loop",
+                runnable(|state: AgentState| Ok(serde_json::json!({"count": state.count + 1}))),
+            )
+            .with_edge(START, "loop")
+            .with_edge("loop", "loop")
+            .compile()
+            .unwrap()
+            .with_recursion_limit(5);
+
+        let error = graph
+            .invoke_simple(AgentState::default())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Recursion limit of 5"));
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_invalid_update() {
+        let graph = StateGraph::new()
+            .with_node(
+                "bad",
+                runnable(|_: AgentState| Ok(serde_json::json!({"count": "many"}))),
+            )
+            .with_edge(START, "bad")
+            .compile()
+            .unwrap();
+
+        let error = graph
+            .invoke_simple(AgentState::default())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Invalid state update"));
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_compile_errors() {
+        let unknown = StateGraph::<AgentState>::new()
+            .with_edge(START, "missing")
+            .compile();
+        assert!(unknown.is_err());
+
+        let no_entry = StateGraph::<AgentState>::new()
+            .with_node("a", runnable(|_: AgentState| Ok(serde_json::Value::Null)))
+            .compile();
+        assert!(no_entry.is_err());
+
+        let reserved = StateGraph::<AgentState>::new()
+            .with_node(END, runnable(|_: AgentState| Ok(serde_json::Value::Null)))
+            .with_edge(START, END)
+            .compile();
+        assert!(reserved.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_interrupt_and_resume() {
+        let checkpointer = Arc::new(InMemoryCheckpointer::new());
+        let graph = tool_graph()
+            .compile()
+            .unwrap()
+            .with_checkpointer(checkpointer.clone())
+            .with_interrupt_before(["tools"]);
+
+        let state = graph
+            .invoke(AgentState::default(), Some(thread("t1")))
+            .await
+            .unwrap();
+        assert_eq!(state.messages, vec!["call tool"]);
+
+        let checkpoint = graph.get_state("t1").await.unwrap().unwrap();
+        assert!(checkpoint.is_interrupted());
+        assert_eq!(checkpoint.next, vec!["tools"]);
+        assert_eq!(checkpoint.step, 1);
+        assert!(checkpoint.paused_before);
+
+        // A human approves the call before the tool runs
+        graph
+            .update_state("t1", serde_json::json!({"messages": ["approved"]}))
+            .await
+            .unwrap();
+
+        let state = graph.resume(thread("t1")).await.unwrap();
+        assert_eq!(
+            state.messages,
+            vec!["call tool", "approved", "tool ran after 2 messages"]
+        );
+        assert!(
+            !graph
+                .get_state("t1")
+                .await
+                .unwrap()
+                .unwrap()
+                .is_interrupted()
+        );
+        assert_eq!(checkpointer.list("t1").await.unwrap().len(), 4);
+
+        // Interrupts need a thread to checkpoint to
+        assert!(graph.invoke_simple(AgentState::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_resume_after_step_pauses_before_next() {
+        let graph = tool_graph()
+            .compile()
+            .unwrap()
+            .with_checkpointer(Arc::new(InMemoryCheckpointer::new()))
+            .with_interrupt_after(["agent"])
+            .with_interrupt_before(["tools"]);
+
+        graph
+            .invoke(AgentState::default(), Some(thread("t1")))
+            .await
+            .unwrap();
+        let checkpoint = graph.get_state("t1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.next, vec!["tools"]);
+        assert!(!checkpoint.paused_before);
+
+        // Resuming after "agent" still pauses before "tools"
+        let state = graph.resume(thread("t1")).await.unwrap();
+        assert_eq!(state.messages, vec!["call tool"]);
+        let checkpoint = graph.get_state("t1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.next, vec!["tools"]);
+        assert!(checkpoint.paused_before);
+
+        let state = graph.resume(thread("t1")).await.unwrap();
+        assert_eq!(
+            state.messages,
+            vec!["call tool", "tool ran after 1 messages"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_interrupt_after_with_file_checkpointer() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let graph = tool_graph()
+            .compile()
+            .unwrap()
+            .with_checkpointer(Arc::new(FileCheckpointer::new(dir.path())))
+            .with_interrupt_after(["agent"]);
+        graph
+            .invoke(AgentState::default(), Some(thread("session-1")))
+            .await
+            .unwrap();
+
+        // A new graph over the same directory resumes after a restart
+        let restarted = tool_graph()
+            .compile()
+            .unwrap()
+            .with_checkpointer(Arc::new(FileCheckpointer::new(dir.path())))
+            .with_interrupt_after(["agent"]);
+        let checkpoint = restarted.get_state("session-1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.next, vec!["tools"]);
+        assert_eq!(
+            checkpoint.state::<AgentState>().unwrap().messages,
+            vec!["call tool"]
+        );
+
+        let state = restarted.resume(thread("session-1")).await.unwrap();
+        assert_eq!(
+            state.messages,
+            vec!["call tool", "tool ran after 1 messages"]
+        );
+
+        let checkpointer = FileCheckpointer::new(dir.path());
+        // Checkpoints of a graph whose nodes were renamed cannot be resumed
+        checkpointer
+            .put(Checkpoint::new(
+                "session-2",
+                1,
+                serde_json::to_value(AgentState::default()).unwrap(),
+                vec!["tools".to_string()],
+            ))
+            .await
+            .unwrap();
+        let renamed = counter_graph()
+            .compile()
+            .unwrap()
+            .with_checkpointer(Arc::new(FileCheckpointer::new(dir.path())));
+        let error = renamed.resume(thread("session-2")).await.unwrap_err();
+        assert!(error.to_string().contains("unknown node 'tools'"));
+
+        assert!(checkpointer.get("../escape").await.is_err());
+        checkpointer.delete("session-1").await.unwrap();
+        assert!(checkpointer.get("session-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_state_graph_config_schema_and_graph() {
+        let graph = counter_graph()
+            .compile()
+            .unwrap()
+            .with_checkpointer(Arc::new(InMemoryCheckpointer::new()));
+
+        let schema = graph.config_schema().unwrap();
+        assert_eq!(schema["properties"][THREAD_ID_KEY]["type"], "string");
+
+        let config = thread("t").with_configurable("unknown", serde_json::json!(1));
+        assert!(
+            graph
+                .invoke(AgentState::default(), Some(config))
+                .await
+                .is_err()
+        );
+
+        let drawn = graph.get_graph();
+        let names: Vec<_> = drawn.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec![START, "agent", END]);
+        let mermaid = drawn.draw_mermaid();
+        assert!(mermaid.contains("n0 --> n1;"));
+        assert!(mermaid.contains("n1 -. \"again\" .-> n1;"));
+        assert!(mermaid.contains("n1 -. \"done\" .-> n2;"));
+        assert!(drawn.draw_ascii().contains("agent --> agent (again)"));
+    }
+}
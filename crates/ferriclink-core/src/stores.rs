@@ -0,0 +1,579 @@
+//! Key-value stores for FerricLink Core
+//!
+//! This module provides the [`BaseStore`] abstraction used to persist data
+//! such as cached embeddings, with implementations keeping values in memory,
+//! on the local file system, or encoded on top of another store.
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::errors::{FerricLinkError, Result};
+use crate::serializable::Serializable;
+
+/// Base trait for key-value stores
+///
+/// The sync methods must be implemented; the async variants default to
+/// calling them.
+#[async_trait]
+pub trait BaseStore<K, V>: Send + Sync
+where
+    K: Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    /// Get the values of the given keys, `None` for missing keys
+    fn mget(&self, keys: &[K]) -> Result<Vec<Option<V>>>;
+
+    /// Set the values of the given keys
+    fn mset(&self, items: Vec<(K, V)>) -> Result<()>;
+
+    /// Delete the given keys, ignoring missing ones
+    fn mdelete(&self, keys: &[K]) -> Result<()>;
+
+    /// Get the keys of the store, optionally only those starting with `prefix`
+    fn yield_keys(&self, prefix: Option<&str>) -> Result<Vec<K>>;
+
+    /// Async get the values of the given keys, `None` for missing keys
+    async fn amget(&self, keys: &[K]) -> Result<Vec<Option<V>>> {
+        self.mget(keys)
+    }
+
+    /// Async set the values of the given keys
+    async fn amset(&self, items: Vec<(K, V)>) -> Result<()> {
+        self.mset(items)
+    }
+
+    /// Async delete the given keys, ignoring missing ones
+    async fn amdelete(&self, keys: &[K]) -> Result<()> {
+        self.mdelete(keys)
+    }
+
+    /// Async get the keys of the store, optionally only those starting with `prefix`
+    async fn ayield_keys(&self, prefix: Option<&str>) -> Result<Vec<K>> {
+        self.yield_keys(prefix)
+    }
+}
+
+/// Store mapping string keys to bytes
+pub trait ByteStore: BaseStore<String, Vec<u8>> {}
+
+impl<T: BaseStore<String, Vec<u8>> + ?Sized> ByteStore for T {}
+
+/// Store keeping its values in memory
+///
+/// Clones share the same values.
+#[derive(Debug)]
+pub struct InMemoryStore<V> {
+    values: Arc<RwLock<HashMap<String, V>>>,
+}
+
+/// In-memory store of bytes
+pub type InMemoryByteStore = InMemoryStore<Vec<u8>>;
+
+impl<V> InMemoryStore<V> {
+    /// Create a new empty in-memory store
+    pub fn new() -> Self {
+        Self {
+            values: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Get the number of stored keys
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Check if the store is empty
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, V>> {
+        self.values.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, V>> {
+        self.values.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<V> Default for InMemoryStore<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Clone for InMemoryStore<V> {
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+        }
+    }
+}
+
+impl<V> BaseStore<String, V> for InMemoryStore<V>
+where
+    V: Clone + Send + Sync + 'static,
+{
+    fn mget(&self, keys: &[String]) -> Result<Vec<Option<V>>> {
+        let values = self.read();
+        Ok(keys.iter().map(|key| values.get(key).cloned()).collect())
+    }
+
+    fn mset(&self, items: Vec<(String, V)>) -> Result<()> {
+        self.write().extend(items);
+        Ok(())
+    }
+
+    fn mdelete(&self, keys: &[String]) -> Result<()> {
+        let mut values = self.write();
+        for key in keys {
+            values.remove(key);
+        }
+        Ok(())
+    }
+
+    fn yield_keys(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        let mut keys: Vec<String> = self
+            .read()
+            .keys()
+            .filter(|key| prefix.is_none_or(|prefix| key.starts_with(prefix)))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Byte store keeping every value in its own file under a root directory
+///
+/// Keys are relative paths of `/`-separated segments made of ASCII letters,
+/// digits, `-`, `_` and `.`, not starting with `.`, so they cannot escape the
+/// root directory. Paths resolving outside the root through symbolic links
+/// are rejected as well. Values are written to a temporary file and renamed
+/// into place.
+#[derive(Debug, Clone)]
+pub struct LocalFileStore {
+    root: PathBuf,
+}
+
+impl LocalFileStore {
+    /// Create a new file store, creating the root directory if needed
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        std::fs::create_dir_all(root.as_ref())?;
+        Ok(Self {
+            root: root.as_ref().canonicalize()?,
+        })
+    }
+
+    /// Get the root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Check that a key can be used as a path relative to the root
+    fn is_valid_key(key: &str) -> bool {
+        key.split('/').all(|segment| {
+            !segment.is_empty()
+                && !segment.starts_with('.')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
+    }
+
+    /// Get the path of the file holding a key
+    fn key_path(&self, key: &str) -> Result<PathBuf> {
+        if !Self::is_valid_key(key) {
+            return Err(FerricLinkError::validation(format!(
+                "Invalid store key '{key}': keys must be '/'-separated segments of ASCII letters, digits, '-', '_' and '.', not starting with '.'"
+            )));
+        }
+        Ok(self.root.join(key))
+    }
+
+    /// Check that an existing path resolves inside the root
+    fn check_inside_root(&self, path: &Path) -> Result<()> {
+        match path.canonicalize() {
+            Ok(resolved) if !resolved.starts_with(&self.root) => {
+                Err(FerricLinkError::validation(format!(
+                    "Store path '{}' resolves outside of '{}'",
+                    path.display(),
+                    self.root.display()
+                )))
+            }
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn read_value(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.key_path(key)?;
+        self.check_inside_root(&path)?;
+        match std::fs::read(&path) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_value(&self, key: &str, value: &[u8]) -> Result<()> {
+        let path = self.key_path(key)?;
+        let parent = path.parent().unwrap_or(&self.root);
+        // Check the deepest existing directory before creating the missing ones
+        if let Some(existing) = parent.ancestors().find(|ancestor| ancestor.exists()) {
+            self.check_inside_root(existing)?;
+        }
+        std::fs::create_dir_all(parent)?;
+        self.check_inside_root(&path)?;
+
+        let temp_path = parent.join(format!(".{}.tmp", uuid::Uuid::new_v4()));
+        let write = || -> Result<()> {
+            let mut file = std::fs::File::create(&temp_path)?;
+            std::io::Write::write_all(&mut file, value)?;
+            file.sync_all()?;
+            std::fs::rename(&temp_path, &path)?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn delete_value(&self, key: &str) -> Result<()> {
+        let path = self.key_path(key)?;
+        self.check_inside_root(&path)?;
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Collect the keys of the files under a directory, skipping hidden files and links
+    fn collect_keys(directory: &Path, prefix: &str, keys: &mut Vec<String>) -> Result<()> {
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let key = format!("{prefix}{name}");
+            let file_type = entry.file_type()?;
+            if !Self::is_valid_key(&key) {
+                continue;
+            } else if file_type.is_dir() {
+                Self::collect_keys(&entry.path(), &format!("{key}/"), keys)?;
+            } else if file_type.is_file() {
+                keys.push(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a function with a clone of the store on the blocking thread pool.
+    async fn run_blocking<T, F>(&self, func: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(LocalFileStore) -> Result<T> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || func(store))
+            .await
+            .map_err(|e| FerricLinkError::runtime(format!("Store task failed: {e}")))?
+    }
+}
+
+#[async_trait]
+impl BaseStore<String, Vec<u8>> for LocalFileStore {
+    fn mget(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        keys.iter().map(|key| self.read_value(key)).collect()
+    }
+
+    fn mset(&self, items: Vec<(String, Vec<u8>)>) -> Result<()> {
+        for (key, value) in items {
+            self.write_value(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    fn mdelete(&self, keys: &[String]) -> Result<()> {
+        for key in keys {
+            self.delete_value(key)?;
+        }
+        Ok(())
+    }
+
+    fn yield_keys(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        Self::collect_keys(&self.root, "", &mut keys)?;
+        if let Some(prefix) = prefix {
+            keys.retain(|key| key.starts_with(prefix));
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn amget(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        let keys = keys.to_vec();
+        self.run_blocking(move |store| store.mget(&keys)).await
+    }
+
+    async fn amset(&self, items: Vec<(String, Vec<u8>)>) -> Result<()> {
+        self.run_blocking(move |store| store.mset(items)).await
+    }
+
+    async fn amdelete(&self, keys: &[String]) -> Result<()> {
+        let keys = keys.to_vec();
+        self.run_blocking(move |store| store.mdelete(&keys)).await
+    }
+
+    async fn ayield_keys(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        let prefix = prefix.map(str::to_string);
+        self.run_blocking(move |store| store.yield_keys(prefix.as_deref()))
+            .await
+    }
+}
+
+/// Store of [`Serializable`] values, encoded as JSON in a byte store
+pub struct EncoderBackedStore<V> {
+    /// The underlying byte store
+    store: Arc<dyn ByteStore>,
+    _value: PhantomData<fn() -> V>,
+}
+
+impl<V: Serializable> EncoderBackedStore<V> {
+    /// Create a new store encoding its values into a byte store
+    pub fn new(store: Arc<dyn ByteStore>) -> Self {
+        Self {
+            store,
+            _value: PhantomData,
+        }
+    }
+
+    /// Get the underlying byte store
+    pub fn inner(&self) -> &Arc<dyn ByteStore> {
+        &self.store
+    }
+
+    fn encode(items: Vec<(String, V)>) -> Result<Vec<(String, Vec<u8>)>> {
+        items
+            .into_iter()
+            .map(|(key, value)| Ok((key, value.to_json()?.into_bytes())))
+            .collect()
+    }
+
+    fn decode(values: Vec<Option<Vec<u8>>>) -> Result<Vec<Option<V>>> {
+        values
+            .into_iter()
+            .map(|value| {
+                value
+                    .map(|bytes| {
+                        let json = String::from_utf8(bytes)
+                            .map_err(|e| FerricLinkError::generic(format!("Invalid UTF-8: {e}")))?;
+                        V::from_json(&json)
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+}
+
+impl<V> std::fmt::Debug for EncoderBackedStore<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncoderBackedStore")
+            .field("value_type", &std::any::type_name::<V>())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<V: Serializable> BaseStore<String, V> for EncoderBackedStore<V> {
+    fn mget(&self, keys: &[String]) -> Result<Vec<Option<V>>> {
+        Self::decode(self.store.mget(keys)?)
+    }
+
+    fn mset(&self, items: Vec<(String, V)>) -> Result<()> {
+        self.store.mset(Self::encode(items)?)
+    }
+
+    fn mdelete(&self, keys: &[String]) -> Result<()> {
+        self.store.mdelete(keys)
+    }
+
+    fn yield_keys(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        self.store.yield_keys(prefix)
+    }
+
+    async fn amget(&self, keys: &[String]) -> Result<Vec<Option<V>>> {
+        Self::decode(self.store.amget(keys).await?)
+    }
+
+    async fn amset(&self, items: Vec<(String, V)>) -> Result<()> {
+        self.store.amset(Self::encode(items)?).await
+    }
+
+    async fn amdelete(&self, keys: &[String]) -> Result<()> {
+        self.store.amdelete(keys).await
+    }
+
+    async fn ayield_keys(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+        self.store.ayield_keys(prefix).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::Document;
+
+    fn keys(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn check_store(store: &dyn ByteStore) {
+        let all = keys(&["a", "nested/b"]);
+        assert_eq!(store.mget(&all).unwrap(), vec![None, None]);
+
+        store
+            .mset(vec![
+                ("a".to_string(), b"1".to_vec()),
+                ("nested/b".to_string(), b"2".to_vec()),
+            ])
+            .unwrap();
+        assert_eq!(
+            store.mget(&all).unwrap(),
+            vec![Some(b"1".to_vec()), Some(b"2".to_vec())]
+        );
+        assert_eq!(store.yield_keys(None).unwrap(), all);
+        assert_eq!(
+            store.yield_keys(Some("nested/")).unwrap(),
+            keys(&["nested/b"])
+        );
+
+        store.mset(vec![("a".to_string(), b"3".to_vec())]).unwrap();
+        store.mdelete(&keys(&["nested/b", "missing"])).unwrap();
+        assert_eq!(store.mget(&all).unwrap(), vec![Some(b"3".to_vec()), None]);
+    }
+
+    async fn check_store_async(store: &dyn ByteStore) {
+        store
+            .amset(vec![("x/y".to_string(), b"4".to_vec())])
+            .await
+            .unwrap();
+        assert_eq!(
+            store.amget(&keys(&["x/y", "z"])).await.unwrap(),
+            vec![Some(b"4".to_vec()), None]
+        );
+        assert_eq!(store.ayield_keys(Some("x")).await.unwrap(), keys(&["x/y"]));
+        store.amdelete(&keys(&["x/y"])).await.unwrap();
+        assert!(store.ayield_keys(Some("x")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let store = InMemoryByteStore::new();
+        check_store(&store);
+        check_store_async(&store).await;
+        assert_eq!(store.len(), 1);
+
+        // Clones share their values
+        let values: InMemoryStore<usize> = InMemoryStore::new();
+        values.clone().mset(vec![("one".to_string(), 1)]).unwrap();
+        assert_eq!(values.mget(&keys(&["one"])).unwrap(), vec![Some(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_local_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalFileStore::new(dir.path()).unwrap();
+        check_store(&store);
+        check_store_async(&store).await;
+
+        // Values survive reopening the store
+        let reopened = LocalFileStore::new(dir.path()).unwrap();
+        assert_eq!(
+            reopened.mget(&keys(&["a"])).unwrap(),
+            vec![Some(b"3".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_local_file_store_rejects_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let store = LocalFileStore::new(&root).unwrap();
+
+        for key in [
+            "../escape",
+            "/absolute",
+            "a/../../b",
+            "",
+            "a//b",
+            ".hidden",
+            "a\\b",
+        ] {
+            assert!(
+                store.mset(vec![(key.to_string(), b"x".to_vec())]).is_err(),
+                "key {key:?} should be rejected"
+            );
+            assert!(store.mget(&[key.to_string()]).is_err());
+        }
+        assert!(!dir.path().join("escape").exists());
+        assert!(store.yield_keys(None).unwrap().is_empty());
+
+        #[cfg(unix)]
+        {
+            let outside = dir.path().join("outside");
+            std::fs::create_dir(&outside).unwrap();
+            std::fs::write(outside.join("secret"), b"s").unwrap();
+            std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+            assert!(store.mget(&keys(&["link/secret"])).is_err());
+            assert!(
+                store
+                    .mset(vec![("link/new".to_string(), b"x".to_vec())])
+                    .is_err()
+            );
+            assert!(!outside.join("new").exists());
+            assert!(
+                store
+                    .mset(vec![("link/sub/new".to_string(), b"x".to_vec())])
+                    .is_err()
+            );
+            assert!(!outside.join("sub").exists());
+            assert!(store.yield_keys(None).unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encoder_backed_store() {
+        let bytes = Arc::new(InMemoryByteStore::new());
+        let store: EncoderBackedStore<Document> = EncoderBackedStore::new(bytes.clone());
+
+        let document = Document::new("content");
+        store
+            .amset(vec![("doc-1".to_string(), document.clone())])
+            .await
+            .unwrap();
+        assert_eq!(
+            store.amget(&keys(&["doc-1", "doc-2"])).await.unwrap(),
+            vec![Some(document.clone()), None]
+        );
+        assert_eq!(
+            bytes.mget(&keys(&["doc-1"])).unwrap(),
+            vec![Some(document.to_json().unwrap().into_bytes())]
+        );
+        assert_eq!(store.yield_keys(Some("doc")).unwrap(), keys(&["doc-1"]));
+
+        bytes
+            .mset(vec![("bad".to_string(), b"not json".to_vec())])
+            .unwrap();
+        assert!(store.mget(&keys(&["bad"])).is_err());
+
+        store.mdelete(&keys(&["doc-1"])).unwrap();
+        assert_eq!(bytes.len(), 1);
+    }
+}
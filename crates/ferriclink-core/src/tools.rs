@@ -8,8 +8,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::errors::Result;
+use crate::graph::{Graph, Node};
 use crate::impl_serializable;
-use crate::runnables::{Runnable, RunnableConfig};
+use crate::runnables::{Runnable, RunnableConfig, run_with_callbacks, to_callback_value};
 
 /// A tool call made by a language model
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -285,9 +286,42 @@ where
         input: HashMap<String, serde_json::Value>,
         config: Option<RunnableConfig>,
     ) -> Result<ToolResult> {
-        let mut result = self.tool.invoke(input, config).await?;
-        result.tool_call_id = self.tool_call_id.clone();
-        Ok(result)
+        run_with_callbacks(
+            config,
+            self.name(),
+            "tool",
+            to_callback_value(&input),
+            to_callback_value,
+            |run_config| async move {
+                let mut result = run_config
+                    .run_cancellable(self.tool.invoke(input, Some(run_config.clone())))
+                    .await?;
+                result.tool_call_id = self.tool_call_id.clone();
+                Ok(result)
+            },
+        )
+        .await
+    }
+
+    fn name(&self) -> String {
+        self.tool.name().to_string()
+    }
+
+    fn input_schema(&self) -> Option<serde_json::Value> {
+        self.tool.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<serde_json::Value> {
+        self.tool.output_schema()
+    }
+
+    fn get_graph(&self) -> Graph {
+        Graph::from_node(
+            Node::new(self.name())
+                .with_component_type("tool")
+                .with_input_schema(self.input_schema())
+                .with_output_schema(self.output_schema()),
+        )
     }
 }
 
@@ -328,6 +362,13 @@ impl ToolCollection {
         &self.tools
     }
 
+    /// Get the schemas of all tools, sorted by name
+    pub fn schemas(&self) -> Vec<ToolSchema> {
+        let mut schemas: Vec<_> = self.tools.values().map(|tool| tool.schema()).collect();
+        schemas.sort_by(|a, b| a.name.cmp(&b.name));
+        schemas
+    }
+
     /// Get the number of tools
     pub fn len(&self) -> usize {
         self.tools.len()
@@ -349,7 +390,19 @@ impl ToolCollection {
             crate::errors::FerricLinkError::generic(format!("Tool '{name}' not found"))
         })?;
 
-        tool.invoke(input, config).await
+        run_with_callbacks(
+            config,
+            name,
+            "tool",
+            to_callback_value(&input),
+            to_callback_value,
+            |run_config| async move {
+                run_config
+                    .run_cancellable(tool.invoke(input, Some(run_config.clone())))
+                    .await
+            },
+        )
+        .await
     }
 }
 
@@ -388,6 +441,7 @@ where
 mod tests {
     use super::*;
     use crate::serializable::Serializable;
+    use std::sync::Arc;
 
     #[test]
     fn test_tool_call() {
@@ -494,6 +548,26 @@ mod tests {
         assert_eq!(result.content, "9");
     }
 
+    #[tokio::test]
+    async fn test_tool_collection_cancelled() {
+        let mut collection = ToolCollection::new();
+        collection.add_tool(function_tool(
+            "echo",
+            "Echo input",
+            |_| Ok("ok".to_string()),
+        ));
+
+        let token = crate::runnables::CancellationToken::new();
+        token.cancel();
+        let config = RunnableConfig::new().with_cancellation_token(token);
+
+        let error = collection
+            .invoke_tool("echo", HashMap::new(), Some(config))
+            .await
+            .unwrap_err();
+        assert!(error.is_cancelled());
+    }
+
     #[tokio::test]
     async fn test_runnable_tool() {
         let tool = function_tool("test", "Test tool", |_| Ok("test result".to_string()));
@@ -506,6 +580,46 @@ mod tests {
         assert_eq!(result.content, "test result");
     }
 
+    #[tokio::test]
+    async fn test_runnable_tool_cancelled() {
+        let tool = function_tool("echo", "Echo input", |_| Ok("ok".to_string()));
+        let runnable_tool = RunnableTool::new(tool, "call_1");
+
+        let token = crate::runnables::CancellationToken::new();
+        token.cancel();
+        let config = RunnableConfig::new().with_cancellation_token(token);
+        let error = runnable_tool
+            .invoke(HashMap::new(), Some(config))
+            .await
+            .unwrap_err();
+        assert!(error.is_cancelled());
+
+        let expired = RunnableConfig::new().with_timeout(std::time::Duration::ZERO);
+        let error = runnable_tool
+            .invoke(HashMap::new(), Some(expired))
+            .await
+            .unwrap_err();
+        assert!(error.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn test_runnable_tool_callbacks() {
+        let handler = Arc::new(crate::callbacks::MemoryCallbackHandler::new());
+        let tool = function_tool("echo", "Echo tool", |_| Ok("echoed".to_string()));
+        let runnable_tool = RunnableTool::new(tool, "call_1");
+
+        let config = RunnableConfig::new().with_callback(handler.clone());
+        runnable_tool
+            .invoke(HashMap::new(), Some(config))
+            .await
+            .unwrap();
+
+        let runs = handler.get_runs_by_type("tool").await;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].name, "echo");
+        assert_eq!(runs[0].output.as_ref().unwrap()["content"], "echoed");
+    }
+
     #[test]
     fn test_serialization() {
         let call = ToolCall::new("call_123", "test_tool");
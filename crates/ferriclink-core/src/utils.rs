@@ -70,3 +70,40 @@ pub fn get_colored_text(text: &str, color: &str) -> String {
 pub fn get_bolded_text(text: &str) -> String {
     format!("{}{}{}", colors::BOLD, text, colors::RESET)
 }
+
+/// Check that a name can be used as a file name without escaping its directory
+///
+/// Only non-empty names made of ASCII letters, digits, `-` and `_` are accepted.
+pub fn is_safe_file_stem(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Hash the parts of a key into a hex SHA-256 digest
+///
+/// Every part is prefixed with its length, so that parts containing any
+/// delimiter cannot collide, and large inputs are not stored as keys.
+pub fn hash_key_parts(parts: &[&str]) -> String {
+    use sha2::Digest;
+
+    let mut hasher = sha2::Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Get the unqualified name of a type, without module path or generic parameters
+pub fn short_type_name<T: ?Sized>() -> String {
+    let full = std::any::type_name::<T>();
+    let base = full.split('<').next().unwrap_or(full);
+    base.rsplit("::").next().unwrap_or(base).to_string()
+}
+
+/// Approximate the number of tokens of a text, assuming 4 characters per token
+pub fn approximate_token_count(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}